@@ -0,0 +1,444 @@
+// ClipForge - Audio Export Module
+// Audio-only exports (MP3, M4A, Opus, FLAC, WAV) of a clip list with tags and chapter markers
+
+use std::fs;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::get_ffmpeg_path;
+use crate::audio_tracks::split_edit_ranges;
+use crate::export::{parse_ffmpeg_error, push_main_clip_audio, ClipData};
+use crate::export_plan::{run_export_plan, ExportPlan};
+use crate::export_progress::RunningExport;
+use crate::export_settings::ffmpeg_has_encoder;
+use crate::loudness::{
+    apply_mix_loudness, loudness_pass_args, main_clip_audio, normalize_clip_loudness, plan_with_loudness,
+    LoudnessSettings, LoudnessTarget,
+};
+
+const MP3_SAMPLE_RATES: &[u32] = &[8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000];
+const AAC_SAMPLE_RATES: &[u32] = &[8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 88200, 96000];
+const OPUS_SAMPLE_RATES: &[u32] = &[8000, 12000, 16000, 24000, 48000];
+const PCM_SAMPLE_RATES: &[u32] = &[8000, 11025, 16000, 22050, 32000, 44100, 48000, 88200, 96000, 176400, 192000];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    #[default]
+    Mp3,
+    /// AAC in an MP4 audio container
+    M4a,
+    Opus,
+    Flac,
+    Wav,
+}
+
+impl AudioFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::M4a => "m4a",
+            AudioFormat::Opus => "opus",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Wav => "wav",
+        }
+    }
+
+    /// FFmpeg muxer name; "ipod" is the MP4 muxer variant that writes .m4a files
+    fn muxer(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::M4a => "ipod",
+            AudioFormat::Opus => "opus",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Wav => "wav",
+        }
+    }
+
+    fn encoder(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "libmp3lame",
+            AudioFormat::M4a => "aac",
+            AudioFormat::Opus => "libopus",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Wav => "pcm_s16le",
+        }
+    }
+
+    /// Bitrate used when none is set; None for lossless formats, which ignore bitrates
+    fn default_bitrate_kbps(&self) -> Option<u32> {
+        match self {
+            AudioFormat::Mp3 => Some(192),
+            AudioFormat::M4a => Some(160),
+            AudioFormat::Opus => Some(96),
+            AudioFormat::Flac | AudioFormat::Wav => None,
+        }
+    }
+
+    fn sample_rates(&self) -> &'static [u32] {
+        match self {
+            AudioFormat::Mp3 => MP3_SAMPLE_RATES,
+            AudioFormat::M4a => AAC_SAMPLE_RATES,
+            AudioFormat::Opus => OPUS_SAMPLE_RATES,
+            AudioFormat::Flac | AudioFormat::Wav => PCM_SAMPLE_RATES,
+        }
+    }
+}
+
+/// A chapter marker, written as ID3 CHAP frames, MP4 chapters or Vorbis comments depending on the format
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AudioChapter {
+    pub title: String,
+    /// Seconds from the start of the export
+    pub start: f64,
+    /// Defaults to the next chapter's start, or the end of the export
+    pub end: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AudioMetadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub date: Option<String>,
+    pub comment: Option<String>,
+    pub chapters: Vec<AudioChapter>,
+}
+
+impl AudioMetadata {
+    /// Global tags under FFmpeg's generic keys, which each muxer maps to its own tag names
+    fn tags(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("title", &self.title),
+            ("artist", &self.artist),
+            ("album", &self.album),
+            ("date", &self.date),
+            ("comment", &self.comment),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value.as_deref()?)))
+        .collect()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tags().is_empty() && self.chapters.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AudioExportSettings {
+    pub format: AudioFormat,
+    /// Lossy formats only; each format has its own default
+    #[serde(rename = "bitrateKbps")]
+    pub bitrate_kbps: Option<u32>,
+    /// Output sample rate; the 48kHz mix rate when unset
+    #[serde(rename = "sampleRate")]
+    pub sample_rate: Option<u32>,
+    pub metadata: AudioMetadata,
+    pub loudness: Option<LoudnessSettings>,
+}
+
+impl AudioExportSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(rate) = self.sample_rate {
+            if !self.format.sample_rates().contains(&rate) {
+                return Err(format!("{} export does not support a {} Hz sample rate",
+                    self.format.extension().to_uppercase(), rate));
+            }
+        }
+        if let Some(bitrate) = self.bitrate_kbps {
+            if !(32..=512).contains(&bitrate) {
+                return Err("Audio bitrate must be between 32 and 512 kbps".to_string());
+            }
+        }
+        if let Some(loudness) = &self.loudness {
+            loudness.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Encoder and muxer options placed just before the output path
+    fn output_args(&self) -> Vec<String> {
+        let mut args = vec!["-c:a".to_string(), self.format.encoder().to_string()];
+        if let Some(bitrate) = self.format.default_bitrate_kbps().map(|d| self.bitrate_kbps.unwrap_or(d)) {
+            args.extend(["-b:a".to_string(), format!("{}k", bitrate)]);
+        }
+        if let Some(rate) = self.sample_rate {
+            args.extend(["-ar".to_string(), rate.to_string()]);
+        }
+        match self.format {
+            // ID3v2.3 is the version most podcast apps and players read, chapters included
+            AudioFormat::Mp3 => args.extend(["-id3v2_version", "3"].map(String::from)),
+            AudioFormat::M4a => args.extend(["-movflags", "+faststart"].map(String::from)),
+            _ => {}
+        }
+        args.extend(["-f".to_string(), self.format.muxer().to_string()]);
+        args
+    }
+}
+
+impl LoudnessTarget for AudioExportSettings {
+    fn loudness_mut(&mut self) -> Option<&mut LoudnessSettings> {
+        self.loudness.as_mut()
+    }
+}
+
+/// FFMETADATA values escape the characters the format treats as syntax
+fn escape_metadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// FFMETADATA1 document with the tags and chapters of an export `duration` seconds long
+fn ffmetadata(metadata: &AudioMetadata, duration: f64) -> Result<String, String> {
+    let mut lines = vec![";FFMETADATA1".to_string()];
+    for (key, value) in metadata.tags() {
+        lines.push(format!("{}={}", key, escape_metadata(value)));
+    }
+
+    let mut chapters: Vec<&AudioChapter> = metadata.chapters.iter().collect();
+    chapters.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    for (i, chapter) in chapters.iter().enumerate() {
+        let end = chapter.end
+            .or_else(|| chapters.get(i + 1).map(|next| next.start))
+            .unwrap_or(duration)
+            .min(duration);
+        if chapter.start < 0.0 || end <= chapter.start {
+            return Err(format!("Chapter '{}' does not fit inside the {:.1}s export", chapter.title, duration));
+        }
+
+        lines.push("[CHAPTER]".to_string());
+        lines.push("TIMEBASE=1/1000".to_string());
+        lines.push(format!("START={}", (chapter.start * 1000.0).round() as i64));
+        lines.push(format!("END={}", (end * 1000.0).round() as i64));
+        lines.push(format!("title={}", escape_metadata(&chapter.title)));
+    }
+
+    Ok(lines.join("\n") + "\n")
+}
+
+/// Temp file for an export's metadata, unique per output file
+fn metadata_path(output_path: &str) -> Result<PathBuf, String> {
+    let metadata_dir = std::env::temp_dir().join("clipforge_metadata");
+    fs::create_dir_all(&metadata_dir)
+        .map_err(|e| format!("Failed to create metadata directory: {}", e))?;
+
+    Ok(metadata_dir.join(format!("{:x}.txt", md5::compute(output_path.as_bytes()))))
+}
+
+/// Join the clips' audio into `out`, cross-fading where a clip has a transition into the next one.
+/// Returns the joined duration; each transition overlaps two clips and shortens it by its length.
+fn build_audio_join(filter_parts: &mut Vec<String>, durations: &[f64], clips: &[ClipData], out: &str) -> f64 {
+    if clips[..clips.len() - 1].iter().all(|c| c.transition.is_none()) {
+        let inputs: String = (0..clips.len()).map(|i| format!("[a{}]", i)).collect();
+        filter_parts.push(format!("{}concat=n={}:v=0:a=1{}", inputs, clips.len(), out));
+        return durations.iter().sum();
+    }
+
+    let mut audio = "[a0]".to_string();
+    let mut duration = durations[0];
+    // Part of the previous clip already used by its incoming transition
+    let mut incoming_overlap = 0.0;
+
+    for i in 1..clips.len() {
+        let audio_out = if i == clips.len() - 1 { out.to_string() } else { format!("[audio_join{}]", i) };
+        let overlap = clips[i - 1].transition.as_ref()
+            .map(|t| t.duration.min(durations[i - 1] - incoming_overlap).min(durations[i]))
+            .filter(|d| *d > 0.0);
+
+        match overlap {
+            Some(overlap) => {
+                filter_parts.push(format!("{}[a{}]acrossfade=d={}{}", audio, i, overlap, audio_out));
+                duration += durations[i] - overlap;
+                incoming_overlap = overlap;
+            }
+            None => {
+                filter_parts.push(format!("{}[a{}]concat=n=2:v=0:a=1{}", audio, i, audio_out));
+                duration += durations[i];
+                incoming_overlap = 0.0;
+            }
+        }
+        audio = audio_out;
+    }
+
+    duration
+}
+
+/// Build the FFmpeg arguments for an audio-only export of a clip list
+pub fn build_audio_export_plan(
+    clips: &[ClipData],
+    output_path: &str,
+    settings: &AudioExportSettings,
+) -> Result<ExportPlan, String> {
+    if clips.is_empty() {
+        return Err("No clips to export".to_string());
+    }
+    settings.validate()?;
+
+    let mut cmd_args = Vec::new();
+    for clip in clips {
+        cmd_args.push("-i".to_string());
+        cmd_args.push(clip.path.clone());
+    }
+
+    let mut filter_parts = Vec::new();
+    let mut durations = Vec::new();
+    let video_ranges: Vec<(f64, f64)> = clips.iter()
+        .map(|c| (c.source_offset.unwrap_or(c.in_point), c.out_point - c.in_point))
+        .collect();
+    // J/L cuts still move the edit points when there is no picture
+    for (i, (clip, (trim_start, trim_duration))) in clips.iter().zip(split_edit_ranges(clips, &video_ranges)).enumerate() {
+        push_main_clip_audio(&mut filter_parts, clip, i, trim_start, trim_duration, &format!("[a{}]", i))?;
+        durations.push(trim_duration);
+    }
+
+    let total_duration = build_audio_join(&mut filter_parts, &durations, clips, "[outa]");
+    let audio_output = apply_mix_loudness(&mut filter_parts, "[outa]".to_string(), settings.loudness.as_ref());
+
+    // Tags and chapters come from a generated metadata input; without one, don't inherit the
+    // first source's tags and chapters
+    let metadata_input = if settings.metadata.is_empty() {
+        "-1".to_string()
+    } else {
+        let path = metadata_path(output_path)?;
+        fs::write(&path, ffmetadata(&settings.metadata, total_duration)?)
+            .map_err(|e| format!("Failed to write export metadata: {}", e))?;
+        cmd_args.extend(["-f".to_string(), "ffmetadata".to_string(), "-i".to_string(), path.to_string_lossy().to_string()]);
+        clips.len().to_string()
+    };
+
+    cmd_args.push("-filter_complex".to_string());
+    cmd_args.push(filter_parts.join(";"));
+    cmd_args.push("-map".to_string());
+    cmd_args.push(audio_output);
+    cmd_args.extend(["-map_metadata".to_string(), metadata_input.clone(), "-map_chapters".to_string(), metadata_input]);
+
+    let loudness_pass_args = loudness_pass_args(&cmd_args, settings.loudness.as_ref());
+    let mut args = cmd_args;
+    args.extend(settings.output_args());
+    args.push("-y".to_string());
+    args.push(output_path.to_string());
+
+    Ok(ExportPlan {
+        args,
+        first_pass_args: None,
+        loudness_pass_args,
+        total_duration,
+        width: 0,
+        height: 0,
+    })
+}
+
+/// Export the clip list's audio only, e.g. a podcast episode cut from video recordings
+#[tauri::command]
+pub async fn export_audio(
+    app: AppHandle,
+    mut clips: Vec<ClipData>,
+    output_path: String,
+    export_id: Option<String>,
+    settings: Option<AudioExportSettings>,
+) -> Result<String, String> {
+    if clips.is_empty() {
+        return Err("No clips to export".to_string());
+    }
+
+    let ffmpeg_path = get_ffmpeg_path()?;
+    let settings = settings.unwrap_or_default();
+    let encoder = settings.format.encoder();
+    if !ffmpeg_has_encoder(encoder) {
+        return Err(format!("{} export requires an FFmpeg build with {}", settings.format.extension().to_uppercase(), encoder));
+    }
+
+    let export_id = export_id.unwrap_or_else(|| output_path.clone());
+    let export = RunningExport::start(&app, &export_id)?;
+    normalize_clip_loudness(&export, main_clip_audio(&mut clips), settings.loudness.as_ref()).await?;
+    let plan = plan_with_loudness(&export, &ffmpeg_path, &settings, |s| {
+        build_audio_export_plan(&clips, &output_path, s)
+    }).await?;
+
+    let output = run_export_plan(&export, &ffmpeg_path, &plan, &output_path).await;
+    if let Ok(path) = metadata_path(&output_path) {
+        let _ = fs::remove_file(path);
+    }
+
+    let output = output?;
+    if !output.success {
+        return Err(parse_ffmpeg_error(&output.stderr));
+    }
+
+    Ok("Audio export completed successfully".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffmetadata_tags_and_chapters() {
+        let metadata = AudioMetadata {
+            title: Some("Episode 12; the = sign".to_string()),
+            artist: Some("ClipForge".to_string()),
+            chapters: vec![
+                AudioChapter { title: "Outro".to_string(), start: 90.0, end: None },
+                AudioChapter { title: "Intro".to_string(), start: 0.0, end: None },
+            ],
+            ..Default::default()
+        };
+
+        let document = ffmetadata(&metadata, 120.5).unwrap();
+        assert_eq!(document, "\
+;FFMETADATA1
+title=Episode 12\\; the \\= sign
+artist=ClipForge
+[CHAPTER]
+TIMEBASE=1/1000
+START=0
+END=90000
+title=Intro
+[CHAPTER]
+TIMEBASE=1/1000
+START=90000
+END=120500
+title=Outro
+");
+
+        let late = AudioMetadata {
+            chapters: vec![AudioChapter { title: "Bonus".to_string(), start: 130.0, end: None }],
+            ..Default::default()
+        };
+        assert!(ffmetadata(&late, 120.5).is_err());
+    }
+
+    #[test]
+    fn test_audio_plan_crossfades_and_encodes_mp3() {
+        let clips: Vec<ClipData> = serde_json::from_value(serde_json::json!([
+            { "path": "a.mp4", "duration": 10.0, "inPoint": 0.0, "outPoint": 10.0, "volume": 100.0, "muted": false,
+              "transition": { "type": "fade", "duration": 1.0 } },
+            { "path": "b.mp4", "duration": 5.0, "inPoint": 0.0, "outPoint": 5.0, "volume": 100.0, "muted": false }
+        ])).unwrap();
+        let settings = AudioExportSettings { sample_rate: Some(44100), ..Default::default() };
+
+        let plan = build_audio_export_plan(&clips, "/tmp/episode.mp3", &settings).unwrap();
+
+        assert_eq!(plan.total_duration, 14.0);
+        assert!(plan.args.iter().any(|a| a.ends_with("[a0][a1]acrossfade=d=1[outa]")));
+        let tail: Vec<&str> = plan.args.iter().rev().take(16).rev().map(String::as_str).collect();
+        assert_eq!(tail, [
+            "-map_metadata", "-1", "-map_chapters", "-1", "-c:a", "libmp3lame", "-b:a", "192k",
+            "-ar", "44100", "-id3v2_version", "3", "-f", "mp3", "-y", "/tmp/episode.mp3",
+        ]);
+
+        let invalid = AudioExportSettings { format: AudioFormat::Opus, sample_rate: Some(44100), ..Default::default() };
+        assert!(build_audio_export_plan(&clips, "/tmp/episode.opus", &invalid).is_err());
+    }
+}
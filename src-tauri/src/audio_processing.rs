@@ -0,0 +1,272 @@
+// ClipForge - Audio Processing Module
+// Per-clip voice cleanup (noise reduction, high-pass, EQ, de-esser, compressor) and rendered previews
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use serde::{Deserialize, Serialize};
+
+use crate::{get_ffmpeg_path, get_models_dir};
+use crate::export::{build_clip_audio_filter, has_audio_stream, ClipData};
+use crate::text_overlay::escape_filter_value;
+
+/// RNNoise model file expected in the bundled models directory
+const RNNOISE_MODEL: &str = "rnnoise.rnnn";
+const DEFAULT_PREVIEW_SECONDS: f64 = 10.0;
+const MAX_PREVIEW_SECONDS: f64 = 30.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoiseReductionMethod {
+    /// Spectral denoiser (afftdn); good for steady hum and fan noise
+    #[default]
+    Fft,
+    /// Neural speech denoiser (arnndn) using the bundled RNNoise model
+    Rnnoise,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NoiseReduction {
+    pub method: NoiseReductionMethod,
+    /// 0 to 1
+    pub strength: f64,
+}
+
+impl Default for NoiseReduction {
+    fn default() -> Self {
+        Self {
+            method: NoiseReductionMethod::Fft,
+            strength: 0.5,
+        }
+    }
+}
+
+impl NoiseReduction {
+    fn filter(&self) -> Result<String, String> {
+        self.filter_with_models(get_models_dir)
+    }
+
+    /// `models_dir` is only looked up for RNNoise, so FFT works without a models directory
+    fn filter_with_models(&self, models_dir: impl FnOnce() -> Result<PathBuf, String>) -> Result<String, String> {
+        let strength = self.strength.clamp(0.0, 1.0);
+        match self.method {
+            NoiseReductionMethod::Fft => Ok(format!("afftdn=nr={}:nf=-50:tn=1", 6.0 + strength * 24.0)),
+            NoiseReductionMethod::Rnnoise => {
+                let model = models_dir()?.join(RNNOISE_MODEL);
+                if !model.is_file() {
+                    return Err(format!(
+                        "RNNoise noise reduction needs the {} model in the bundled models directory; use the FFT method instead",
+                        RNNOISE_MODEL
+                    ));
+                }
+                Ok(format!("arnndn=m={}:mix={}", escape_filter_value(&model.to_string_lossy()), strength))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Compressor {
+    #[serde(rename = "thresholdDb")]
+    pub threshold_db: f64,
+    pub ratio: f64,
+    #[serde(rename = "attackMs")]
+    pub attack_ms: f64,
+    #[serde(rename = "releaseMs")]
+    pub release_ms: f64,
+    #[serde(rename = "makeupDb")]
+    pub makeup_db: f64,
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        Self {
+            threshold_db: -18.0,
+            ratio: 3.0,
+            attack_ms: 20.0,
+            release_ms: 250.0,
+            makeup_db: 2.0,
+        }
+    }
+}
+
+impl Compressor {
+    /// acompressor takes the threshold and makeup gain as linear amplitudes
+    fn filter(&self) -> String {
+        let linear = |db: f64| 10f64.powf(db / 20.0);
+        format!("acompressor=threshold={}:ratio={}:attack={}:release={}:makeup={}",
+            linear(self.threshold_db.clamp(-60.0, 0.0)),
+            self.ratio.clamp(1.0, 20.0),
+            self.attack_ms.clamp(0.01, 2000.0),
+            self.release_ms.clamp(0.01, 9000.0),
+            linear(self.makeup_db.clamp(0.0, 24.0)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EqPreset {
+    /// Less low-mid mud, more presence and air
+    VoiceClarity,
+    /// Fuller low end, softer highs for thin or harsh microphones
+    Warmth,
+    /// Radio-style: low shelf lift, scooped mids, presence boost
+    Broadcast,
+}
+
+impl EqPreset {
+    fn filter(&self) -> &'static str {
+        match self {
+            EqPreset::VoiceClarity => "equalizer=f=250:t=q:w=1:g=-3,equalizer=f=3500:t=q:w=1.2:g=3,highshelf=f=10000:g=1.5",
+            EqPreset::Warmth => "lowshelf=f=150:g=2.5,equalizer=f=3000:t=q:w=1:g=-1.5,highshelf=f=8000:g=-2",
+            EqPreset::Broadcast => "lowshelf=f=100:g=2,equalizer=f=400:t=q:w=1:g=-2.5,equalizer=f=5000:t=q:w=1:g=2.5",
+        }
+    }
+}
+
+/// Audio cleanup applied to a clip before it is joined onto the track
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AudioProcessing {
+    #[serde(rename = "noiseReduction")]
+    pub noise_reduction: Option<NoiseReduction>,
+    /// Cutoff in Hz for rumble and hum below the voice
+    #[serde(rename = "highPassHz")]
+    pub high_pass_hz: Option<f64>,
+    pub eq: Option<EqPreset>,
+    /// De-esser intensity, 0 to 1
+    #[serde(rename = "deEsser")]
+    pub de_esser: Option<f64>,
+    pub compressor: Option<Compressor>,
+}
+
+impl AudioProcessing {
+    pub fn is_empty(&self) -> bool {
+        matches!(self.filter(), Ok(None))
+    }
+
+    /// Filter chain in processing order: remove rumble and noise first so the EQ and compressor
+    /// don't bring them up, and compress last so it sees the final tone
+    pub fn filter(&self) -> Result<Option<String>, String> {
+        let mut filters = Vec::new();
+        if let Some(cutoff) = self.high_pass_hz {
+            filters.push(format!("highpass=f={}:poles=2", cutoff.clamp(20.0, 1000.0)));
+        }
+        if let Some(noise_reduction) = &self.noise_reduction {
+            filters.push(noise_reduction.filter()?);
+        }
+        if let Some(eq) = self.eq {
+            filters.push(eq.filter().to_string());
+        }
+        if let Some(intensity) = self.de_esser.filter(|i| *i > 0.0) {
+            filters.push(format!("deesser=i={}", intensity.min(1.0)));
+        }
+        if let Some(compressor) = &self.compressor {
+            filters.push(compressor.filter());
+        }
+        Ok((!filters.is_empty()).then(|| filters.join(",")))
+    }
+}
+
+/// Temp WAV for a preview, keyed by everything that affects how it sounds
+fn preview_path(clip: &ClipData, start: f64, duration: f64) -> Result<PathBuf, String> {
+    let preview_dir = std::env::temp_dir().join("clipforge_preview");
+    fs::create_dir_all(&preview_dir)
+        .map_err(|e| format!("Failed to create preview directory: {}", e))?;
+
+    let key = format!("{}|{}|{}|{}|{}", clip.path, clip.volume, start, duration,
+        serde_json::to_string(&clip.audio_processing).unwrap_or_default());
+    Ok(preview_dir.join(format!("{:x}.wav", md5::compute(key.as_bytes()))))
+}
+
+/// Render a short WAV of a clip's audio with its processing applied. `start` is in clip time and
+/// defaults to the beginning of the clip. Returns the path of the rendered preview.
+#[tauri::command]
+pub async fn preview_audio_processing(
+    clip: ClipData,
+    start: Option<f64>,
+    duration: Option<f64>,
+) -> Result<String, String> {
+    if !has_audio_stream(&clip.path) {
+        return Err("Clip has no audio to preview".to_string());
+    }
+
+    let clip_length = (clip.out_point - clip.in_point).max(0.0);
+    let start = start.unwrap_or(0.0).clamp(0.0, clip_length);
+    let duration = duration.unwrap_or(DEFAULT_PREVIEW_SECONDS).min(MAX_PREVIEW_SECONDS).min(clip_length - start);
+    if duration <= 0.0 {
+        return Err("Preview range is outside the clip".to_string());
+    }
+
+    let output_path = preview_path(&clip, start, duration)?;
+    if output_path.exists() {
+        return Ok(output_path.to_string_lossy().to_string());
+    }
+
+    // Same gain and format as the export graph, so the preview sounds like the export. Seeking the
+    // input instead of trimming keeps previews deep into long recordings fast.
+    let seek = clip.source_offset.unwrap_or(clip.in_point) + start;
+    let mut filter = build_clip_audio_filter(0, true, 0.0, duration, clip.volume, false, "");
+    if let Some(processing) = clip.audio_processing.as_ref().map(AudioProcessing::filter).transpose()?.flatten() {
+        filter = format!("{},{}", filter, processing);
+    }
+
+    let ffmpeg_path = get_ffmpeg_path()?;
+    let output = Command::new(&ffmpeg_path)
+        .args(["-hide_banner", "-nostats", "-ss", &seek.to_string(), "-i", &clip.path, "-filter_complex", &format!("{}[preview]", filter)])
+        .args(["-map", "[preview]", "-c:a", "pcm_s16le", "-y"])
+        .arg(&output_path)
+        .output()
+        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
+
+    if !output.status.success() {
+        let _ = fs::remove_file(&output_path);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Audio preview failed: {}", stderr.lines().last().unwrap_or("unknown error")));
+    }
+
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_processing_chain_order() {
+        let processing: AudioProcessing = serde_json::from_value(serde_json::json!({
+            "compressor": { "thresholdDb": -20.0, "ratio": 4.0, "makeupDb": 0.0 },
+            "deEsser": 0.4,
+            "eq": "voiceClarity",
+            "highPassHz": 80.0,
+            "noiseReduction": { "method": "fft", "strength": 0.5 }
+        })).unwrap();
+
+        let filter = processing.filter().unwrap().unwrap();
+        let names: Vec<&str> = filter.split(',').filter_map(|f| f.split('=').next()).collect();
+        assert_eq!(names, ["highpass", "afftdn", "equalizer", "equalizer", "highshelf", "deesser", "acompressor"]);
+        assert!(filter.contains("afftdn=nr=18:nf=-50:tn=1"));
+        assert!(filter.contains("acompressor=threshold=0.1:ratio=4:attack=20:release=250:makeup=1"));
+
+        assert!(AudioProcessing::default().is_empty());
+    }
+
+    #[test]
+    fn test_rnnoise_uses_model_or_fails() {
+        let noise_reduction = NoiseReduction { method: NoiseReductionMethod::Rnnoise, strength: 0.8 };
+        let models_dir = std::env::temp_dir().join(format!("clipforge_models_test_{}", std::process::id()));
+        fs::create_dir_all(&models_dir).unwrap();
+
+        // Never switch to afftdn without telling the user
+        assert!(noise_reduction.filter_with_models(|| Ok(models_dir.clone())).is_err());
+
+        fs::write(models_dir.join(RNNOISE_MODEL), b"").unwrap();
+        let filter = noise_reduction.filter_with_models(|| Ok(models_dir.clone())).unwrap();
+        assert!(filter.starts_with("arnndn=m="));
+        assert!(filter.contains(RNNOISE_MODEL));
+        assert!(filter.ends_with(":mix=0.8"));
+        let _ = fs::remove_dir_all(&models_dir);
+    }
+}
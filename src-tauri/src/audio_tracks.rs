@@ -0,0 +1,226 @@
+// ClipForge - Audio Tracks Module
+// Audio detached from video: voice-over and detached-audio tracks, plus J/L cuts on the main track
+
+use serde::{Deserialize, Serialize};
+
+use crate::audio_processing::AudioProcessing;
+use crate::export::{build_clip_audio_filter, has_audio_stream, ClipData};
+
+/// A piece of audio placed freely on the timeline, e.g. a voice-over or a clip's detached audio
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AudioClip {
+    pub path: String,
+    #[serde(rename = "timelineStart")]
+    pub timeline_start: f64,
+    #[serde(rename = "inPoint")]
+    pub in_point: f64,
+    #[serde(rename = "outPoint")]
+    pub out_point: f64,
+    #[serde(rename = "sourceOffset")]
+    pub source_offset: Option<f64>,
+    #[serde(default = "default_volume")]
+    pub volume: f64,
+    #[serde(default)]
+    pub muted: bool,
+    #[serde(rename = "fadeIn", default)]
+    pub fade_in: f64,
+    #[serde(rename = "fadeOut", default)]
+    pub fade_out: f64,
+    #[serde(rename = "audioProcessing")]
+    pub audio_processing: Option<AudioProcessing>,
+}
+
+fn default_volume() -> f64 {
+    100.0
+}
+
+impl AudioClip {
+    pub fn trim_duration(&self) -> f64 {
+        (self.out_point - self.in_point).max(0.0)
+    }
+
+    pub fn timeline_end(&self) -> f64 {
+        self.timeline_start.max(0.0) + self.trim_duration()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AudioTrack {
+    pub id: Option<String>,
+    pub muted: bool,
+    pub clips: Vec<AudioClip>,
+}
+
+/// Push an audio clip's branch: trimmed and gained like any clip, then processed, faded and delayed
+/// to its timeline start. Returns the label of the result.
+pub(crate) fn push_audio_clip(filter_parts: &mut Vec<String>, clip: &AudioClip, input_index: usize, label: &str) -> Result<String, String> {
+    let duration = clip.trim_duration();
+    filter_parts.push(build_clip_audio_filter(
+        input_index,
+        has_audio_stream(&clip.path),
+        clip.source_offset.unwrap_or(clip.in_point),
+        duration,
+        clip.volume,
+        clip.muted,
+        &format!("[{}_atrim]", label),
+    ));
+
+    let mut filters: Vec<String> = clip.audio_processing.as_ref().map(AudioProcessing::filter).transpose()?.flatten().into_iter().collect();
+    let max_fade = duration / 2.0;
+    if clip.fade_in > 0.0 {
+        filters.push(format!("afade=t=in:st=0:d={}", clip.fade_in.min(max_fade)));
+    }
+    if clip.fade_out > 0.0 {
+        let fade_out = clip.fade_out.min(max_fade);
+        filters.push(format!("afade=t=out:st={}:d={}", duration - fade_out, fade_out));
+    }
+    filters.push(format!("adelay=delays={}:all=1", (clip.timeline_start.max(0.0) * 1000.0).round() as i64));
+
+    let output = format!("[{}_audio]", label);
+    filter_parts.push(format!("[{}_atrim]{}{}", label, filters.join(","), output));
+    Ok(output)
+}
+
+/// Add the inputs and branches of every unmuted audio track, appending their labels to
+/// `mix_inputs`. Returns the next free input index.
+pub(crate) fn push_audio_tracks(
+    cmd_args: &mut Vec<String>,
+    filter_parts: &mut Vec<String>,
+    tracks: &[AudioTrack],
+    mut input_index: usize,
+    mix_inputs: &mut Vec<String>,
+) -> Result<usize, String> {
+    for (t, track) in tracks.iter().enumerate().filter(|(_, t)| !t.muted) {
+        for (c, clip) in track.clips.iter().enumerate() {
+            cmd_args.push("-i".to_string());
+            cmd_args.push(clip.path.clone());
+            mix_inputs.push(push_audio_clip(filter_parts, clip, input_index, &format!("at{}c{}", t, c))?);
+            input_index += 1;
+        }
+    }
+    Ok(input_index)
+}
+
+/// End of the last clip on any unmuted audio track
+pub(crate) fn audio_tracks_end(tracks: &[AudioTrack]) -> f64 {
+    tracks.iter()
+        .filter(|t| !t.muted)
+        .flat_map(|t| t.clips.iter())
+        .map(AudioClip::timeline_end)
+        .fold(0.0, f64::max)
+}
+
+/// Source range (trim start, duration) of each main-track clip's audio once J and L cuts move the
+/// audio edit points away from the picture cuts. A J-cut (`audioLead`) starts a clip's audio before
+/// its picture and ends the previous clip's audio early; an L-cut (`audioTrail`) keeps a clip's audio
+/// playing over the start of the next clip. Each edit only moves, so the audio keeps the video's
+/// total length; the track join concatenates audio and video separately.
+pub(crate) fn split_edit_ranges(clips: &[ClipData], video_ranges: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    // How far each edit between clip i and i + 1 moves; positive is later (L-cut)
+    let shifts: Vec<f64> = clips.windows(2)
+        .zip(video_ranges.windows(2))
+        .map(|(pair, ranges)| {
+            let shift = pair[0].audio_trail.unwrap_or(0.0) - pair[1].audio_lead.unwrap_or(0.0);
+            let ((_, previous_duration), (next_start, next_duration)) = (ranges[0], ranges[1]);
+            // Source left after the previous clip's out point (none for split pieces, whose
+            // duration is their own length)
+            let previous_tail = (pair[0].duration - pair[0].out_point).max(0.0);
+            // A clip trimmed to nothing (out before in) has no audio to move the edit into
+            if previous_duration <= 0.0 || next_duration <= 0.0 {
+                return 0.0;
+            }
+            // Cuts can't reach past either clip's source, and neither may take more than half
+            // of a clip's audio
+            let earliest = -(previous_duration / 2.0).min(next_start.max(0.0));
+            let latest = (next_duration / 2.0).min(previous_tail);
+            shift.max(earliest).min(latest)
+        })
+        .collect();
+
+    video_ranges.iter()
+        .enumerate()
+        .map(|(i, (start, duration))| {
+            let before = i.checked_sub(1).map(|b| shifts[b]).unwrap_or(0.0);
+            let after = shifts.get(i).copied().unwrap_or(0.0);
+            (start + before, duration - before + after)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DetachedAudio {
+    /// The clip with its own audio turned off
+    pub clip: ClipData,
+    /// The clip's audio as a free-standing audio track clip, still in sync with the picture
+    pub audio: AudioClip,
+}
+
+/// Split a clip's audio from its video so it can be moved, trimmed or offset on an audio track.
+/// `timeline_start` is where the clip currently sits on the export timeline.
+#[tauri::command]
+pub async fn detach_clip_audio(clip: ClipData, timeline_start: f64) -> Result<DetachedAudio, String> {
+    if clip.muted {
+        return Err("Clip audio is muted; there is nothing to detach".to_string());
+    }
+
+    let audio = AudioClip {
+        path: clip.path.clone(),
+        timeline_start,
+        in_point: clip.in_point,
+        out_point: clip.out_point,
+        source_offset: clip.source_offset,
+        volume: clip.volume,
+        muted: false,
+        fade_in: clip.audio_fade_in.unwrap_or(0.0),
+        fade_out: clip.audio_fade_out.unwrap_or(0.0),
+        audio_processing: clip.audio_processing.clone(),
+    };
+
+    let clip = ClipData {
+        muted: true,
+        audio_lead: None,
+        audio_trail: None,
+        ..clip
+    };
+
+    Ok(DetachedAudio { clip, audio })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_edits_move_audio_cut_points() {
+        let clips: Vec<ClipData> = serde_json::from_value(serde_json::json!([
+            { "path": "a.mp4", "duration": 12.0, "inPoint": 0.0, "outPoint": 10.0, "volume": 100.0, "muted": false,
+              "audioTrail": 3.0 },
+            { "path": "b.mp4", "duration": 8.0, "inPoint": 2.0, "outPoint": 8.0, "volume": 100.0, "muted": false },
+            { "path": "c.mp4", "duration": 6.0, "inPoint": 0.5, "outPoint": 6.0, "volume": 100.0, "muted": false,
+              "audioLead": 2.0 }
+        ])).unwrap();
+        let video = [(0.0, 10.0), (2.0, 6.0), (0.5, 5.5)];
+
+        // L-cut: a's audio runs into b, limited by a's 2s of tail. J-cut: c's audio starts early,
+        // limited by c's 0.5s of head
+        let audio = split_edit_ranges(&clips, &video);
+        assert_eq!(audio, vec![(0.0, 12.0), (4.0, 3.5), (0.0, 6.0)]);
+
+        let total = |ranges: &[(f64, f64)]| ranges.iter().map(|r| r.1).sum::<f64>();
+        assert_eq!(total(&audio), total(&video));
+    }
+
+    #[test]
+    fn test_split_edit_next_to_inverted_clip_stays_put() {
+        // a's out point is before its in point, which used to make the shift bounds cross and panic
+        let clips: Vec<ClipData> = serde_json::from_value(serde_json::json!([
+            { "path": "a.mp4", "duration": 12.0, "inPoint": 6.0, "outPoint": 4.0, "volume": 100.0, "muted": false,
+              "audioTrail": 3.0 },
+            { "path": "b.mp4", "duration": 8.0, "inPoint": 2.0, "outPoint": 3.0, "volume": 100.0, "muted": false }
+        ])).unwrap();
+        let video = [(6.0, -2.0), (2.0, 1.0)];
+
+        assert_eq!(split_edit_ranges(&clips, &video), video.to_vec());
+    }
+}
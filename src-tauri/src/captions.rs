@@ -0,0 +1,404 @@
+// ClipForge - Captions Module
+// Turns Whisper transcripts into SRT/WebVTT captions and adds them to exports (burned in or as a soft track)
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::get_fonts_dir;
+use crate::export_settings::{Container, ExportSettings};
+use crate::filler_detection::{WhisperResponse, WhisperWord};
+use crate::text_overlay::escape_filter_value;
+
+/// Silence longer than this between words always starts a new cue
+const MAX_WORD_GAP: f64 = 1.0;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CaptionOptions {
+    #[serde(rename = "maxCharsPerLine")]
+    pub max_chars_per_line: usize,
+    #[serde(rename = "maxLines")]
+    pub max_lines: usize,
+    /// Longest time a single cue stays on screen, in seconds
+    #[serde(rename = "maxCueDuration")]
+    pub max_cue_duration: f64,
+    /// Added to every timestamp, e.g. the clip's position on the timeline
+    #[serde(rename = "timeOffset")]
+    pub time_offset: f64,
+    /// Prefix each cue with its speaker, e.g. "Speaker 1: Hello"
+    #[serde(rename = "speakerLabels")]
+    pub speaker_labels: bool,
+    /// Caption colour per speaker as #RRGGBB
+    #[serde(rename = "speakerColors")]
+    pub speaker_colors: HashMap<String, String>,
+}
+
+impl Default for CaptionOptions {
+    fn default() -> Self {
+        Self {
+            max_chars_per_line: 42,
+            max_lines: 2,
+            max_cue_duration: 6.0,
+            time_offset: 0.0,
+            speaker_labels: false,
+            speaker_colors: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Cue {
+    pub start: f64,
+    pub end: f64,
+    /// Caption text, already wrapped into lines
+    pub text: String,
+    pub speaker: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CaptionFiles {
+    #[serde(rename = "srtPath")]
+    pub srt_path: String,
+    #[serde(rename = "vttPath")]
+    pub vtt_path: String,
+    #[serde(rename = "cueCount")]
+    pub cue_count: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CaptionMode {
+    /// Rendered into the picture with the subtitles filter
+    BurnIn,
+    /// Muxed as a subtitle stream the player can toggle
+    Soft,
+}
+
+/// Caption file to include in an export
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExportCaptions {
+    /// SRT or WebVTT file
+    pub path: String,
+    pub mode: CaptionMode,
+    /// ISO 639-2 language tag for soft tracks
+    pub language: Option<String>,
+}
+
+/// Greedily wrap words into lines of at most `max_chars` characters
+fn wrap_lines(words: &[&str], max_chars: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in words {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= max_chars => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines
+}
+
+fn ends_sentence(word: &str) -> bool {
+    word.ends_with(['.', '?', '!'])
+}
+
+/// Group timed words into cues that respect the line length, line count and duration limits
+fn cues_from_words(words: &[&WhisperWord], options: &CaptionOptions) -> Vec<Cue> {
+    let mut cues = Vec::new();
+    let mut current: Vec<&WhisperWord> = Vec::new();
+
+    let flush = |current: &mut Vec<&WhisperWord>, cues: &mut Vec<Cue>| {
+        if let (Some(first), Some(last)) = (current.first(), current.last()) {
+            let texts: Vec<&str> = current.iter().map(|w| w.word.trim()).collect();
+            cues.push(Cue {
+                start: first.start,
+                end: last.end.max(first.start),
+                text: wrap_lines(&texts, options.max_chars_per_line).join("\n"),
+                speaker: first.speaker.clone(),
+            });
+        }
+        current.clear();
+    };
+
+    for word in words {
+        let text = word.word.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        if let (Some(first), Some(last)) = (current.first(), current.last()) {
+            let mut texts: Vec<&str> = current.iter().map(|w| w.word.trim()).collect();
+            texts.push(text);
+
+            let too_many_lines = wrap_lines(&texts, options.max_chars_per_line).len() > options.max_lines;
+            let too_long = word.end - first.start > options.max_cue_duration;
+            let paused = word.start - last.end > MAX_WORD_GAP;
+            let new_speaker = word.speaker != last.speaker;
+            if too_many_lines || too_long || paused || new_speaker {
+                flush(&mut current, &mut cues);
+            }
+        }
+
+        current.push(word);
+
+        // Prefer to end cues at sentence boundaries
+        if ends_sentence(text) {
+            flush(&mut current, &mut cues);
+        }
+    }
+    flush(&mut current, &mut cues);
+
+    cues
+}
+
+/// Fallback for transcripts without word timings: split each segment's text into cues
+/// and share the segment's time between them by character count
+fn cues_from_segments(transcript: &WhisperResponse, options: &CaptionOptions) -> Vec<Cue> {
+    let mut cues = Vec::new();
+
+    for segment in transcript.segments.iter().flatten() {
+        let words: Vec<&str> = segment.text.split_whitespace().collect();
+        let lines = wrap_lines(&words, options.max_chars_per_line);
+        let total_chars: usize = lines.iter().map(|l| l.chars().count()).sum();
+        if total_chars == 0 {
+            continue;
+        }
+
+        let mut start = segment.start;
+        for chunk in lines.chunks(options.max_lines.max(1)) {
+            let chunk_chars: usize = chunk.iter().map(|l| l.chars().count()).sum();
+            let end = start + (segment.end - segment.start) * chunk_chars as f64 / total_chars as f64;
+            cues.push(Cue { start, end, text: chunk.join("\n"), speaker: segment.speaker.clone() });
+            start = end;
+        }
+    }
+
+    cues
+}
+
+/// Build caption cues from a transcript
+pub fn build_cues(transcript: &WhisperResponse, options: &CaptionOptions) -> Vec<Cue> {
+    let words = transcript.all_words();
+    let mut cues = if words.is_empty() {
+        cues_from_segments(transcript, options)
+    } else {
+        cues_from_words(&words, options)
+    };
+
+    for cue in &mut cues {
+        cue.start = (cue.start + options.time_offset).max(0.0);
+        cue.end = (cue.end + options.time_offset).max(cue.start);
+        if let (true, Some(speaker)) = (options.speaker_labels, &cue.speaker) {
+            cue.text = format!("{}: {}", speaker, cue.text);
+        }
+    }
+    cues
+}
+
+/// Format seconds as HH:MM:SS plus milliseconds, using the given separator before the millis
+fn format_timestamp(seconds: f64, millis_separator: char) -> String {
+    let total_ms = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!("{:02}:{:02}:{:02}{}{:03}",
+        total_ms / 3_600_000,
+        (total_ms / 60_000) % 60,
+        (total_ms / 1000) % 60,
+        millis_separator,
+        total_ms % 1000)
+}
+
+/// The configured colour for a cue's speaker, if it is a valid #RRGGBB value
+fn speaker_color<'a>(cue: &Cue, options: &'a CaptionOptions) -> Option<&'a str> {
+    let color = options.speaker_colors.get(cue.speaker.as_ref()?)?;
+    let valid = color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+    valid.then_some(color.as_str())
+}
+
+pub fn format_srt(cues: &[Cue], options: &CaptionOptions) -> String {
+    cues.iter()
+        .enumerate()
+        .map(|(i, cue)| {
+            let text = match speaker_color(cue, options) {
+                Some(color) => format!("<font color=\"{}\">{}</font>", color, cue.text),
+                None => cue.text.clone(),
+            };
+            format!("{}\n{} --> {}\n{}\n",
+                i + 1, format_timestamp(cue.start, ','), format_timestamp(cue.end, ','), text)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn format_vtt(cues: &[Cue], options: &CaptionOptions) -> String {
+    let mut vtt = String::from("WEBVTT\n");
+
+    // Speaker colours are styled through the cues' voice spans
+    let mut styled: Vec<(&str, &str)> = Vec::new();
+    for cue in cues {
+        if let (Some(speaker), Some(color)) = (cue.speaker.as_deref(), speaker_color(cue, options)) {
+            if !styled.iter().any(|(s, _)| *s == speaker) {
+                styled.push((speaker, color));
+            }
+        }
+    }
+    for (speaker, color) in styled {
+        vtt.push_str(&format!("\nSTYLE\n::cue(v[voice=\"{}\"]) {{ color: {}; }}\n", speaker, color));
+    }
+
+    for cue in cues {
+        let text = match &cue.speaker {
+            Some(speaker) => format!("<v {}>{}", speaker, cue.text),
+            None => cue.text.clone(),
+        };
+        vtt.push_str(&format!("\n{} --> {}\n{}\n",
+            format_timestamp(cue.start, '.'), format_timestamp(cue.end, '.'), text));
+    }
+    vtt
+}
+
+/// Subtitle codec for a soft caption track in the given container
+fn soft_subtitle_codec(container: Container) -> &'static str {
+    match container {
+        Container::Mp4 | Container::Mov => "mov_text",
+        Container::Webm => "webvtt",
+        Container::Mkv => "srt",
+    }
+}
+
+impl ExportCaptions {
+    pub fn validate(&self) -> Result<(), String> {
+        let extension = Path::new(&self.path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        if !matches!(extension.as_deref(), Some("srt") | Some("vtt")) {
+            return Err("Captions must be an SRT or WebVTT file".to_string());
+        }
+        if !Path::new(&self.path).exists() {
+            return Err(format!("Caption file not found: {}", self.path));
+        }
+        Ok(())
+    }
+}
+
+/// Add the export's captions (if any). Burned-in captions extend the filter graph and return a
+/// new video label; soft captions add an input and return the extra -map arguments.
+pub(crate) fn apply_captions(
+    cmd_args: &mut Vec<String>,
+    filter_parts: &mut Vec<String>,
+    settings: &ExportSettings,
+    input_index: usize,
+    current: &str,
+) -> Result<(String, Vec<String>), String> {
+    let Some(captions) = &settings.captions else {
+        return Ok((current.to_string(), Vec::new()));
+    };
+    captions.validate()?;
+
+    match captions.mode {
+        CaptionMode::BurnIn => {
+            // Render with the bundled fonts so burned-in captions look the same on every machine
+            let fonts_dir = get_fonts_dir()
+                .map(|dir| format!(":fontsdir={}", escape_filter_value(&dir.to_string_lossy())))
+                .unwrap_or_default();
+            filter_parts.push(format!("{}subtitles=filename={}{}[captioned]",
+                current, escape_filter_value(&captions.path), fonts_dir));
+            Ok(("[captioned]".to_string(), Vec::new()))
+        }
+        CaptionMode::Soft => {
+            cmd_args.push("-i".to_string());
+            cmd_args.push(captions.path.clone());
+            Ok((current.to_string(), vec!["-map".to_string(), format!("{}:s", input_index)]))
+        }
+    }
+}
+
+/// Output options for a soft caption track
+pub(crate) fn subtitle_args(settings: &ExportSettings) -> Vec<String> {
+    match &settings.captions {
+        Some(captions) if captions.mode == CaptionMode::Soft => {
+            let mut args = vec![
+                "-c:s".to_string(), soft_subtitle_codec(settings.container).to_string(),
+            ];
+            if let Some(language) = &captions.language {
+                args.extend(["-metadata:s:s:0".to_string(), format!("language={}", language)]);
+            }
+            args
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Write SRT and WebVTT captions for a transcript next to `output_path`
+#[tauri::command]
+pub async fn generate_captions(
+    transcript: WhisperResponse,
+    output_path: String,
+    options: Option<CaptionOptions>,
+) -> Result<CaptionFiles, String> {
+    let options = options.unwrap_or_default();
+    if options.max_chars_per_line == 0 || options.max_lines == 0 || options.max_cue_duration <= 0.0 {
+        return Err("Caption line length, line count and cue duration must be greater than 0".to_string());
+    }
+
+    let cues = build_cues(&transcript, &options);
+    if cues.is_empty() {
+        return Err("Transcript contains no speech to caption".to_string());
+    }
+
+    let srt_path = Path::new(&output_path).with_extension("srt");
+    let vtt_path = Path::new(&output_path).with_extension("vtt");
+    fs::write(&srt_path, format_srt(&cues, &options))
+        .map_err(|e| format!("Failed to write SRT file: {}", e))?;
+    fs::write(&vtt_path, format_vtt(&cues, &options))
+        .map_err(|e| format!("Failed to write WebVTT file: {}", e))?;
+
+    Ok(CaptionFiles {
+        srt_path: srt_path.to_string_lossy().to_string(),
+        vtt_path: vtt_path.to_string_lossy().to_string(),
+        cue_count: cues.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(word: &str, start: f64, end: f64) -> WhisperWord {
+        WhisperWord { word: word.to_string(), start, end, speaker: None }
+    }
+
+    #[test]
+    fn test_cues_split_on_sentences_and_line_limits() {
+        let transcript = WhisperResponse {
+            text: String::new(),
+            words: Some(vec![
+                word("Hello", 0.0, 0.4),
+                word("there.", 0.5, 0.9),
+                word("This", 1.0, 1.2),
+                word("caption", 1.3, 1.7),
+                word("wraps", 1.8, 2.1),
+            ]),
+            segments: None,
+            language: None,
+        };
+        let options = CaptionOptions { max_chars_per_line: 12, max_lines: 1, ..Default::default() };
+
+        let cues = build_cues(&transcript, &options);
+        assert_eq!(cues.iter().map(|c| c.text.as_str()).collect::<Vec<_>>(),
+            vec!["Hello there.", "This caption", "wraps"]);
+        assert_eq!((cues[1].start, cues[1].end), (1.0, 1.7));
+    }
+
+    #[test]
+    fn test_subtitle_formats() {
+        let cues = vec![Cue { start: 61.5, end: 3723.25, text: "Hi\nthere".to_string(), speaker: None }];
+        let options = CaptionOptions::default();
+        assert_eq!(format_srt(&cues, &options), "1\n00:01:01,500 --> 01:02:03,250\nHi\nthere\n");
+        assert_eq!(format_vtt(&cues, &options), "WEBVTT\n\n00:01:01.500 --> 01:02:03.250\nHi\nthere\n");
+    }
+}
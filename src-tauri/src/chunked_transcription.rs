@@ -0,0 +1,288 @@
+// ClipForge - Chunked Transcription Module
+// Splits long recordings on silence into upload-sized chunks, transcribes them in parallel and stitches the results
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::get_ffmpeg_path;
+use crate::export_settings::ffmpeg_has_encoder;
+use crate::filler_detection::{TempFileCleanup, WhisperResponse};
+use crate::silence::{detect_silence_ranges, SilenceRange};
+use crate::transcriber::Transcriber;
+
+/// 16kHz mono 16-bit PCM, the format `extract_audio_to_temp` writes
+const WAV_BYTES_PER_SECOND: f64 = 32000.0;
+/// Leave headroom under the upload limit for container overhead and bitrate variation
+const UPLOAD_SIZE_MARGIN: f64 = 0.9;
+/// Shorter chunks upload in parallel and are cheaper to retry
+const MAX_CHUNK_SECONDS: f64 = 600.0;
+/// Only cut at silences in the second half of a chunk so chunks stay close to the limit
+const MIN_CHUNK_FRACTION: f64 = 0.5;
+const SILENCE_NOISE_DB: f64 = -35.0;
+const SILENCE_MIN_DURATION: f64 = 0.3;
+const MAX_PARALLEL_UPLOADS: usize = 3;
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_BACKOFF_MS: u64 = 1000;
+
+/// Audio format for uploaded chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkFormat {
+    #[default]
+    Wav,
+    Opus,
+    Mp3,
+}
+
+impl ChunkFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ChunkFormat::Wav => "wav",
+            ChunkFormat::Opus => "ogg",
+            ChunkFormat::Mp3 => "mp3",
+        }
+    }
+
+    fn codec_args(&self) -> &'static [&'static str] {
+        match self {
+            ChunkFormat::Wav => &["-c:a", "pcm_s16le"],
+            // Speech stays intelligible to Whisper at low bitrates
+            ChunkFormat::Opus => &["-c:a", "libopus", "-b:a", "24k", "-application", "voip"],
+            ChunkFormat::Mp3 => &["-c:a", "libmp3lame", "-b:a", "32k"],
+        }
+    }
+
+    fn bytes_per_second(&self) -> f64 {
+        match self {
+            ChunkFormat::Wav => WAV_BYTES_PER_SECOND,
+            ChunkFormat::Opus => 3000.0,
+            ChunkFormat::Mp3 => 4000.0,
+        }
+    }
+
+    /// The requested format, or WAV when FFmpeg lacks its encoder
+    fn available(self) -> Self {
+        let encoder = match self {
+            ChunkFormat::Wav => return self,
+            ChunkFormat::Opus => "libopus",
+            ChunkFormat::Mp3 => "libmp3lame",
+        };
+        if ffmpeg_has_encoder(encoder) {
+            self
+        } else {
+            eprintln!("Warning: FFmpeg has no {} encoder, uploading WAV chunks", encoder);
+            ChunkFormat::Wav
+        }
+    }
+}
+
+/// Split `[0, duration)` into chunks of at most `max_chunk` seconds, cutting in the middle of
+/// silences where possible so no word is split between two requests
+pub(crate) fn plan_chunks(duration: f64, silences: &[SilenceRange], max_chunk: f64) -> Vec<(f64, f64)> {
+    let mut chunks = Vec::new();
+    let mut start = 0.0;
+
+    while duration - start > max_chunk {
+        let limit = start + max_chunk;
+        let earliest = start + max_chunk * MIN_CHUNK_FRACTION;
+
+        let cut = silences.iter()
+            .map(|s| s.midpoint())
+            .rfind(|m| *m > earliest && *m <= limit)
+            .unwrap_or(limit);
+
+        chunks.push((start, cut));
+        start = cut;
+    }
+    chunks.push((start, duration));
+
+    chunks
+}
+
+/// Shift every timestamp of each chunk's transcript by the chunk's start and join them
+pub(crate) fn stitch_transcripts(parts: Vec<(f64, WhisperResponse)>) -> WhisperResponse {
+    let mut text = Vec::new();
+    let mut words = Vec::new();
+    let mut segments = Vec::new();
+    let mut has_words = false;
+    let mut has_segments = false;
+    let mut language = None;
+
+    for (offset, part) in parts {
+        text.push(part.text.trim().to_string());
+        language = language.or(part.language);
+
+        if let Some(part_words) = part.words {
+            has_words = true;
+            words.extend(part_words.into_iter().map(|mut w| {
+                w.start += offset;
+                w.end += offset;
+                w
+            }));
+        }
+
+        if let Some(part_segments) = part.segments {
+            has_segments = true;
+            for mut segment in part_segments {
+                segment.id = Some(segments.len() as i32);
+                segment.seek = None;
+                segment.start += offset;
+                segment.end += offset;
+                for word in segment.words.iter_mut().flatten() {
+                    word.start += offset;
+                    word.end += offset;
+                }
+                segments.push(segment);
+            }
+        }
+    }
+
+    WhisperResponse {
+        text: text.into_iter().filter(|t| !t.is_empty()).collect::<Vec<_>>().join(" "),
+        words: has_words.then_some(words),
+        segments: has_segments.then_some(segments),
+        language,
+    }
+}
+
+/// Bad credentials won't improve on retry; everything else (timeouts, rate limits, 5xx) might
+fn is_retryable(error: &str) -> bool {
+    !error.starts_with("Invalid API key")
+}
+
+async fn transcribe_with_retry(transcriber: &dyn Transcriber, audio_path: &str) -> Result<WhisperResponse, String> {
+    let mut attempt = 1;
+    loop {
+        match transcriber.transcribe(audio_path).await {
+            Ok(response) => return Ok(response),
+            Err(e) if attempt < MAX_ATTEMPTS && is_retryable(&e) => {
+                let backoff = INITIAL_BACKOFF_MS * 2u64.pow(attempt - 1);
+                eprintln!("Transcription attempt {} failed ({}), retrying in {}ms", attempt, e, backoff);
+                tokio::time::sleep(Duration::from_millis(backoff)).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn encode_chunk(audio_path: &str, start: f64, end: f64, format: ChunkFormat, chunk_path: &Path) -> Result<(), String> {
+    let ffmpeg_path = get_ffmpeg_path()?;
+    let output = Command::new(&ffmpeg_path)
+        .args(["-ss", &start.to_string(), "-i", audio_path, "-t", &(end - start).to_string(), "-vn"])
+        .args(format.codec_args())
+        .args(["-ar", "16000", "-ac", "1", "-y"])
+        .arg(chunk_path)
+        .output()
+        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("FFmpeg audio chunking failed: {}", stderr));
+    }
+    Ok(())
+}
+
+/// Transcribe a 16kHz mono WAV, splitting it into chunks when it is over the backend's upload limit
+pub(crate) async fn transcribe_audio_file(
+    transcriber: Arc<dyn Transcriber>,
+    audio_path: &str,
+    chunk_format: ChunkFormat,
+) -> Result<WhisperResponse, String> {
+    let size = fs::metadata(audio_path)
+        .map_err(|e| format!("Failed to read audio file: {}", e))?
+        .len();
+
+    let limit = match transcriber.max_upload_bytes() {
+        Some(limit) if size > limit => limit,
+        _ => return transcribe_with_retry(transcriber.as_ref(), audio_path).await,
+    };
+
+    let format = chunk_format.available();
+    let duration = size as f64 / WAV_BYTES_PER_SECOND;
+    let max_chunk = (limit as f64 * UPLOAD_SIZE_MARGIN / format.bytes_per_second()).min(MAX_CHUNK_SECONDS);
+
+    // Without silence information the chunks are simply cut at the size limit
+    let silences = detect_silence_ranges(audio_path, SILENCE_NOISE_DB, SILENCE_MIN_DURATION, Some(duration))
+        .unwrap_or_else(|e| {
+            eprintln!("Warning: {}", e);
+            Vec::new()
+        });
+    let chunks = plan_chunks(duration, &silences, max_chunk);
+
+    let mut cleanups = Vec::new();
+    let mut chunk_paths = Vec::new();
+    for (i, (start, end)) in chunks.iter().enumerate() {
+        let chunk_path = Path::new(audio_path).with_extension(format!("chunk{}.{}", i, format.extension()));
+        cleanups.push(TempFileCleanup::new(chunk_path.to_string_lossy().to_string()));
+        encode_chunk(audio_path, *start, *end, format, &chunk_path)?;
+        chunk_paths.push(chunk_path.to_string_lossy().to_string());
+    }
+
+    // Dropping the JoinSet on an early return aborts the remaining uploads
+    let semaphore = Arc::new(Semaphore::new(MAX_PARALLEL_UPLOADS));
+    let mut tasks = JoinSet::new();
+    for (i, chunk_path) in chunk_paths.into_iter().enumerate() {
+        let transcriber = Arc::clone(&transcriber);
+        let semaphore = Arc::clone(&semaphore);
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.map_err(|e| e.to_string())?;
+            transcribe_with_retry(transcriber.as_ref(), &chunk_path).await.map(|r| (i, r))
+        });
+    }
+
+    let mut results: Vec<Option<WhisperResponse>> = vec![None; chunks.len()];
+    while let Some(joined) = tasks.join_next().await {
+        let (i, response) = joined
+            .map_err(|e| format!("Transcription task failed: {}", e))?
+            .map_err(|e| format!("Transcribing part of the recording failed: {}", e))?;
+        results[i] = Some(response);
+    }
+
+    let parts = chunks.iter()
+        .zip(results)
+        .map(|((start, _), response)| response.map(|r| (*start, r)))
+        .collect::<Option<Vec<_>>>()
+        .ok_or("Transcription finished with missing chunks")?;
+
+    Ok(stitch_transcripts(parts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filler_detection::WhisperWord;
+
+    #[test]
+    fn test_chunks_cut_at_silence() {
+        let silences = vec![
+            SilenceRange { start: 2.0, end: 3.0 },
+            SilenceRange { start: 8.0, end: 9.0 },
+        ];
+
+        // Cuts at the last usable silence, falls back to a hard cut when none is in range
+        assert_eq!(plan_chunks(25.0, &silences, 10.0), vec![(0.0, 8.5), (8.5, 18.5), (18.5, 25.0)]);
+    }
+
+    #[test]
+    fn test_stitch_offsets_words() {
+        let part = |word: &str| WhisperResponse {
+            text: word.to_string(),
+            words: Some(vec![WhisperWord { word: word.to_string(), start: 1.0, end: 1.5, speaker: None }]),
+            segments: None,
+            language: None,
+        };
+
+        let stitched = stitch_transcripts(vec![(0.0, part("first")), (600.0, part("second"))]);
+        assert_eq!(stitched.text, "first second");
+        let words = stitched.words.unwrap();
+        assert_eq!((words[1].start, words[1].end), (601.0, 601.5));
+        assert!(stitched.segments.is_none());
+    }
+}
@@ -0,0 +1,445 @@
+// ClipForge - Speaker Diarization Module
+// Labels who is speaking by clustering spectral embeddings of the extracted 16kHz WAV, then tags transcript words and segments
+
+use std::f32::consts::PI;
+use std::fs;
+use serde::{Deserialize, Serialize};
+
+use crate::filler_detection::WhisperResponse;
+use crate::speaker_clustering::{kmeans, normalize_embeddings, separation, silhouette, smooth_labels};
+
+/// 25ms analysis frames every 10ms, as is usual for speech features
+const FRAME_SECONDS: f64 = 0.025;
+const HOP_SECONDS: f64 = 0.01;
+const FFT_SIZE: usize = 512;
+const MEL_BANDS: usize = 24;
+const MEL_MIN_HZ: f32 = 80.0;
+const MEL_MAX_HZ: f32 = 7600.0;
+/// Cepstral coefficients 1..=CEPSTRA; c0 is loudness, which says little about who is talking
+pub(crate) const CEPSTRA: usize = 12;
+/// Frames quieter than this RMS (about -45 dBFS) are treated as silence
+const SILENCE_RMS: f32 = 0.0056;
+/// Each embedding summarises 1.5s of audio, with windows overlapping by half
+const WINDOW_FRAMES: usize = 150;
+const WINDOW_HOP_FRAMES: usize = 75;
+const MIN_VOICED_FRACTION: f64 = 0.5;
+/// Speaker counts tried when the count isn't configured
+const MAX_AUTO_SPEAKERS: usize = 4;
+const MAX_SPEAKERS: usize = 8;
+/// Different speakers' mean cepstra sit several within-speaker spreads apart; closer clusters are one
+/// speaker whose voice drifts
+const MIN_SEPARATION: f32 = 3.0;
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DiarizationSettings {
+    pub enabled: bool,
+    /// Number of speakers if known; None estimates it (up to four)
+    pub speakers: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeakerTurn {
+    pub start: f64,
+    pub end: f64,
+    pub speaker: String,
+}
+
+/// Read a mono 16-bit PCM WAV as samples in [-1, 1] plus its sample rate
+fn read_pcm_wav(path: &str) -> Result<(Vec<f32>, u32), String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read audio file: {}", e))?;
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err("Audio file is not a WAV file".to_string());
+    }
+
+    let mut sample_rate = None;
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let size = u32::from_le_bytes([bytes[offset + 4], bytes[offset + 5], bytes[offset + 6], bytes[offset + 7]]) as usize;
+        let body = &bytes[offset + 8..(offset + 8 + size).min(bytes.len())];
+
+        if id == b"fmt " && body.len() >= 16 {
+            let channels = u16::from_le_bytes([body[2], body[3]]);
+            let bits = u16::from_le_bytes([body[14], body[15]]);
+            if channels != 1 || bits != 16 {
+                return Err("Diarization needs mono 16-bit audio".to_string());
+            }
+            sample_rate = Some(u32::from_le_bytes([body[4], body[5], body[6], body[7]]));
+        } else if id == b"data" {
+            let rate = sample_rate.ok_or("WAV data chunk comes before its format chunk")?;
+            let samples = body.chunks_exact(2)
+                .map(|s| i16::from_le_bytes([s[0], s[1]]) as f32 / 32768.0)
+                .collect();
+            return Ok((samples, rate));
+        }
+
+        // Chunks are padded to an even length
+        offset += 8 + size + (size & 1);
+    }
+
+    Err("WAV file has no audio data".to_string())
+}
+
+/// In-place iterative radix-2 FFT over (re, im) pairs; the length must be a power of two
+fn fft(buffer: &mut [(f32, f32)]) {
+    let n = buffer.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            buffer.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (buffer[start + k], buffer[start + k + len / 2]);
+                let t = (b.0 * cos - b.1 * sin, b.0 * sin + b.1 * cos);
+                buffer[start + k] = (a.0 + t.0, a.1 + t.1);
+                buffer[start + k + len / 2] = (a.0 - t.0, a.1 - t.1);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+fn hz_to_mel(hz: f32) -> f32 {
+    2595.0 * (1.0 + hz / 700.0).log10()
+}
+
+fn mel_to_hz(mel: f32) -> f32 {
+    700.0 * (10f32.powf(mel / 2595.0) - 1.0)
+}
+
+/// Triangular mel filters as (first bin, weights) over the FFT power spectrum
+fn mel_filterbank(sample_rate: u32) -> Vec<(usize, Vec<f32>)> {
+    let (low, high) = (hz_to_mel(MEL_MIN_HZ), hz_to_mel(MEL_MAX_HZ.min(sample_rate as f32 / 2.0)));
+    let bin = |mel: f32| (mel_to_hz(mel) * FFT_SIZE as f32 / sample_rate as f32).floor() as usize;
+    let edges: Vec<usize> = (0..MEL_BANDS + 2)
+        .map(|i| bin(low + (high - low) * i as f32 / (MEL_BANDS + 1) as f32))
+        .collect();
+
+    (0..MEL_BANDS)
+        .map(|b| {
+            let (left, center, right) = (edges[b], edges[b + 1].max(edges[b] + 1), edges[b + 2].max(edges[b] + 2));
+            let weights = (left..right)
+                .map(|k| if k < center {
+                    (k - left) as f32 / (center - left) as f32
+                } else {
+                    (right - k) as f32 / (right - center) as f32
+                })
+                .collect();
+            (left, weights)
+        })
+        .collect()
+}
+
+/// Cepstral features for each frame, or None for silent frames
+fn frame_features(samples: &[f32], sample_rate: u32) -> Vec<Option<[f32; CEPSTRA]>> {
+    let frame_len = ((FRAME_SECONDS * sample_rate as f64) as usize).min(FFT_SIZE);
+    let hop = (HOP_SECONDS * sample_rate as f64) as usize;
+    let window: Vec<f32> = (0..frame_len)
+        .map(|i| 0.54 - 0.46 * (2.0 * PI * i as f32 / (frame_len - 1) as f32).cos())
+        .collect();
+    let filters = mel_filterbank(sample_rate);
+    let mut buffer = vec![(0.0f32, 0.0f32); FFT_SIZE];
+
+    (0..samples.len().saturating_sub(frame_len) / hop.max(1))
+        .map(|f| {
+            let frame = &samples[f * hop..f * hop + frame_len];
+            let rms = (frame.iter().map(|s| s * s).sum::<f32>() / frame_len as f32).sqrt();
+            if rms < SILENCE_RMS {
+                return None;
+            }
+
+            buffer.fill((0.0, 0.0));
+            for (i, (sample, weight)) in frame.iter().zip(&window).enumerate() {
+                buffer[i].0 = sample * weight;
+            }
+            fft(&mut buffer);
+
+            let log_mel: Vec<f32> = filters.iter()
+                .map(|(first, weights)| {
+                    let energy: f32 = weights.iter()
+                        .enumerate()
+                        .map(|(k, w)| {
+                            let (re, im) = buffer[first + k];
+                            w * (re * re + im * im)
+                        })
+                        .sum();
+                    (energy + 1e-10).ln()
+                })
+                .collect();
+
+            let mut cepstra = [0.0f32; CEPSTRA];
+            for (c, value) in cepstra.iter_mut().enumerate() {
+                let n = c + 1;
+                *value = log_mel.iter()
+                    .enumerate()
+                    .map(|(m, e)| e * (PI * n as f32 * (m as f32 + 0.5) / MEL_BANDS as f32).cos())
+                    .sum();
+            }
+            Some(cepstra)
+        })
+        .collect()
+}
+
+/// One embedding (mean cepstrum) per window with enough speech, keyed by window start frame.
+/// Deviations are left out: windows straddling a speaker change have high deviation and would form a cluster of their own.
+fn window_embeddings(frames: &[Option<[f32; CEPSTRA]>]) -> Vec<(usize, Vec<f32>)> {
+    let mut embeddings: Vec<(usize, Vec<f32>)> = Vec::new();
+
+    for start in (0..frames.len().saturating_sub(WINDOW_FRAMES / 2)).step_by(WINDOW_HOP_FRAMES) {
+        let voiced: Vec<&[f32; CEPSTRA]> = frames[start..(start + WINDOW_FRAMES).min(frames.len())]
+            .iter()
+            .flatten()
+            .collect();
+        if (voiced.len() as f64) < WINDOW_FRAMES as f64 * MIN_VOICED_FRACTION {
+            continue;
+        }
+
+        let count = voiced.len() as f32;
+        let embedding = (0..CEPSTRA)
+            .map(|c| voiced.iter().map(|f| f[c]).sum::<f32>() / count)
+            .collect();
+        embeddings.push((start, embedding));
+    }
+
+    embeddings
+}
+
+/// Find who speaks when in a mono 16-bit WAV
+pub(crate) fn diarize_audio(audio_path: &str, speakers: Option<usize>) -> Result<Vec<SpeakerTurn>, String> {
+    let (samples, sample_rate) = read_pcm_wav(audio_path)?;
+    let frames = frame_features(&samples, sample_rate);
+    let windows = window_embeddings(&frames);
+    if windows.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let raw: Vec<Vec<f32>> = windows.iter().map(|(_, e)| e.clone()).collect();
+    let points = normalize_embeddings(&raw);
+    let (k, labels) = match speakers {
+        Some(count) => {
+            let k = count.clamp(1, MAX_SPEAKERS).min(points.len());
+            (k, kmeans(&points, k))
+        }
+        // The best-scoring count whose speakers are clearly apart, else a single speaker
+        None => (2..=MAX_AUTO_SPEAKERS.min(points.len()))
+            .map(|k| (k, kmeans(&points, k)))
+            .filter(|(k, labels)| separation(&raw, labels, *k) >= MIN_SEPARATION)
+            .map(|(k, labels)| (silhouette(&points, &labels, k), k, labels))
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, k, labels)| (k, labels))
+            .unwrap_or((1, vec![0; points.len()])),
+    };
+    let labels = smooth_labels(&labels, k);
+
+    // Name speakers in order of first appearance
+    let mut order: Vec<usize> = Vec::new();
+    for label in &labels {
+        if !order.contains(label) {
+            order.push(*label);
+        }
+    }
+
+    // Each window stands for the hop-length stretch around its centre
+    let mut turns: Vec<SpeakerTurn> = Vec::new();
+    for ((start_frame, _), label) in windows.iter().zip(&labels) {
+        let center = (start_frame + WINDOW_FRAMES / 2) as f64 * HOP_SECONDS;
+        let half_hop = WINDOW_HOP_FRAMES as f64 * HOP_SECONDS / 2.0;
+        let (start, end) = ((center - half_hop).max(0.0), center + half_hop);
+        let speaker = format!("Speaker {}", order.iter().position(|l| l == label).unwrap_or(0) + 1);
+
+        match turns.last_mut() {
+            Some(turn) if turn.speaker == speaker && start - turn.end < 1e-6 => turn.end = end,
+            _ => turns.push(SpeakerTurn { start, end, speaker }),
+        }
+    }
+
+    Ok(turns)
+}
+
+/// Speaker of the turn overlapping a time range the most, or of the nearest turn
+fn speaker_at(turns: &[SpeakerTurn], start: f64, end: f64) -> Option<String> {
+    let midpoint = (start + end) / 2.0;
+    turns.iter()
+        .min_by(|a, b| {
+            let distance = |t: &SpeakerTurn| {
+                let overlap = end.min(t.end) - start.max(t.start);
+                if overlap > 0.0 { -overlap } else { (midpoint - midpoint.clamp(t.start, t.end)).abs() }
+            };
+            distance(a).total_cmp(&distance(b))
+        })
+        .map(|t| t.speaker.clone())
+}
+
+/// Attach speaker labels to every word, and to each segment by majority of its words
+pub(crate) fn apply_speakers(response: &mut WhisperResponse, turns: &[SpeakerTurn]) {
+    if turns.is_empty() {
+        return;
+    }
+
+    for word in response.words.iter_mut().flatten() {
+        word.speaker = speaker_at(turns, word.start, word.end);
+    }
+
+    for segment in response.segments.iter_mut().flatten() {
+        let mut counts: Vec<(String, usize)> = Vec::new();
+        for word in segment.words.iter_mut().flatten() {
+            word.speaker = speaker_at(turns, word.start, word.end);
+            if let Some(speaker) = &word.speaker {
+                match counts.iter_mut().find(|(s, _)| s == speaker) {
+                    Some((_, count)) => *count += 1,
+                    None => counts.push((speaker.clone(), 1)),
+                }
+            }
+        }
+
+        segment.speaker = counts.into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(speaker, _)| speaker)
+            .or_else(|| speaker_at(turns, segment.start, segment.end));
+    }
+}
+
+/// Diarize the transcript's audio and label its words and segments. Failures only lose the labels.
+pub(crate) async fn diarize_response(response: &mut WhisperResponse, audio_path: &str, settings: &DiarizationSettings) {
+    let path = audio_path.to_string();
+    let speakers = settings.speakers;
+
+    match tokio::task::spawn_blocking(move || diarize_audio(&path, speakers)).await {
+        Ok(Ok(turns)) => apply_speakers(response, &turns),
+        Ok(Err(e)) => eprintln!("Warning: Speaker diarization failed: {}", e),
+        Err(e) => eprintln!("Warning: Speaker diarization failed: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16000;
+
+    fn write_pcm_wav(name: &str, samples: &[f32]) -> String {
+        let path = std::env::temp_dir().join(format!("clipforge_diarization_{}_{}.wav", name, std::process::id()));
+        let data: Vec<u8> = samples.iter()
+            .flat_map(|s| ((s.clamp(-1.0, 1.0) * 32767.0) as i16).to_le_bytes())
+            .collect();
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&RATE.to_le_bytes());
+        bytes.extend_from_slice(&(RATE * 2).to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&data);
+
+        fs::write(&path, bytes).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// A buzzy harmonic "voice": a low, dark one or a higher, brighter one, with a little noise
+    fn voice(seconds: f64, bright: bool, seed: &mut u32) -> Vec<f32> {
+        let (pitch, formant) = if bright { (220.0, 2200.0) } else { (110.0, 500.0) };
+        (0..(seconds * RATE as f64) as usize)
+            .map(|i| {
+                let t = i as f32 / RATE as f32;
+                let tone: f32 = (1..=30)
+                    .map(|h| {
+                        let freq = pitch * h as f32;
+                        let gain = (-((freq - formant) / 600.0).powi(2)).exp();
+                        gain * (2.0 * PI * freq * t).sin()
+                    })
+                    .sum();
+                *seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                let noise = (*seed >> 8) as f32 / (1u32 << 24) as f32 - 0.5;
+                0.15 * tone + 0.01 * noise
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_read_pcm_wav_and_fft() {
+        let samples = vec![0.0, 0.5, -0.5, 0.25];
+        let path = write_pcm_wav("roundtrip", &samples);
+        let (read, rate) = read_pcm_wav(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(rate, RATE);
+        assert_eq!(read.len(), samples.len());
+        assert!(read.iter().zip(&samples).all(|(a, b)| (a - b).abs() < 1e-3));
+
+        // A cosine at bin 4 of a 32-point FFT puts half its energy in bin 4 and half in bin 28
+        let mut buffer: Vec<(f32, f32)> = (0..32)
+            .map(|i| ((2.0 * PI * 4.0 * i as f32 / 32.0).cos(), 0.0))
+            .collect();
+        fft(&mut buffer);
+        for (k, (re, im)) in buffer.iter().enumerate() {
+            let expected = if k == 4 || k == 28 { 16.0 } else { 0.0 };
+            assert!((re - expected).abs() < 1e-3 && im.abs() < 1e-3, "bin {}: ({}, {})", k, re, im);
+        }
+    }
+
+    #[test]
+    fn test_two_voices_found() {
+        let mut seed = 7;
+        let mut samples = Vec::new();
+        for bright in [false, true, false, true] {
+            samples.extend(voice(6.0, bright, &mut seed));
+        }
+        let path = write_pcm_wav("two_voices", &samples);
+        let turns = diarize_audio(&path, None);
+        let _ = fs::remove_file(&path);
+        let turns = turns.unwrap();
+
+        let speakers: Vec<&str> = turns.iter().map(|t| t.speaker.as_str()).collect();
+        assert_eq!(speakers, ["Speaker 1", "Speaker 2", "Speaker 1", "Speaker 2"]);
+        for (turn, change) in turns.iter().skip(1).zip([6.0, 12.0, 18.0]) {
+            assert!((turn.start - change).abs() < 1.0, "turn at {} expected near {}", turn.start, change);
+        }
+    }
+
+    #[test]
+    fn test_speakers_applied_by_overlap() {
+        let mut response: WhisperResponse = serde_json::from_value(serde_json::json!({
+            "text": "Hi there hello",
+            "segments": [
+                { "start": 0.0, "end": 1.2, "text": "Hi there", "words": [
+                    { "word": "Hi", "start": 0.0, "end": 0.4 },
+                    { "word": "there", "start": 0.5, "end": 1.2 }
+                ] },
+                { "start": 2.0, "end": 2.5, "text": "hello", "words": [
+                    { "word": "hello", "start": 2.0, "end": 2.5 }
+                ] }
+            ]
+        })).unwrap();
+        let turns = vec![
+            SpeakerTurn { start: 0.0, end: 1.0, speaker: "Speaker 1".to_string() },
+            SpeakerTurn { start: 1.0, end: 1.8, speaker: "Speaker 2".to_string() },
+        ];
+
+        apply_speakers(&mut response, &turns);
+        let segments = response.segments.unwrap();
+
+        // "there" overlaps speaker 1 more; "hello" falls after every turn and takes the nearest
+        assert_eq!(segments[0].speaker.as_deref(), Some("Speaker 1"));
+        assert_eq!(segments[1].words.as_ref().unwrap()[0].speaker.as_deref(), Some("Speaker 2"));
+    }
+}
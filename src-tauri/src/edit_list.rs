@@ -0,0 +1,265 @@
+// ClipForge - Edit List Module
+// Cuts ranges out of a clip, producing the list of clips that remain, including cuts made by editing the transcript
+
+use serde::{Deserialize, Serialize};
+
+use crate::export::ClipData;
+use crate::transcription::TranscriptWord;
+
+/// Word timings from the frontend round-trip through JSON, so compare them with a tolerance
+const WORD_TIME_TOLERANCE: f64 = 0.001;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EditOptions {
+    /// Seconds of audio kept on each side of a removed range so cuts don't clip neighbouring words
+    pub padding: f64,
+    /// Removed ranges closer together than this are merged, and slivers shorter than this are dropped
+    #[serde(rename = "minGap")]
+    pub min_gap: f64,
+    /// Audio fade length at each cut in seconds, 0 for hard cuts
+    pub crossfade: f64,
+    /// Extend cuts within `min_gap` of the clip's start or end to the edge
+    #[serde(skip)]
+    pub snap_edges: bool,
+}
+
+impl Default for EditOptions {
+    fn default() -> Self {
+        Self {
+            padding: 0.05,
+            min_gap: 0.3,
+            crossfade: 0.0,
+            snap_edges: true,
+        }
+    }
+}
+
+/// Shrink each range by the padding, clamp it to the clip and merge ranges separated by less than `min_gap`.
+/// Ranges are in clip time, where 0 is the clip's trim start.
+fn normalize_ranges(ranges: &[(f64, f64)], clip_length: f64, options: &EditOptions) -> Vec<(f64, f64)> {
+    let mut padded: Vec<(f64, f64)> = ranges.iter()
+        .map(|(start, end)| ((start + options.padding).max(0.0), (end - options.padding).min(clip_length)))
+        .filter(|(start, end)| end > start)
+        .collect();
+    padded.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut merged: Vec<(f64, f64)> = Vec::new();
+    for (start, end) in padded {
+        match merged.last_mut() {
+            Some(last) if start - last.1 < options.min_gap => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    // Don't leave slivers at the clip edges
+    if !options.snap_edges {
+        return merged;
+    }
+    if let Some(first) = merged.first_mut() {
+        if first.0 < options.min_gap {
+            first.0 = 0.0;
+        }
+    }
+    if let Some(last) = merged.last_mut() {
+        if clip_length - last.1 < options.min_gap {
+            last.1 = clip_length;
+        }
+    }
+
+    merged
+}
+
+/// Remove `ranges` (clip time, relative to the trim start) from `clip` and return the pieces that remain.
+/// Each piece is a clean clip (`in_point` 0) whose `source_offset` points at its start in the source file.
+pub fn split_clip(clip: &ClipData, ranges: &[(f64, f64)], options: &EditOptions) -> Vec<ClipData> {
+    let clip_length = (clip.out_point - clip.in_point).max(0.0);
+    let trim_start = clip.source_offset.unwrap_or(clip.in_point);
+    let removed = normalize_ranges(ranges, clip_length, options);
+
+    let mut kept = Vec::new();
+    let mut cursor = 0.0;
+    for (start, end) in removed {
+        kept.push((cursor, start));
+        cursor = end;
+    }
+    kept.push((cursor, clip_length));
+    kept.retain(|(start, end)| end > start);
+
+    let cut_fade = (options.crossfade > 0.0).then_some(options.crossfade);
+    let last = kept.len().saturating_sub(1);
+
+    kept.iter()
+        .enumerate()
+        .map(|(i, (start, end))| {
+            let length = end - start;
+            ClipData {
+                path: clip.path.clone(),
+                duration: length,
+                in_point: 0.0,
+                out_point: length,
+                volume: clip.volume,
+                muted: clip.muted,
+                source_offset: Some(trim_start + start),
+                // Only the last piece leads into the next clip on the track
+                transition: if i == last { clip.transition.clone() } else { None },
+                audio_fade_in: if *start > 0.0 { cut_fade } else { clip.audio_fade_in },
+                audio_fade_out: if *end < clip_length { cut_fade } else { clip.audio_fade_out },
+                audio_processing: clip.audio_processing.clone(),
+                // J/L cuts stay on the outer edits of the original clip
+                audio_lead: if i == 0 { clip.audio_lead } else { None },
+                audio_trail: if i == last { clip.audio_trail } else { None },
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TranscriptEditOptions {
+    /// Cuts shorter than this are skipped; they are too short to hear and only fragment the clip
+    #[serde(rename = "minCut")]
+    pub min_cut: f64,
+    /// Audio fade length at each cut in seconds, 0 for hard cuts
+    pub crossfade: f64,
+    /// Extend cuts within `min_gap` of the clip's start or end to the edge
+    #[serde(skip)]
+    pub snap_edges: bool,
+}
+
+impl Default for TranscriptEditOptions {
+    fn default() -> Self {
+        Self {
+            min_cut: 0.08,
+            crossfade: 0.0,
+            snap_edges: true,
+        }
+    }
+}
+
+fn same_word(a: &TranscriptWord, b: &TranscriptWord) -> bool {
+    a.word == b.word
+        && (a.source_start - b.source_start).abs() < WORD_TIME_TOLERANCE
+        && (a.source_end - b.source_end).abs() < WORD_TIME_TOLERANCE
+}
+
+/// Ranges to cut (clip time) for the words missing from `kept`. Each run of deleted words becomes one
+/// cut whose edges sit in the middle of the gaps to the surrounding kept words.
+fn transcript_cuts(
+    clip: &ClipData,
+    words: &[TranscriptWord],
+    kept: &[TranscriptWord],
+    min_cut: f64,
+) -> Result<Vec<(f64, f64)>, String> {
+    let clip_length = (clip.out_point - clip.in_point).max(0.0);
+    let trim_start = clip.source_offset.unwrap_or(clip.in_point);
+
+    // Clip-time span of each word in the clip and whether the edit kept it
+    let mut remaining = kept.iter().peekable();
+    let mut spans = Vec::new();
+    for word in words {
+        let is_kept = remaining.next_if(|k| same_word(k, word)).is_some();
+        let (start, end) = (word.source_start - trim_start, word.source_end - trim_start);
+        let midpoint = (start + end) / 2.0;
+        if (0.0..=clip_length).contains(&midpoint) {
+            spans.push((start.max(0.0), end.min(clip_length), is_kept));
+        }
+    }
+    if remaining.next().is_some() {
+        return Err("Edited words must be a subset of the original words, in order".to_string());
+    }
+
+    let mut cuts = Vec::new();
+    let mut i = 0;
+    while i < spans.len() {
+        if spans[i].2 {
+            i += 1;
+            continue;
+        }
+        let run_start = i;
+        while i < spans.len() && !spans[i].2 {
+            i += 1;
+        }
+
+        let start = match run_start.checked_sub(1) {
+            Some(previous) => (spans[previous].1 + spans[run_start].0) / 2.0,
+            None => 0.0,
+        };
+        let end = match spans.get(i) {
+            Some(next) => (spans[i - 1].1 + next.0) / 2.0,
+            None => clip_length,
+        };
+        if end - start >= min_cut {
+            cuts.push((start, end));
+        }
+    }
+
+    Ok(cuts)
+}
+
+/// Cut the words deleted from a clip's transcript out of the clip and return the clips that remain
+#[tauri::command]
+pub async fn edit_clip_from_transcript(
+    clip: ClipData,
+    words: Vec<TranscriptWord>,
+    kept_words: Vec<TranscriptWord>,
+    options: Option<TranscriptEditOptions>,
+) -> Result<Vec<ClipData>, String> {
+    let options = options.unwrap_or_default();
+    let cuts = transcript_cuts(&clip, &words, &kept_words, options.min_cut)?;
+
+    // Cuts are already snapped to word gaps and never split kept words, so no padding or merging
+    let edit_options = EditOptions { padding: 0.0, min_gap: 0.0, crossfade: options.crossfade, snap_edges: true };
+    let pieces = split_clip(&clip, &cuts, &edit_options);
+    if pieces.is_empty() {
+        return Err("Deleting every word would leave nothing of the clip".to_string());
+    }
+
+    Ok(pieces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_clip_around_removed_ranges() {
+        let clip: ClipData = serde_json::from_value(serde_json::json!({
+            "path": "talk.mp4",
+            "duration": 10.0,
+            "inPoint": 0.0,
+            "outPoint": 10.0,
+            "volume": 100.0,
+            "muted": false,
+            "sourceOffset": 20.0
+        })).unwrap();
+        let options = EditOptions { padding: 0.0, min_gap: 0.3, crossfade: 0.02, snap_edges: true };
+
+        // The first two ranges are merged, the last one swallows the sliver at the clip end
+        let pieces = split_clip(&clip, &[(2.0, 3.0), (3.1, 4.0), (7.0, 9.8)], &options);
+
+        assert_eq!(pieces.len(), 2);
+        assert_eq!((pieces[0].source_offset, pieces[0].out_point), (Some(20.0), 2.0));
+        assert_eq!((pieces[1].source_offset, pieces[1].out_point), (Some(24.0), 3.0));
+        assert_eq!((pieces[0].audio_fade_in, pieces[0].audio_fade_out), (None, Some(0.02)));
+        assert_eq!((pieces[1].audio_fade_in, pieces[1].audio_fade_out), (Some(0.02), Some(0.02)));
+    }
+
+    #[test]
+    fn test_transcript_cuts_snap_to_word_gaps() {
+        let clip: ClipData = serde_json::from_value(serde_json::json!({
+            "path": "talk.mp4", "duration": 5.0, "inPoint": 1.0, "outPoint": 5.0, "volume": 100.0, "muted": false
+        })).unwrap();
+        let word = |text: &str, start: f64, end: f64| TranscriptWord {
+            word: text.to_string(), start, end, source_start: start, source_end: end, speaker: None,
+        };
+        let words = vec![
+            word("we", 1.2, 1.4), word("um", 1.6, 1.8), word("really", 2.0, 2.4),
+            word("shipped", 2.6, 3.0), word("it", 3.2, 3.4),
+        ];
+        let kept = vec![words[0].clone(), words[3].clone()];
+
+        // "um really" is one cut between the gaps around it; the trailing "it" runs to the clip end
+        assert_eq!(transcript_cuts(&clip, &words, &kept, 0.08).unwrap(), vec![(0.5, 1.5), (2.1, 4.0)]);
+    }
+}
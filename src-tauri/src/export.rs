@@ -5,9 +5,20 @@ use std::process::Command;
 use serde::{Deserialize, Serialize};
 use tauri::AppHandle;
 
-use crate::get_ffmpeg_path;
+use crate::{get_ffmpeg_path, get_ffprobe_path};
+use crate::export_plan::{apply_export_overlays, finish_output_args, run_export_plan, ExportPlan};
+use crate::export_progress::{RunningExport, EXPORT_CANCELLED};
+use crate::export_settings::ExportSettings;
+use crate::audio_processing::AudioProcessing;
+use crate::audio_tracks::split_edit_ranges;
+use crate::loudness::{apply_mix_loudness, loudness_pass_args, main_clip_audio, normalize_clip_loudness, plan_with_loudness};
+use crate::stream_copy::{plan_stream_copy, run_stream_copy_export};
+use crate::transitions::{build_track_join, transition_normalize_filter, Transition, TrackSegment};
 
-#[derive(Deserialize, Serialize)]
+/// Sample rate every clip's audio is resampled to before concatenation
+pub(crate) const AUDIO_SAMPLE_RATE: u32 = 48000;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ClipData {
     pub path: String,
     pub duration: f64,
@@ -19,38 +30,64 @@ pub struct ClipData {
     pub muted: bool,
     #[serde(rename = "sourceOffset")]
     pub source_offset: Option<f64>,
+    /// Transition from this clip into the next clip on the track
+    pub transition: Option<Transition>,
+    /// Short audio fades (seconds) at the clip edges so cuts inside speech don't click
+    #[serde(rename = "audioFadeIn")]
+    pub audio_fade_in: Option<f64>,
+    #[serde(rename = "audioFadeOut")]
+    pub audio_fade_out: Option<f64>,
+    /// Noise reduction, EQ and dynamics applied to the clip's audio
+    #[serde(rename = "audioProcessing")]
+    pub audio_processing: Option<AudioProcessing>,
+    /// J-cut: seconds the clip's audio starts before its picture, over the end of the previous clip
+    #[serde(rename = "audioLead")]
+    pub audio_lead: Option<f64>,
+    /// L-cut: seconds the clip's audio keeps playing over the start of the next clip
+    #[serde(rename = "audioTrail")]
+    pub audio_trail: Option<f64>,
 }
 
-#[derive(Deserialize, Serialize)]
-pub struct PipClipData {
-    pub path: String,
-    pub duration: f64,
-    #[serde(rename = "inPoint")]
-    pub in_point: f64,
-    #[serde(rename = "outPoint")]
-    pub out_point: f64,
-    pub volume: f64,
-    pub muted: bool,
-    #[serde(rename = "sourceOffset")]
-    pub source_offset: Option<f64>,
-    #[serde(rename = "pipSettings")]
-    pub pip_settings: Option<PipSettings>,
-}
+impl ClipData {
+    /// afade filters for the clip's edge fades, or None when it has none
+    fn audio_fade_filter(&self, trim_duration: f64) -> Option<String> {
+        let max_fade = trim_duration / 2.0;
+        let mut fades = Vec::new();
+        if let Some(fade_in) = self.audio_fade_in.filter(|d| *d > 0.0) {
+            fades.push(format!("afade=t=in:st=0:d={}", fade_in.min(max_fade)));
+        }
+        if let Some(fade_out) = self.audio_fade_out.filter(|d| *d > 0.0) {
+            let fade_out = fade_out.min(max_fade);
+            fades.push(format!("afade=t=out:st={}:d={}", trim_duration - fade_out, fade_out));
+        }
+        (!fades.is_empty()).then(|| fades.join(","))
+    }
+
+    pub fn has_audio_fades(&self) -> bool {
+        self.audio_fade_in.is_some_and(|d| d > 0.0) || self.audio_fade_out.is_some_and(|d| d > 0.0)
+    }
 
-#[derive(Deserialize, Serialize)]
-pub struct PipSettings {
-    pub x: f64,
-    pub y: f64,
-    pub width: f64,
-    pub height: f64,
-    pub opacity: f64,
+    pub fn has_audio_processing(&self) -> bool {
+        self.audio_processing.as_ref().is_some_and(|p| !p.is_empty())
+    }
+
+    /// Whether the clip's audio edit points differ from its picture cuts (J/L cuts)
+    pub fn has_split_edit(&self) -> bool {
+        self.audio_lead.is_some_and(|d| d > 0.0) || self.audio_trail.is_some_and(|d| d > 0.0)
+    }
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn export_video(
-    _app: AppHandle,
+    app: AppHandle,
     clips: Vec<serde_json::Value>,
     output_path: String,
+    width: Option<i32>,
+    height: Option<i32>,
+    export_id: Option<String>,
+    settings: Option<ExportSettings>,
+    stream_copy: Option<bool>,
 ) -> Result<String, String> {
     if clips.is_empty() {
         return Err("No clips to export".to_string());
@@ -66,83 +103,126 @@ pub async fn export_video(
         parsed_clips.push(clip);
     }
     
+    let settings = settings.unwrap_or_default();
+    let export_id = export_id.unwrap_or_else(|| output_path.clone());
+    let export = RunningExport::start(&app, &export_id)?;
+    
+    // Opt-in lossless fast path: copy the source streams when they already match the export
+    // target and nothing needs the filter graph. Falls back to the full render if it fails.
+    if stream_copy.unwrap_or(false) {
+        let target = resolve_export_resolution(width, height)?;
+        if let Some(copy_plan) = plan_stream_copy(&parsed_clips, &settings, target) {
+            match run_stream_copy_export(&export, &ffmpeg_path, &copy_plan, &output_path).await {
+                Ok(()) => return Ok("Export completed successfully (stream copy)".to_string()),
+                Err(e) if e == EXPORT_CANCELLED => return Err(e),
+                Err(e) => eprintln!("Warning: Stream copy export failed, re-encoding instead: {}", e),
+            }
+        }
+    }
+    
+    normalize_clip_loudness(&export, main_clip_audio(&mut parsed_clips), settings.loudness.as_ref()).await?;
+    let plan = plan_with_loudness(&export, &ffmpeg_path, &settings, |s| {
+        build_export_plan(&parsed_clips, &output_path, width, height, s)
+    }).await?;
+    
+    // Execute FFmpeg, reporting progress against the total timeline duration
+    let output = run_export_plan(&export, &ffmpeg_path, &plan, &output_path).await?;
+    
+    if !output.success {
+        return Err(parse_ffmpeg_error(&output.stderr));
+    }
+    
+    Ok("Export completed successfully".to_string())
+}
+
+/// Build the FFmpeg arguments for a single-track export that concatenates every clip
+pub fn build_export_plan(
+    clips: &[ClipData],
+    output_path: &str,
+    width: Option<i32>,
+    height: Option<i32>,
+    settings: &ExportSettings,
+) -> Result<ExportPlan, String> {
+    if clips.is_empty() {
+        return Err("No clips to export".to_string());
+    }
+    
+    let (export_width, export_height) = resolve_export_resolution(width, height)?;
+    
     // Build FFmpeg command for multi-clip concatenation
     let mut cmd_args = Vec::new();
     
     // Add all input files
-    for clip in &parsed_clips {
+    for clip in clips {
         cmd_args.push("-i".to_string());
         cmd_args.push(clip.path.clone());
     }
     
-    // Build filter complex for trimming and concatenation (video only for now)
+    // Build filter complex for trimming and concatenation
     let mut filter_parts = Vec::new();
-    let mut video_inputs = Vec::new();
+    let mut segments = Vec::new();
+    let normalize = transition_normalize_filter(clips, settings);
     
-    for (i, clip) in parsed_clips.iter().enumerate() {
+    // For split clips, use source_offset as the trim start; otherwise use in_point
+    let video_ranges: Vec<(f64, f64)> = clips.iter()
+        .map(|c| (c.source_offset.unwrap_or(c.in_point), c.out_point - c.in_point))
+        .collect();
+    let audio_ranges = split_edit_ranges(clips, &video_ranges);
+    
+    for (i, clip) in clips.iter().enumerate() {
         let input_video = format!("[{}:v]", i);
         
         // Video processing: trim and scale
-        // For split clips, use source_offset as the trim start; otherwise use in_point
-        let trim_start = clip.source_offset.unwrap_or(clip.in_point);
-        let trim_duration = clip.out_point - clip.in_point;
+        let (trim_start, trim_duration) = video_ranges[i];
         
-        let video_filter = format!("{}trim=start={}:duration={},setpts=PTS-STARTPTS,scale=1280:720:force_original_aspect_ratio=decrease,pad=1280:720:(ow-iw)/2:(oh-ih)/2[v{}]", 
-            input_video, trim_start, trim_duration, i);
+        let video_filter = format!("{}trim=start={}:duration={},setpts=PTS-STARTPTS,scale={}:{}:force_original_aspect_ratio=decrease,pad={}:{}:(ow-iw)/2:(oh-ih)/2{}[v{}]", 
+            input_video, trim_start, trim_duration, export_width, export_height, export_width, export_height, normalize, i);
         
         filter_parts.push(video_filter);
-        video_inputs.push(format!("[v{}]", i));
+        
+        // Audio processing: trim alongside the video (moved by any J/L cut) and apply clip gain
+        let (audio_start, audio_duration) = audio_ranges[i];
+        push_main_clip_audio(&mut filter_parts, clip, i, audio_start, audio_duration, &format!("[a{}]", i))?;
+        
+        segments.push(TrackSegment {
+            video: format!("[v{}]", i),
+            audio: format!("[a{}]", i),
+            duration: trim_duration,
+            transition: clip.transition.clone(),
+        });
     }
     
-    // Concatenate video streams only (no audio for now)
-    let video_concat = format!("{}concat=n={}:v=1:a=0[outv]", video_inputs.join(""), parsed_clips.len());
-    filter_parts.push(video_concat);
+    // Concatenate video and audio streams, cross-fading at transitions
+    let (join_parts, total_duration) = build_track_join(&segments, "join", "[outv]", "[outa]");
+    filter_parts.extend(join_parts);
+    
+    let (video_output, caption_maps) = apply_export_overlays(&mut cmd_args, &mut filter_parts, settings, clips.len(), "[outv]", export_width, total_duration)?;
+    let audio_output = apply_mix_loudness(&mut filter_parts, "[outa]".to_string(), settings.loudness.as_ref());
     
     let filter_complex = filter_parts.join(";");
     
     cmd_args.push("-filter_complex".to_string());
     cmd_args.push(filter_complex);
     cmd_args.push("-map".to_string());
-    cmd_args.push("[outv]".to_string());
-    cmd_args.push("-c:v".to_string());
-    cmd_args.push("libx264".to_string());
-    cmd_args.push("-preset".to_string());
-    cmd_args.push("fast".to_string());
-    cmd_args.push("-crf".to_string());
-    cmd_args.push("23".to_string());
-    cmd_args.push("-pix_fmt".to_string());
-    cmd_args.push("yuv420p".to_string());
-    cmd_args.push("-y".to_string());
-    cmd_args.push(output_path.clone());
-    
-    // Execute FFmpeg
-    let output = Command::new(&ffmpeg_path)
-        .args(&cmd_args)
-        .output()
-        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
-    
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(parse_ffmpeg_error(&stderr));
-    }
-    
-    Ok("Export completed successfully".to_string())
+    cmd_args.push(video_output);
+    cmd_args.push("-map".to_string());
+    cmd_args.push(audio_output);
+    cmd_args.extend(caption_maps);
+    let loudness_pass_args = loudness_pass_args(&cmd_args, settings.loudness.as_ref());
+    let (args, first_pass_args) = finish_output_args(cmd_args, settings, output_path)?;
+    
+    Ok(ExportPlan {
+        args,
+        first_pass_args,
+        loudness_pass_args,
+        total_duration,
+        width: export_width,
+        height: export_height,
+    })
 }
 
-#[tauri::command]
-pub async fn export_multi_track_video(
-    _app: AppHandle,
-    main_track_clips: Vec<serde_json::Value>,
-    pip_track_clips: Vec<serde_json::Value>,
-    output_path: String,
-    width: Option<i32>,
-    height: Option<i32>,
-) -> Result<String, String> {
-    if main_track_clips.is_empty() && pip_track_clips.is_empty() {
-        return Err("No clips to export".to_string());
-    }
-    
-    // Get export resolution (default to 1280x720 if not specified)
+/// Get export resolution (default to 1280x720 if not specified)
+pub fn resolve_export_resolution(width: Option<i32>, height: Option<i32>) -> Result<(i32, i32), String> {
     // FFmpeg requires even dimensions, so ensure they're even
     let export_width = width.unwrap_or(1280) & !1; // Round down to even
     let export_height = height.unwrap_or(720) & !1; // Round down to even
@@ -155,177 +235,83 @@ pub async fn export_multi_track_video(
         return Err("Export resolution too large. Maximum is 7680x4320 (8K).".to_string());
     }
     
-    let ffmpeg_path = get_ffmpeg_path()?;
-    
-    // Parse main track clips
-    let mut parsed_main_clips = Vec::new();
-    for clip_json in main_track_clips {
-        let clip: ClipData = serde_json::from_value(clip_json)
-            .map_err(|e| format!("Failed to parse main track clip data: {}", e))?;
-        parsed_main_clips.push(clip);
-    }
-    
-    // Parse PIP track clips
-    let mut parsed_pip_clips = Vec::new();
-    for clip_json in pip_track_clips {
-        let clip: PipClipData = serde_json::from_value(clip_json)
-            .map_err(|e| format!("Failed to parse PIP track clip data: {}", e))?;
-        parsed_pip_clips.push(clip);
-    }
-    
-    // Build FFmpeg command
-    let mut cmd_args = Vec::new();
-    
-    // Add all input files (main track first, then PIP track)
-    for clip in &parsed_main_clips {
-        cmd_args.push("-i".to_string());
-        cmd_args.push(clip.path.clone());
-    }
-    for clip in &parsed_pip_clips {
-        cmd_args.push("-i".to_string());
-        cmd_args.push(clip.path.clone());
-    }
-    
-    // Build filter complex
-    let mut filter_parts = Vec::new();
-    
-    // Process main track clips
-    let mut main_video_inputs = Vec::new();
-    for (i, clip) in parsed_main_clips.iter().enumerate() {
-        let input_video = format!("[{}:v]", i);
-        
-        // For split clips, use source_offset as the trim start; otherwise use in_point
-        let trim_start = clip.source_offset.unwrap_or(clip.in_point);
-        let trim_duration = if clip.out_point < clip.duration {
-            clip.out_point - clip.in_point
-        } else {
-            clip.duration - clip.in_point
-        };
-        
-        let video_filter = format!("{}trim=start={}:duration={},setpts=PTS-STARTPTS,scale={}:{}:force_original_aspect_ratio=decrease,pad={}:{}:(ow-iw)/2:(oh-ih)/2[v{}]", 
-            input_video, trim_start, trim_duration, export_width, export_height, export_width, export_height, i);
-        
-        filter_parts.push(video_filter);
-        main_video_inputs.push(format!("[v{}]", i));
-    }
-    
-    // Calculate durations
-    let main_duration: f64 = parsed_main_clips.iter().map(|c| c.duration).sum();
-    let pip_duration: f64 = parsed_pip_clips.iter().map(|c| c.duration).sum();
-    
-    // Use the longer duration for the final video
-    let final_duration = main_duration.max(pip_duration);
-    
-    // Concatenate main track videos
-    // If main track exists, we need to create [main] output
-    // If no main track clips, create black background as [main]
-    if !main_video_inputs.is_empty() {
-        let concat_inputs_str = main_video_inputs.join("");
-        let concat_filter = format!("{}concat=n={}:v=1:a=0[main_concat]", concat_inputs_str, parsed_main_clips.len());
-        
-        // If main track is shorter than final duration, extend with black
-        if main_duration < final_duration {
-            filter_parts.push(format!("{};[main_concat]tpad=stop_mode=clone:stop_duration={}[main]", 
-                concat_filter, final_duration - main_duration));
-        } else {
-            // Main track is long enough - just relabel by using scale passthrough
-            filter_parts.push(format!("{};[main_concat]scale=iw:ih[main]", concat_filter));
-        }
+    Ok((export_width, export_height))
+}
+
+/// Push a main-track clip's audio branch, followed by its processing and edge fades when it has any
+pub(crate) fn push_main_clip_audio(
+    filter_parts: &mut Vec<String>,
+    clip: &ClipData,
+    input_index: usize,
+    trim_start: f64,
+    trim_duration: f64,
+    output_label: &str,
+) -> Result<(), String> {
+    let processing = clip.audio_processing.as_ref().map(AudioProcessing::filter).transpose()?.flatten();
+    let post: Vec<String> = processing.into_iter().chain(clip.audio_fade_filter(trim_duration)).collect();
+    let audio_label = if post.is_empty() {
+        output_label.to_string()
     } else {
-        // No main track clips, create a black background for the full duration
-        // Note: color filter needs explicit format for proper rendering
-        filter_parts.push(format!("color=black:size={}x{}:duration={}:rate=30[main]", 
-            export_width, export_height, final_duration));
+        format!("[a{}_raw]", input_index)
+    };
+
+    filter_parts.push(build_clip_audio_filter(
+        input_index,
+        has_audio_stream(&clip.path),
+        trim_start,
+        trim_duration,
+        clip.volume,
+        clip.muted,
+        &audio_label,
+    ));
+    if !post.is_empty() {
+        filter_parts.push(format!("{}{}{}", audio_label, post.join(","), output_label));
     }
-    
-    // Process PIP track clips and overlay them with proper timing
-    let mut current_output = "[main]".to_string();
-    let mut input_index = parsed_main_clips.len();
-    let mut pip_start_time = 0.0;
-    
-    for (i, clip) in parsed_pip_clips.iter().enumerate() {
-        let input_video = format!("[{}:v]", input_index);
-        
-        // For split clips, use source_offset as the trim start; otherwise use in_point
-        let trim_start = clip.source_offset.unwrap_or(clip.in_point);
-        let trim_duration = clip.out_point - clip.in_point;
-        
-        // Get PIP settings or use defaults
-        let pip_settings = clip.pip_settings.as_ref().unwrap_or(&PipSettings {
-            x: 0.75,
-            y: 0.75,
-            width: 0.25,
-            height: 0.25,
-            opacity: 1.0,
-        });
-        
-        // Scale PIP clip to the specified size (relative to export resolution)
-        let pip_width = (export_width as f64 * pip_settings.width) as i32;
-        let pip_height = (export_height as f64 * pip_settings.height) as i32;
-        
-        // Process PIP clip with timing
-        let pip_filter = format!("{}trim=start={}:duration={},setpts=PTS-STARTPTS,scale={}:{}[pip{}]", 
-            input_video, trim_start, trim_duration, pip_width, pip_height, i);
-        filter_parts.push(pip_filter);
-        
-        // Overlay PIP on main video with proper timing (relative to export resolution)
-        let overlay_x = (export_width as f64 * pip_settings.x) as i32;
-        let overlay_y = (export_height as f64 * pip_settings.y) as i32;
-        
-        // Use enable filter to control when PIP appears
-        let overlay_filter = format!("{}[pip{}]overlay=x={}:y={}:enable='gte(t,{})'[overlay{}]", 
-            current_output, i, overlay_x, overlay_y, pip_start_time, i);
-        filter_parts.push(overlay_filter);
-        
-        current_output = format!("[overlay{}]", i);
-        pip_start_time += clip.duration; // Move to next PIP clip position
-        input_index += 1;
+    Ok(())
+}
+
+/// Build the audio branch for a single clip: trim it to match the video, apply the
+/// clip gain, and substitute generated silence for muted clips or inputs without audio
+pub(crate) fn build_clip_audio_filter(
+    input_index: usize,
+    has_audio: bool,
+    trim_start: f64,
+    trim_duration: f64,
+    volume: f64,
+    muted: bool,
+    output_label: &str,
+) -> String {
+    if muted || !has_audio {
+        return format!("anullsrc=channel_layout=stereo:sample_rate={},atrim=duration={}{}", 
+            AUDIO_SAMPLE_RATE, trim_duration, output_label);
     }
     
-    let filter_complex = filter_parts.join(";");
-    
-    cmd_args.push("-filter_complex".to_string());
-    cmd_args.push(filter_complex);
-    cmd_args.push("-map".to_string());
-    cmd_args.push(format!("{}", current_output));
-    cmd_args.push("-c:v".to_string());
-    cmd_args.push("libx264".to_string());
-    cmd_args.push("-preset".to_string());
-    cmd_args.push("fast".to_string());
-    cmd_args.push("-crf".to_string());
-    cmd_args.push("23".to_string());
-    cmd_args.push("-pix_fmt".to_string());
-    cmd_args.push("yuv420p".to_string());
-    cmd_args.push("-y".to_string());
-    cmd_args.push(output_path.clone());
-    
-    // Debug: Log export parameters for troubleshooting
-    eprintln!("FFmpeg export resolution: {}x{}", export_width, export_height);
-    eprintln!("Main clips: {}, PiP clips: {}", parsed_main_clips.len(), parsed_pip_clips.len());
+    // ClipData.volume uses a 0-200 scale where 100 is unity gain
+    let gain = volume.max(0.0) / 100.0;
     
-    // Execute FFmpeg
-    let output = Command::new(&ffmpeg_path)
-        .args(&cmd_args)
+    // apad + whole_dur keeps audio the same length as the video when the source audio ends early
+    format!("[{}:a]atrim=start={}:duration={},asetpts=PTS-STARTPTS,volume={},aformat=sample_fmts=fltp:sample_rates={}:channel_layouts=stereo,apad=whole_dur={}{}", 
+        input_index, trim_start, trim_duration, gain, AUDIO_SAMPLE_RATE, trim_duration, output_label)
+}
+
+/// Check whether a media file contains at least one audio stream
+pub fn has_audio_stream(path: &str) -> bool {
+    let ffprobe_path = match get_ffprobe_path() {
+        Ok(p) => p,
+        Err(_) => return false,
+    };
+    
+    Command::new(&ffprobe_path)
+        .args([
+            "-v", "error",
+            "-select_streams", "a",
+            "-show_entries", "stream=index",
+            "-of", "csv=p=0",
+            path,
+        ])
         .output()
-        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
-    
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        
-        // Log full error for debugging
-        eprintln!("FFmpeg stderr: {}", stderr);
-        if !stdout.is_empty() {
-            eprintln!("FFmpeg stdout: {}", stdout);
-        }
-        
-        // Return detailed error with actual FFmpeg output
-        let parsed_error = parse_ffmpeg_error(&stderr);
-        return Err(format!("{}\n\nDebug info:\n- Resolution: {}x{}\n- Main clips: {}\n- PiP clips: {}\n\nFull FFmpeg error:\n{}", 
-            parsed_error, export_width, export_height, parsed_main_clips.len(), parsed_pip_clips.len(), stderr));
-    }
-    
-    Ok("Multi-track export completed successfully".to_string())
+        .map(|o| o.status.success() && !String::from_utf8_lossy(&o.stdout).trim().is_empty())
+        .unwrap_or(false)
 }
 
 pub fn parse_ffmpeg_error(stderr: &str) -> String {
@@ -342,3 +328,56 @@ pub fn parse_ffmpeg_error(stderr: &str) -> String {
         format!("Export failed. FFmpeg error:\n\n{}", stderr)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_muted_clip_uses_silence() {
+        let filter = build_clip_audio_filter(0, true, 1.0, 4.0, 100.0, true, "[a0]");
+        assert!(filter.starts_with("anullsrc="));
+        assert!(filter.contains("atrim=duration=4"));
+        assert!(filter.ends_with("[a0]"));
+    }
+
+    #[test]
+    fn test_single_track_uses_export_resolution() {
+        let clips: Vec<ClipData> = serde_json::from_value(serde_json::json!([
+            { "path": "a.mp4", "duration": 10.0, "inPoint": 0.0, "outPoint": 10.0, "volume": 100.0, "muted": false }
+        ])).unwrap();
+
+        let plan = build_export_plan(&clips, "out.mp4", Some(1920), Some(1080), &ExportSettings::default()).unwrap();
+        assert_eq!((plan.width, plan.height), (1920, 1080));
+        assert!(plan.args.iter().any(|a| a.contains("scale=1920:1080:force_original_aspect_ratio=decrease,pad=1920:1080:")));
+    }
+
+    #[test]
+    fn test_split_edit_joins_audio_apart_from_video() {
+        let clips: Vec<ClipData> = serde_json::from_value(serde_json::json!([
+            { "path": "a.mp4", "duration": 12.0, "inPoint": 0.0, "outPoint": 10.0, "volume": 100.0, "muted": false,
+              "audioTrail": 1.5 },
+            { "path": "b.mp4", "duration": 8.0, "inPoint": 2.0, "outPoint": 8.0, "volume": 100.0, "muted": false }
+        ])).unwrap();
+
+        let plan = build_export_plan(&clips, "out.mp4", None, None, &ExportSettings::default()).unwrap();
+        let graph = &plan.args[plan.args.iter().position(|a| a == "-filter_complex").unwrap() + 1];
+        let parts: Vec<&str> = graph.split(';').collect();
+
+        // The pictures cut at 10s while a's audio runs 1.5s under b, and the track stays 16s long
+        assert!(parts.iter().any(|p| p.starts_with("[0:v]trim=start=0:duration=10,")));
+        assert!(parts.iter().any(|p| p.starts_with("[1:v]trim=start=2:duration=6,")));
+        assert!(parts.iter().any(|p| p.contains("atrim=duration=11.5[a0]")));
+        assert!(parts.iter().any(|p| p.contains("atrim=duration=4.5[a1]")));
+        assert!(parts.contains(&"[v0][v1]concat=n=2:v=1:a=0[outv]"));
+        assert!(parts.contains(&"[a0][a1]concat=n=2:v=0:a=1[outa]"));
+        assert_eq!(plan.total_duration, 16.0);
+    }
+
+    #[test]
+    fn test_clip_volume_scale() {
+        let filter = build_clip_audio_filter(2, true, 1.5, 3.0, 150.0, false, "[a2]");
+        assert!(filter.starts_with("[2:a]atrim=start=1.5:duration=3"));
+        assert!(filter.contains("volume=1.5"));
+    }
+}
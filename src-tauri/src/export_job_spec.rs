@@ -0,0 +1,113 @@
+// ClipForge - Export Job Spec Module
+// The persisted description of a queued export and how it compiles into an FFmpeg plan
+
+use serde::{Deserialize, Serialize};
+
+use crate::audio_tracks::AudioTrack;
+use crate::export::{build_export_plan, ClipData};
+use crate::export_plan::ExportPlan;
+use crate::export_progress::RunningExport;
+use crate::export_settings::ExportSettings;
+use crate::image_overlay::ImageLayer;
+use crate::loudness::{audio_track_audio, main_clip_audio, normalize_clip_loudness, pip_clip_audio, timeline_clip_audio};
+use crate::multi_track::{build_multi_track_plan, PipClipData};
+use crate::music::MusicTrack;
+use crate::timeline::{build_timeline_plan, TimelineData};
+
+/// Everything needed to render a job, persisted so it can run after a restart
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "kind")]
+pub enum ExportJobSpec {
+    #[serde(rename = "single")]
+    Single {
+        clips: Vec<ClipData>,
+        #[serde(rename = "outputPath")]
+        output_path: String,
+        width: Option<i32>,
+        height: Option<i32>,
+        #[serde(default)]
+        settings: ExportSettings,
+    },
+    #[serde(rename = "multiTrack")]
+    MultiTrack {
+        #[serde(rename = "mainTrackClips")]
+        main_track_clips: Vec<ClipData>,
+        #[serde(rename = "pipTrackClips")]
+        pip_track_clips: Vec<PipClipData>,
+        #[serde(rename = "imageLayers", default)]
+        image_layers: Vec<ImageLayer>,
+        #[serde(rename = "audioTracks", default)]
+        audio_tracks: Vec<AudioTrack>,
+        #[serde(rename = "musicTrack", default)]
+        music_track: Option<MusicTrack>,
+        #[serde(rename = "outputPath")]
+        output_path: String,
+        width: Option<i32>,
+        height: Option<i32>,
+        #[serde(default)]
+        settings: ExportSettings,
+    },
+    #[serde(rename = "timeline")]
+    Timeline {
+        timeline: TimelineData,
+        #[serde(rename = "outputPath")]
+        output_path: String,
+        width: Option<i32>,
+        height: Option<i32>,
+        #[serde(default)]
+        settings: ExportSettings,
+    },
+}
+
+impl ExportJobSpec {
+    pub fn output_path(&self) -> &str {
+        match self {
+            ExportJobSpec::Single { output_path, .. } => output_path,
+            ExportJobSpec::MultiTrack { output_path, .. } => output_path,
+            ExportJobSpec::Timeline { output_path, .. } => output_path,
+        }
+    }
+
+    pub(crate) fn settings(&self) -> &ExportSettings {
+        match self {
+            ExportJobSpec::Single { settings, .. } => settings,
+            ExportJobSpec::MultiTrack { settings, .. } => settings,
+            ExportJobSpec::Timeline { settings, .. } => settings,
+        }
+    }
+
+    /// Fold per-clip loudness gains into the clip volumes, as the export commands do
+    pub(crate) async fn normalize_clip_loudness(&mut self, export: &RunningExport) -> Result<(), String> {
+        let settings = self.settings().clone();
+        let clip_audio = match self {
+            ExportJobSpec::Single { clips, .. } => main_clip_audio(clips),
+            ExportJobSpec::MultiTrack { main_track_clips, pip_track_clips, audio_tracks, .. } => {
+                let mut clip_audio = main_clip_audio(main_track_clips);
+                clip_audio.extend(pip_clip_audio(pip_track_clips));
+                clip_audio.extend(audio_track_audio(audio_tracks));
+                clip_audio
+            }
+            ExportJobSpec::Timeline { timeline, .. } => timeline_clip_audio(timeline),
+        };
+        normalize_clip_loudness(export, clip_audio, settings.loudness.as_ref()).await
+    }
+
+    /// Compile the spec with the same filter-graph builders the export commands use
+    pub fn plan(&self) -> Result<ExportPlan, String> {
+        self.plan_with_settings(self.settings())
+    }
+
+    pub(crate) fn plan_with_settings(&self, settings: &ExportSettings) -> Result<ExportPlan, String> {
+        match self {
+            ExportJobSpec::Single { clips, output_path, width, height, .. } => {
+                build_export_plan(clips, output_path, *width, *height, settings)
+            }
+            ExportJobSpec::MultiTrack { main_track_clips, pip_track_clips, image_layers, audio_tracks, music_track, output_path, width, height, .. } => {
+                build_multi_track_plan(main_track_clips, pip_track_clips, image_layers, audio_tracks, music_track.as_ref(), output_path, *width, *height, settings)
+            }
+            ExportJobSpec::Timeline { timeline, output_path, width, height, .. } => {
+                build_timeline_plan(timeline, output_path, *width, *height, settings)
+            }
+        }
+    }
+}
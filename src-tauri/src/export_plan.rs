@@ -0,0 +1,133 @@
+// ClipForge - Export Plan Module
+// The FFmpeg arguments an export builds, and running their passes (loudness analysis, two-pass encodes)
+
+use std::fs;
+use std::path::Path;
+
+use crate::captions::{apply_captions, subtitle_args};
+use crate::export_progress::{FfmpegRunResult, RunningExport};
+use crate::export_settings::ExportSettings;
+use crate::image_overlay::apply_watermark;
+
+/// FFmpeg arguments for an export, plus the timeline duration used for progress reporting
+pub struct ExportPlan {
+    pub args: Vec<String>,
+    /// Statistics-only first pass, present for two-pass encodes
+    pub first_pass_args: Option<Vec<String>>,
+    /// loudnorm measuring pass, present while a normalized mix has not been measured
+    pub loudness_pass_args: Option<Vec<String>>,
+    pub total_duration: f64,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl ExportPlan {
+    /// FFmpeg runs the plan takes, each over the whole timeline
+    pub fn pass_count(&self) -> usize {
+        1 + usize::from(self.first_pass_args.is_some()) + usize::from(self.loudness_pass_args.is_some())
+    }
+}
+
+/// Stamp the watermark and captions on the finished video. Returns the final video label and
+/// any extra -map arguments for soft caption tracks.
+pub(crate) fn apply_export_overlays(
+    cmd_args: &mut Vec<String>,
+    filter_parts: &mut Vec<String>,
+    settings: &ExportSettings,
+    input_index: usize,
+    current: &str,
+    canvas_width: i32,
+    duration: f64,
+) -> Result<(String, Vec<String>), String> {
+    let video_output = apply_watermark(cmd_args, filter_parts, settings, input_index, current, canvas_width, duration)?;
+    let next_input = input_index + usize::from(settings.watermark.is_some());
+    apply_captions(cmd_args, filter_parts, settings, next_input, &video_output)
+}
+
+/// Append encoder options and the output path. Two-pass encodes also get a first pass
+/// that only writes encoder statistics.
+pub(crate) fn finish_output_args(
+    base_args: Vec<String>,
+    settings: &ExportSettings,
+    output_path: &str,
+) -> Result<(Vec<String>, Option<Vec<String>>), String> {
+    settings.validate()?;
+    
+    if !settings.two_pass {
+        let mut args = base_args;
+        args.extend(settings.video_args(None)?);
+        args.extend(settings.audio_args());
+        args.extend(subtitle_args(settings));
+        args.extend(settings.container_args());
+        args.push("-y".to_string());
+        args.push(output_path.to_string());
+        return Ok((args, None));
+    }
+    
+    let passlog = passlog_prefix(output_path)?;
+    
+    // First pass keeps the audio mapping (cheap PCM) so the filter graph stays fully connected
+    let mut first_pass = base_args.clone();
+    first_pass.extend(settings.video_args(Some((1, &passlog)))?);
+    first_pass.extend(["-c:a", "pcm_s16le", "-sn", "-f", "null", "-y", "-"].map(String::from));
+    
+    let mut second_pass = base_args;
+    second_pass.extend(settings.video_args(Some((2, &passlog)))?);
+    second_pass.extend(settings.audio_args());
+    second_pass.extend(subtitle_args(settings));
+    second_pass.extend(settings.container_args());
+    second_pass.push("-y".to_string());
+    second_pass.push(output_path.to_string());
+    
+    Ok((second_pass, Some(first_pass)))
+}
+
+/// Temp file prefix for two-pass statistics, unique per output file
+fn passlog_prefix(output_path: &str) -> Result<String, String> {
+    let passlog_dir = std::env::temp_dir().join("clipforge_passlog");
+    fs::create_dir_all(&passlog_dir)
+        .map_err(|e| format!("Failed to create pass log directory: {}", e))?;
+    
+    let passlog = passlog_dir.join(format!("pass_{:x}", md5::compute(output_path.as_bytes())));
+    Ok(passlog.to_string_lossy().to_string())
+}
+
+/// Run every FFmpeg pass of a plan, stopping at the first failed pass
+pub async fn run_export_plan(
+    export: &RunningExport,
+    ffmpeg_path: &Path,
+    plan: &ExportPlan,
+    output_path: &str,
+) -> Result<FfmpegRunResult, String> {
+    // The first pass only writes statistics, so a cancel there leaves any existing output alone
+    let result = match &plan.first_pass_args {
+        Some(first_pass_args) => {
+            let first_pass = export.run_ffmpeg(ffmpeg_path, first_pass_args, None, plan.total_duration).await;
+            match first_pass {
+                Ok(output) if output.success => {
+                    export.run_ffmpeg(ffmpeg_path, &plan.args, Some(output_path), plan.total_duration).await
+                }
+                other => other,
+            }
+        }
+        None => export.run_ffmpeg(ffmpeg_path, &plan.args, Some(output_path), plan.total_duration).await,
+    };
+    
+    // Clean up two-pass statistics files (x264 writes <prefix>-0.log and .mbtree)
+    if plan.first_pass_args.is_some() {
+        if let Ok(passlog) = passlog_prefix(output_path) {
+            let passlog = Path::new(&passlog);
+            if let (Some(dir), Some(prefix)) = (passlog.parent(), passlog.file_name().and_then(|n| n.to_str())) {
+                if let Ok(entries) = fs::read_dir(dir) {
+                    for entry in entries.flatten() {
+                        if entry.file_name().to_string_lossy().starts_with(prefix) {
+                            let _ = fs::remove_file(entry.path());
+                        }
+                    }
+                }
+            }
+        }
+    }
+    
+    result
+}
@@ -0,0 +1,422 @@
+// ClipForge - Export Progress Module
+// Runs FFmpeg exports as child processes, emits progress events and handles cancellation
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::Instant;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+
+/// Event name the frontend listens on for export progress updates
+pub const EXPORT_PROGRESS_EVENT: &str = "export-progress";
+
+/// Error returned when an export is stopped through `cancel_export`
+pub const EXPORT_CANCELLED: &str = "Export cancelled";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportProgress {
+    #[serde(rename = "exportId")]
+    pub export_id: String,
+    pub percent: f64,
+    pub frame: u64,
+    pub fps: f64,
+    pub speed: Option<f64>,
+    #[serde(rename = "outTime")]
+    pub out_time: f64,
+    #[serde(rename = "totalDuration")]
+    pub total_duration: f64,
+    #[serde(rename = "etaSeconds")]
+    pub eta_seconds: Option<f64>,
+    pub done: bool,
+}
+
+/// Exports that are currently running, keyed by export id so they can be cancelled
+#[derive(Default)]
+pub struct ExportRegistry {
+    running: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl ExportRegistry {
+    fn register(&self, export_id: &str) -> Result<CancellationToken, String> {
+        let mut running = self.running.lock().map_err(|_| "Export registry is unavailable".to_string())?;
+        if running.contains_key(export_id) {
+            return Err(format!("An export with id '{}' is already running", export_id));
+        }
+
+        let token = CancellationToken::new();
+        running.insert(export_id.to_string(), token.clone());
+        Ok(token)
+    }
+
+    fn unregister(&self, export_id: &str) {
+        if let Ok(mut running) = self.running.lock() {
+            running.remove(export_id);
+        }
+    }
+
+    pub fn cancel(&self, export_id: &str) -> bool {
+        match self.running.lock() {
+            Ok(running) => match running.get(export_id) {
+                Some(token) => {
+                    token.cancel();
+                    true
+                }
+                None => false,
+            },
+            Err(_) => false,
+        }
+    }
+}
+
+/// Result of an FFmpeg run that was not cancelled
+pub struct FfmpegRunResult {
+    pub success: bool,
+    pub stderr: String,
+}
+
+/// Accumulates the key=value blocks FFmpeg writes with `-progress`
+#[derive(Default)]
+struct ProgressParser {
+    frame: u64,
+    fps: f64,
+    speed: Option<f64>,
+    out_time: f64,
+}
+
+impl ProgressParser {
+    /// Apply one line of progress output. Returns `Some(is_end)` when a block is complete.
+    fn apply_line(&mut self, line: &str) -> Option<bool> {
+        let (key, value) = line.trim().split_once('=')?;
+        let value = value.trim();
+
+        match key {
+            "frame" => self.frame = value.parse().unwrap_or(self.frame),
+            "fps" => self.fps = value.parse().unwrap_or(self.fps),
+            // out_time_ms is actually reported in microseconds by FFmpeg
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(us) = value.parse::<i64>() {
+                    self.out_time = (us.max(0) as f64) / 1_000_000.0;
+                }
+            }
+            "speed" => self.speed = value.trim_end_matches('x').parse().ok().filter(|s: &f64| *s > 0.0),
+            "progress" => return Some(value == "end"),
+            _ => {}
+        }
+
+        None
+    }
+
+    fn snapshot(&self, export_id: &str, span: ProgressSpan, elapsed: f64, is_end: bool) -> ExportProgress {
+        let run_time = if is_end { span.duration } else { self.out_time.min(span.duration) };
+        let out_time = span.offset + run_time;
+        // Only the export's last run finishes it
+        let done = is_end && span.offset + span.duration >= span.total - 1e-6;
+        let percent = if done {
+            100.0
+        } else if span.total > 0.0 {
+            (out_time / span.total * 100.0).clamp(0.0, 100.0)
+        } else {
+            0.0
+        };
+
+        let remaining = (span.total - out_time).max(0.0);
+        let eta_seconds = if done {
+            Some(0.0)
+        } else if let Some(speed) = self.speed {
+            Some(remaining / speed)
+        } else if run_time > 0.0 {
+            // No speed reported yet - extrapolate from this run's wall-clock time
+            Some(elapsed * remaining / run_time)
+        } else {
+            None
+        };
+
+        ExportProgress {
+            export_id: export_id.to_string(),
+            percent,
+            frame: self.frame,
+            fps: self.fps,
+            speed: self.speed,
+            out_time,
+            total_duration: span.total,
+            eta_seconds,
+            done,
+        }
+    }
+}
+
+/// Where one FFmpeg run falls within its export, in seconds of media processed
+#[derive(Debug, Clone, Copy)]
+struct ProgressSpan {
+    offset: f64,
+    duration: f64,
+    total: f64,
+}
+
+/// Seconds of media processed by an export's finished runs, out of all its planned runs
+#[derive(Default)]
+struct PassProgress {
+    completed: f64,
+    total: f64,
+}
+
+/// An export registered under its id from the command entry until it is dropped. Every FFmpeg run
+/// of the export goes through it, so `cancel_export` also stops it between runs, and progress is
+/// reported over all of its runs rather than restarting at 0% for each.
+pub struct RunningExport {
+    app: AppHandle,
+    export_id: String,
+    token: CancellationToken,
+    progress: Mutex<PassProgress>,
+}
+
+impl RunningExport {
+    pub fn start(app: &AppHandle, export_id: &str) -> Result<Self, String> {
+        let token = app.state::<ExportRegistry>().register(export_id)?;
+        Ok(Self {
+            app: app.clone(),
+            export_id: export_id.to_string(),
+            token,
+            progress: Mutex::new(PassProgress::default()),
+        })
+    }
+
+    /// Set the media seconds the export's remaining runs will process, restarting progress at 0%
+    pub fn plan_progress(&self, total: f64) {
+        if let Ok(mut progress) = self.progress.lock() {
+            *progress = PassProgress { completed: 0.0, total };
+        }
+    }
+
+    pub fn check_cancelled(&self) -> Result<(), String> {
+        if self.token.is_cancelled() {
+            Err(EXPORT_CANCELLED.to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Run FFmpeg as the export's next `duration` seconds of progress. `writes` is the file this
+    /// run writes, removed if the run is cancelled; analysis and first passes write none.
+    pub async fn run_ffmpeg(
+        &self,
+        ffmpeg_path: &Path,
+        args: &[String],
+        writes: Option<&str>,
+        duration: f64,
+    ) -> Result<FfmpegRunResult, String> {
+        self.check_cancelled()?;
+        let span = match self.progress.lock() {
+            Ok(progress) => ProgressSpan {
+                offset: progress.completed,
+                duration,
+                total: progress.total.max(progress.completed + duration),
+            },
+            Err(_) => ProgressSpan { offset: 0.0, duration, total: duration },
+        };
+
+        let result = run_ffmpeg_with_token(&self.app, ffmpeg_path, args, &self.export_id, span, &self.token).await;
+        match &result {
+            Err(e) if e == EXPORT_CANCELLED => {
+                if let Some(path) = writes.filter(|p| Path::new(p).exists()) {
+                    if let Err(e) = std::fs::remove_file(path) {
+                        eprintln!("Warning: Failed to remove partial export {}: {}", path, e);
+                    }
+                }
+            }
+            Ok(_) => {
+                if let Ok(mut progress) = self.progress.lock() {
+                    progress.completed += duration;
+                }
+            }
+            Err(_) => {}
+        }
+        result
+    }
+
+    /// Run an FFmpeg pass that reports no progress, such as a loudness analysis
+    pub async fn run_ffmpeg_quiet(&self, ffmpeg_path: &Path, args: &[String]) -> Result<FfmpegRunResult, String> {
+        self.check_cancelled()?;
+        run_ffmpeg_until_cancelled(ffmpeg_path, args, &self.token).await
+    }
+}
+
+impl Drop for RunningExport {
+    fn drop(&mut self) {
+        self.app.state::<ExportRegistry>().unregister(&self.export_id);
+    }
+}
+
+/// Run FFmpeg with the given arguments, emitting progress events until it exits or `token` is cancelled
+async fn run_ffmpeg_with_token(
+    app: &AppHandle,
+    ffmpeg_path: &Path,
+    args: &[String],
+    export_id: &str,
+    span: ProgressSpan,
+    token: &CancellationToken,
+) -> Result<FfmpegRunResult, String> {
+    let mut child = Command::new(ffmpeg_path)
+        .args(["-progress", "pipe:1", "-nostats"])
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
+
+    let stdout = child.stdout.take().ok_or("Failed to capture FFmpeg progress output")?;
+    let mut stderr = child.stderr.take().ok_or("Failed to capture FFmpeg error output")?;
+
+    // Parse the progress stream on a background task
+    let progress_app = app.clone();
+    let progress_id = export_id.to_string();
+    let started = Instant::now();
+    let progress_task = tokio::spawn(async move {
+        let mut parser = ProgressParser::default();
+        let mut lines = BufReader::new(stdout).lines();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            if let Some(is_end) = parser.apply_line(&line) {
+                let progress = parser.snapshot(&progress_id, span, started.elapsed().as_secs_f64(), is_end);
+                let _ = progress_app.emit(EXPORT_PROGRESS_EVENT, progress);
+            }
+        }
+    });
+
+    // Drain stderr concurrently so FFmpeg never blocks on a full pipe
+    let stderr_task = tokio::spawn(async move {
+        let mut buffer = String::new();
+        let _ = stderr.read_to_string(&mut buffer).await;
+        buffer
+    });
+
+    let status = tokio::select! {
+        status = child.wait() => Some(status),
+        _ = token.cancelled() => None,
+    };
+
+    match status {
+        Some(status) => {
+            let status = status.map_err(|e| format!("Failed to wait for FFmpeg: {}", e))?;
+            let _ = progress_task.await;
+            let stderr = stderr_task.await.unwrap_or_default();
+
+            Ok(FfmpegRunResult {
+                success: status.success(),
+                stderr,
+            })
+        }
+        None => {
+            let _ = child.kill().await;
+            progress_task.abort();
+            stderr_task.abort();
+
+            Err(EXPORT_CANCELLED.to_string())
+        }
+    }
+}
+
+/// Run an FFmpeg pass without progress output, killing it if `token` is cancelled
+async fn run_ffmpeg_until_cancelled(
+    ffmpeg_path: &Path,
+    args: &[String],
+    token: &CancellationToken,
+) -> Result<FfmpegRunResult, String> {
+    let child = Command::new(ffmpeg_path)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
+
+    // Dropping the pending wait on cancel drops the child, which kills it
+    tokio::select! {
+        output = child.wait_with_output() => {
+            let output = output.map_err(|e| format!("Failed to wait for FFmpeg: {}", e))?;
+            Ok(FfmpegRunResult {
+                success: output.status.success(),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            })
+        }
+        _ = token.cancelled() => Err(EXPORT_CANCELLED.to_string()),
+    }
+}
+
+/// Cancel a running export and remove its partial output file
+#[tauri::command]
+pub async fn cancel_export(app: AppHandle, export_id: String) -> Result<(), String> {
+    if app.state::<ExportRegistry>().cancel(&export_id) {
+        Ok(())
+    } else {
+        Err(format!("No running export with id '{}'", export_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_block_parsing() {
+        let mut parser = ProgressParser::default();
+        assert_eq!(parser.apply_line("frame=120"), None);
+        assert_eq!(parser.apply_line("fps=60.0"), None);
+        assert_eq!(parser.apply_line("out_time_us=5000000"), None);
+        assert_eq!(parser.apply_line("speed=2.5x"), None);
+        assert_eq!(parser.apply_line("progress=continue"), Some(false));
+
+        let progress = parser.snapshot("job", ProgressSpan { offset: 0.0, duration: 20.0, total: 20.0 }, 2.0, false);
+        assert_eq!(progress.frame, 120);
+        assert_eq!(progress.percent, 25.0);
+        assert_eq!(progress.eta_seconds, Some(6.0));
+    }
+
+    #[test]
+    fn test_progress_spans_every_pass() {
+        let mut parser = ProgressParser::default();
+        parser.apply_line("out_time_us=5000000");
+        parser.apply_line("speed=2x");
+
+        // Halfway through the second of two 10s passes
+        let second_pass = ProgressSpan { offset: 10.0, duration: 10.0, total: 20.0 };
+        let progress = parser.snapshot("job", second_pass, 2.5, false);
+        assert_eq!((progress.out_time, progress.percent, progress.eta_seconds), (15.0, 75.0, Some(2.5)));
+
+        // The end of a first pass is not the end of the export
+        let first_pass = ProgressSpan { offset: 0.0, duration: 10.0, total: 20.0 };
+        let progress = parser.snapshot("job", first_pass, 5.0, true);
+        assert!(!progress.done);
+        assert_eq!(progress.percent, 50.0);
+        assert!(parser.snapshot("job", second_pass, 5.0, true).done);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_pass_is_killed() {
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let started = Instant::now();
+        let result = run_ffmpeg_until_cancelled(Path::new("sleep"), &["5".to_string()], &token).await;
+        assert_eq!(result.err().as_deref(), Some(EXPORT_CANCELLED));
+        assert!(started.elapsed().as_secs() < 5);
+    }
+
+    #[test]
+    fn test_progress_speed_na() {
+        let mut parser = ProgressParser::default();
+        parser.apply_line("out_time_us=10000000");
+        parser.apply_line("speed=N/A");
+
+        // Falls back to wall-clock extrapolation: 4s elapsed for 10s of 20s output
+        let progress = parser.snapshot("job", ProgressSpan { offset: 0.0, duration: 20.0, total: 20.0 }, 4.0, false);
+        assert_eq!(progress.speed, None);
+        assert_eq!(progress.eta_seconds, Some(4.0));
+    }
+}
@@ -0,0 +1,398 @@
+// ClipForge - Export Queue Module
+// Persistent queue of export jobs rendered in the background with a concurrency limit
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::get_ffmpeg_path;
+use crate::export::parse_ffmpeg_error;
+use crate::export_job_spec::ExportJobSpec;
+use crate::export_plan::run_export_plan;
+use crate::export_progress::{ExportRegistry, RunningExport, EXPORT_CANCELLED};
+use crate::loudness::plan_with_loudness;
+
+/// Event emitted whenever a job changes status
+pub const EXPORT_JOB_EVENT: &str = "export-job-updated";
+
+const DEFAULT_MAX_CONCURRENT_EXPORTS: usize = 2;
+const MAX_CONCURRENT_EXPORTS_LIMIT: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportJobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExportJob {
+    pub id: String,
+    pub spec: ExportJobSpec,
+    pub status: ExportJobStatus,
+    pub error: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename = "finishedAt")]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct QueueState {
+    jobs: Vec<ExportJob>,
+    #[serde(rename = "maxConcurrent")]
+    max_concurrent: usize,
+}
+
+impl QueueState {
+    /// Mark queued jobs as running until the concurrency limit is reached. Returns the jobs to start.
+    fn start_queued(&mut self) -> Vec<ExportJob> {
+        let running = self.jobs.iter().filter(|j| j.status == ExportJobStatus::Running).count();
+        let available = self.max_concurrent.saturating_sub(running);
+
+        let mut started = Vec::new();
+        for job in self.jobs.iter_mut().filter(|j| j.status == ExportJobStatus::Queued).take(available) {
+            job.status = ExportJobStatus::Running;
+            job.error = None;
+            job.finished_at = None;
+            started.push(job.clone());
+        }
+        started
+    }
+
+    /// Queue a failed or cancelled job again
+    fn retry(&mut self, job_id: &str) -> Result<&ExportJob, String> {
+        let job = self.jobs.iter_mut()
+            .find(|j| j.id == job_id)
+            .ok_or_else(|| format!("Export job '{}' not found", job_id))?;
+
+        if !matches!(job.status, ExportJobStatus::Failed | ExportJobStatus::Cancelled) {
+            return Err("Only failed or cancelled export jobs can be retried".to_string());
+        }
+
+        job.status = ExportJobStatus::Queued;
+        job.error = None;
+        job.finished_at = None;
+        Ok(job)
+    }
+}
+
+pub struct ExportQueue {
+    state: Mutex<QueueState>,
+    store_path: PathBuf,
+}
+
+impl ExportQueue {
+    /// Load the persisted queue. Jobs that were running when the app exited are queued again.
+    pub fn load() -> Self {
+        Self::load_from(queue_store_path())
+    }
+
+    fn load_from(store_path: PathBuf) -> Self {
+        let mut state = fs::read_to_string(&store_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<QueueState>(&content).ok())
+            .unwrap_or(QueueState {
+                jobs: Vec::new(),
+                max_concurrent: DEFAULT_MAX_CONCURRENT_EXPORTS,
+            });
+
+        for job in state.jobs.iter_mut().filter(|j| j.status == ExportJobStatus::Running) {
+            job.status = ExportJobStatus::Queued;
+        }
+
+        Self {
+            state: Mutex::new(state),
+            store_path,
+        }
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, QueueState>, String> {
+        self.state.lock().map_err(|_| "Export queue is unavailable".to_string())
+    }
+
+    fn save(&self, state: &QueueState) {
+        if let Some(parent) = self.store_path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+
+        match serde_json::to_string_pretty(state) {
+            Ok(json) => {
+                if let Err(e) = fs::write(&self.store_path, json) {
+                    eprintln!("Warning: Failed to save export queue: {}", e);
+                }
+            }
+            Err(e) => eprintln!("Warning: Failed to serialize export queue: {}", e),
+        }
+    }
+}
+
+fn queue_store_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("ClipForge")
+        .join("export_jobs.json")
+}
+
+/// Start queued jobs until the concurrency limit is reached
+pub fn schedule_export_jobs(app: &AppHandle) {
+    let queue = app.state::<ExportQueue>();
+
+    // Jobs are registered while the queue is locked, so a cancel never finds a running job
+    // without a token to cancel
+    let to_start: Vec<_> = match queue.lock() {
+        Ok(mut state) => {
+            let started = state.start_queued();
+            if !started.is_empty() {
+                queue.save(&state);
+            }
+            started.into_iter()
+                .map(|job| {
+                    let export = RunningExport::start(app, &job.id);
+                    (job, export)
+                })
+                .collect()
+        }
+        Err(e) => {
+            eprintln!("Failed to schedule export jobs: {}", e);
+            return;
+        }
+    };
+
+    for (job, export) in to_start {
+        let _ = app.emit(EXPORT_JOB_EVENT, job.clone());
+
+        let export = match export {
+            Ok(export) => export,
+            Err(e) => {
+                finish_export_job(app, &job.id, Err(e));
+                continue;
+            }
+        };
+
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let result = run_export_job(&export, &job).await;
+            // Unregister before the job can be retried under the same id
+            drop(export);
+            finish_export_job(&app, &job.id, result);
+            schedule_export_jobs(&app);
+        });
+    }
+}
+
+/// The job id doubles as the export id, so cancel_export works on queued jobs too
+async fn run_export_job(export: &RunningExport, job: &ExportJob) -> Result<(), String> {
+    let ffmpeg_path = get_ffmpeg_path()?;
+    let mut spec = job.spec.clone();
+
+    spec.normalize_clip_loudness(export).await?;
+    let settings = spec.settings().clone();
+    let plan = plan_with_loudness(export, &ffmpeg_path, &settings, |s| spec.plan_with_settings(s)).await?;
+    let output = run_export_plan(export, &ffmpeg_path, &plan, job.spec.output_path()).await?;
+
+    if !output.success {
+        return Err(parse_ffmpeg_error(&output.stderr));
+    }
+
+    Ok(())
+}
+
+fn finish_export_job(app: &AppHandle, job_id: &str, result: Result<(), String>) {
+    let queue = app.state::<ExportQueue>();
+    let Ok(mut state) = queue.lock() else {
+        return;
+    };
+
+    if let Some(job) = state.jobs.iter_mut().find(|j| j.id == job_id) {
+        match result {
+            Ok(()) => job.status = ExportJobStatus::Completed,
+            Err(e) if e == EXPORT_CANCELLED => job.status = ExportJobStatus::Cancelled,
+            Err(e) => {
+                job.status = ExportJobStatus::Failed;
+                job.error = Some(e);
+            }
+        }
+        job.finished_at = Some(Utc::now());
+        let _ = app.emit(EXPORT_JOB_EVENT, job.clone());
+    }
+
+    queue.save(&state);
+}
+
+/// Add an export to the queue and return its job id
+#[tauri::command]
+pub async fn enqueue_export(app: AppHandle, spec: ExportJobSpec) -> Result<String, String> {
+    // Validate up front so bad specs fail immediately instead of in the background
+    spec.plan()?;
+
+    let job = {
+        let queue = app.state::<ExportQueue>();
+        let mut state = queue.lock()?;
+
+        let job = ExportJob {
+            id: format!("export-{}-{}", Utc::now().timestamp_millis(), state.jobs.len()),
+            spec,
+            status: ExportJobStatus::Queued,
+            error: None,
+            created_at: Utc::now(),
+            finished_at: None,
+        };
+        state.jobs.push(job.clone());
+        queue.save(&state);
+        job
+    };
+
+    let _ = app.emit(EXPORT_JOB_EVENT, job.clone());
+    schedule_export_jobs(&app);
+
+    Ok(job.id)
+}
+
+#[tauri::command]
+pub async fn list_export_jobs(app: AppHandle) -> Result<Vec<ExportJob>, String> {
+    let queue = app.state::<ExportQueue>();
+    let state = queue.lock()?;
+    Ok(state.jobs.clone())
+}
+
+/// Queue a failed or cancelled job again
+#[tauri::command]
+pub async fn retry_export_job(app: AppHandle, job_id: String) -> Result<(), String> {
+    {
+        let queue = app.state::<ExportQueue>();
+        let mut state = queue.lock()?;
+
+        let job = state.retry(&job_id)?;
+        let _ = app.emit(EXPORT_JOB_EVENT, job.clone());
+        queue.save(&state);
+    }
+
+    schedule_export_jobs(&app);
+    Ok(())
+}
+
+/// Cancel a queued job, or stop a running one
+#[tauri::command]
+pub async fn cancel_export_job(app: AppHandle, job_id: String) -> Result<(), String> {
+    let queue = app.state::<ExportQueue>();
+    let mut state = queue.lock()?;
+
+    let job = state.jobs.iter_mut()
+        .find(|j| j.id == job_id)
+        .ok_or_else(|| format!("Export job '{}' not found", job_id))?;
+
+    match job.status {
+        ExportJobStatus::Queued => {
+            job.status = ExportJobStatus::Cancelled;
+            job.finished_at = Some(Utc::now());
+            let _ = app.emit(EXPORT_JOB_EVENT, job.clone());
+            queue.save(&state);
+            Ok(())
+        }
+        // The job is registered for as long as it runs, so cancelling its token stops the
+        // current FFmpeg run or the next one. The job task records the cancellation.
+        ExportJobStatus::Running => {
+            app.state::<ExportRegistry>().cancel(&job_id);
+            Ok(())
+        }
+        _ => Err("Export job has already finished".to_string()),
+    }
+}
+
+/// Set how many export jobs may render at the same time
+#[tauri::command]
+pub async fn set_export_concurrency(app: AppHandle, max_concurrent: usize) -> Result<usize, String> {
+    let limit = max_concurrent.clamp(1, MAX_CONCURRENT_EXPORTS_LIMIT);
+    {
+        let queue = app.state::<ExportQueue>();
+        let mut state = queue.lock()?;
+        state.max_concurrent = limit;
+        queue.save(&state);
+    }
+
+    schedule_export_jobs(&app);
+    Ok(limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_queue(name: &str) -> ExportQueue {
+        let store_path = std::env::temp_dir()
+            .join(format!("clipforge_queue_test_{}_{}", name, std::process::id()))
+            .join("export_jobs.json");
+        let _ = fs::remove_file(&store_path);
+        ExportQueue::load_from(store_path)
+    }
+
+    fn job(id: &str, status: ExportJobStatus) -> ExportJob {
+        let spec = serde_json::from_value(serde_json::json!({
+            "kind": "single",
+            "clips": [{ "path": "a.mp4", "duration": 10.0, "inPoint": 1.0, "outPoint": 9.0, "volume": 80.0, "muted": false }],
+            "outputPath": "out.mp4",
+            "width": 1920,
+            "height": 1080,
+            "settings": { "codec": "h265", "container": "mkv", "crf": 20 }
+        })).unwrap();
+        ExportJob {
+            id: id.to_string(),
+            spec,
+            status,
+            error: None,
+            created_at: Utc::now(),
+            finished_at: None,
+        }
+    }
+
+    #[test]
+    fn test_jobs_survive_restart() {
+        let queue = test_queue("restart");
+        {
+            let mut state = queue.lock().unwrap();
+            state.jobs = vec![job("a", ExportJobStatus::Running), job("b", ExportJobStatus::Completed)];
+            queue.save(&state);
+        }
+
+        let reloaded = ExportQueue::load_from(queue.store_path.clone());
+        let state = reloaded.lock().unwrap();
+        let spec_json = |j: &ExportJob| serde_json::to_value(&j.spec).unwrap();
+        assert_eq!(spec_json(&state.jobs[0]), spec_json(&job("a", ExportJobStatus::Queued)));
+
+        // Interrupted jobs run again; finished ones stay finished
+        assert_eq!(state.jobs[0].status, ExportJobStatus::Queued);
+        assert_eq!(state.jobs[1].status, ExportJobStatus::Completed);
+        let _ = fs::remove_dir_all(queue.store_path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_concurrency_limit_and_retry() {
+        let mut state = QueueState {
+            jobs: ["a", "b", "c"].iter().map(|id| job(id, ExportJobStatus::Queued)).collect(),
+            max_concurrent: 2,
+        };
+
+        let started: Vec<String> = state.start_queued().into_iter().map(|j| j.id).collect();
+        assert_eq!(started, ["a", "b"]);
+        assert!(state.start_queued().is_empty());
+
+        state.jobs[0].status = ExportJobStatus::Failed;
+        state.jobs[0].error = Some("boom".to_string());
+        assert_eq!(state.start_queued()[0].id, "c");
+
+        // Failed and cancelled jobs can be queued again; others can't
+        assert!(state.retry("b").is_err());
+        assert_eq!(state.retry("a").unwrap().status, ExportJobStatus::Queued);
+        assert_eq!(state.jobs[0].error, None);
+        state.jobs[2].status = ExportJobStatus::Cancelled;
+        assert!(state.retry("c").is_ok());
+        assert!(state.retry("missing").is_err());
+    }
+}
@@ -0,0 +1,420 @@
+// ClipForge - Export Settings Module
+// Encoder, container and quality options for exports, plus named presets
+
+use std::process::Command;
+use std::sync::OnceLock;
+use serde::{Deserialize, Serialize};
+
+use crate::get_ffmpeg_path;
+use crate::captions::ExportCaptions;
+use crate::image_overlay::Watermark;
+use crate::loudness::LoudnessSettings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodec {
+    H264,
+    H265,
+    Vp9,
+    Av1,
+    ProRes,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Container {
+    Mp4,
+    Mov,
+    Webm,
+    Mkv,
+}
+
+impl Container {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Mov => "mov",
+            Container::Webm => "webm",
+            Container::Mkv => "mkv",
+        }
+    }
+
+    /// FFmpeg muxer name, passed with -f so the output extension can't pick a different one
+    fn muxer(&self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Mov => "mov",
+            Container::Webm => "webm",
+            Container::Mkv => "matroska",
+        }
+    }
+
+    fn supports(&self, codec: VideoCodec) -> bool {
+        match self {
+            Container::Mp4 => matches!(codec, VideoCodec::H264 | VideoCodec::H265 | VideoCodec::Av1),
+            Container::Mov => matches!(codec, VideoCodec::H264 | VideoCodec::H265 | VideoCodec::ProRes),
+            Container::Webm => matches!(codec, VideoCodec::Vp9 | VideoCodec::Av1),
+            Container::Mkv => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ExportSettings {
+    pub codec: VideoCodec,
+    pub container: Container,
+    /// Constant quality value; ignored when a target bitrate is set
+    pub crf: Option<u32>,
+    #[serde(rename = "videoBitrateKbps")]
+    pub video_bitrate_kbps: Option<u32>,
+    #[serde(rename = "twoPass")]
+    pub two_pass: bool,
+    /// None picks the codec's usual format (see `pixel_format()`)
+    #[serde(rename = "pixelFormat")]
+    pub pixel_format: Option<String>,
+    #[serde(rename = "frameRate")]
+    pub frame_rate: Option<f64>,
+    /// Encoder speed preset (x264/x265 preset names)
+    #[serde(rename = "encoderPreset")]
+    pub encoder_preset: Option<String>,
+    #[serde(rename = "audioBitrateKbps")]
+    pub audio_bitrate_kbps: u32,
+    /// Project logo stamped on every export
+    pub watermark: Option<Watermark>,
+    /// Captions burned into the picture or muxed as a subtitle track
+    pub captions: Option<ExportCaptions>,
+    /// EBU R128 loudness normalization of the exported audio
+    pub loudness: Option<LoudnessSettings>,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            codec: VideoCodec::H264,
+            container: Container::Mp4,
+            crf: Some(23),
+            video_bitrate_kbps: None,
+            two_pass: false,
+            pixel_format: None,
+            frame_rate: None,
+            encoder_preset: Some("fast".to_string()),
+            audio_bitrate_kbps: 192,
+            watermark: None,
+            captions: None,
+            loudness: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportPreset {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub settings: ExportSettings,
+}
+
+impl ExportSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.container.supports(self.codec) {
+            return Err(format!(
+                "{:?} video cannot be stored in a {} file",
+                self.codec,
+                self.container.extension().to_uppercase()
+            ));
+        }
+
+        if let Some(crf) = self.crf {
+            let max_crf = match self.codec {
+                VideoCodec::H264 | VideoCodec::H265 => Some(51),
+                VideoCodec::Vp9 | VideoCodec::Av1 => Some(63),
+                VideoCodec::ProRes => None,
+            };
+            if let Some(max_crf) = max_crf.filter(|max| crf > *max) {
+                return Err(format!("CRF for {:?} must be between 0 and {}", self.codec, max_crf));
+            }
+        }
+
+        if let Some(fps) = self.frame_rate {
+            if !(1.0..=240.0).contains(&fps) {
+                return Err("Frame rate must be between 1 and 240 fps".to_string());
+            }
+        }
+
+        if self.two_pass {
+            if self.codec == VideoCodec::ProRes {
+                return Err("Two-pass encoding is not available for ProRes".to_string());
+            }
+            if self.video_bitrate_kbps.is_none() {
+                return Err("Two-pass encoding requires a target bitrate".to_string());
+            }
+        }
+
+        if let Some(loudness) = &self.loudness {
+            loudness.validate()?;
+        }
+
+        Ok(())
+    }
+
+    /// Video encoder options. `pass` is Some(1) or Some(2) for two-pass encodes.
+    pub fn video_args(&self, pass: Option<(u8, &str)>) -> Result<Vec<String>, String> {
+        let mut args: Vec<String> = Vec::new();
+        let crf = self.crf.map(|c| c.to_string());
+        let bitrate = self.video_bitrate_kbps.map(|b| format!("{}k", b));
+
+        match self.codec {
+            VideoCodec::H264 | VideoCodec::H265 => {
+                let encoder = if self.codec == VideoCodec::H264 { "libx264" } else { "libx265" };
+                args.extend(["-c:v".to_string(), encoder.to_string()]);
+                args.extend(["-preset".to_string(), self.encoder_preset.clone().unwrap_or_else(|| "fast".to_string())]);
+                match (&bitrate, &crf) {
+                    (Some(b), _) => args.extend(["-b:v".to_string(), b.clone()]),
+                    (None, Some(c)) => args.extend(["-crf".to_string(), c.clone()]),
+                    (None, None) => {}
+                }
+                if self.codec == VideoCodec::H265 {
+                    // hvc1 tag is required for HEVC playback in QuickTime/Safari
+                    if matches!(self.container, Container::Mp4 | Container::Mov) {
+                        args.extend(["-tag:v".to_string(), "hvc1".to_string()]);
+                    }
+                    // libx265 takes its pass options through x265-params
+                    if let Some((n, log)) = pass {
+                        args.extend(["-x265-params".to_string(), format!("pass={}:stats={}.log", n, log)]);
+                    }
+                } else if let Some((n, log)) = pass {
+                    args.extend(pass_args(n, log));
+                }
+            }
+            VideoCodec::Vp9 => {
+                args.extend(["-c:v".to_string(), "libvpx-vp9".to_string()]);
+                match (&bitrate, &crf) {
+                    (Some(b), _) => args.extend(["-b:v".to_string(), b.clone()]),
+                    // Constant quality mode requires -b:v 0
+                    (None, c) => {
+                        args.extend(["-b:v".to_string(), "0".to_string()]);
+                        args.extend(["-crf".to_string(), c.clone().unwrap_or_else(|| "31".to_string())]);
+                    }
+                }
+                args.extend(["-row-mt", "1", "-deadline", "good", "-cpu-used", "2"].map(String::from));
+                if let Some((n, log)) = pass {
+                    args.extend(pass_args(n, log));
+                }
+            }
+            VideoCodec::Av1 => {
+                // SVT-AV1 is much faster, but only libaom supports -pass
+                let encoder = if pass.is_none() && ffmpeg_has_encoder("libsvtav1") {
+                    "libsvtav1"
+                } else if ffmpeg_has_encoder("libaom-av1") {
+                    "libaom-av1"
+                } else {
+                    return Err("AV1 export requires an FFmpeg build with libsvtav1 or libaom".to_string());
+                };
+                args.extend(["-c:v".to_string(), encoder.to_string()]);
+                match (&bitrate, &crf) {
+                    (Some(b), _) => args.extend(["-b:v".to_string(), b.clone()]),
+                    (None, c) => {
+                        args.extend(["-crf".to_string(), c.clone().unwrap_or_else(|| "30".to_string())]);
+                        if encoder == "libaom-av1" {
+                            args.extend(["-b:v".to_string(), "0".to_string()]);
+                        }
+                    }
+                }
+                if encoder == "libsvtav1" {
+                    args.extend(["-preset".to_string(), "8".to_string()]);
+                } else {
+                    args.extend(["-cpu-used", "6", "-row-mt", "1"].map(String::from));
+                }
+                if let Some((n, log)) = pass {
+                    args.extend(pass_args(n, log));
+                }
+            }
+            VideoCodec::ProRes => {
+                // Profile 3 = ProRes 422 HQ
+                args.extend(["-c:v", "prores_ks", "-profile:v", "3", "-vendor", "apl0"].map(String::from));
+            }
+        }
+
+        args.extend(["-pix_fmt".to_string(), self.pixel_format()]);
+
+        if let Some(fps) = self.frame_rate {
+            args.extend(["-r".to_string(), fps.to_string()]);
+        }
+
+        Ok(args)
+    }
+
+    /// The requested pixel format, or 10-bit 4:2:2 for ProRes and 4:2:0 for everything else
+    pub fn pixel_format(&self) -> String {
+        self.pixel_format.clone().unwrap_or_else(|| match self.codec {
+            VideoCodec::ProRes => "yuv422p10le".to_string(),
+            _ => "yuv420p".to_string(),
+        })
+    }
+
+    /// Audio encoder options matching the container
+    pub fn audio_args(&self) -> Vec<String> {
+        match (self.container, self.codec) {
+            (Container::Webm, _) => vec![
+                "-c:a".to_string(), "libopus".to_string(),
+                "-b:a".to_string(), format!("{}k", self.audio_bitrate_kbps),
+            ],
+            // Archive exports keep uncompressed audio next to ProRes video
+            (_, VideoCodec::ProRes) => vec!["-c:a".to_string(), "pcm_s16le".to_string()],
+            _ => vec![
+                "-c:a".to_string(), "aac".to_string(),
+                "-b:a".to_string(), format!("{}k", self.audio_bitrate_kbps),
+            ],
+        }
+    }
+
+    /// Muxer options placed just before the output path
+    pub fn container_args(&self) -> Vec<String> {
+        let mut args = vec!["-f".to_string(), self.container.muxer().to_string()];
+        if matches!(self.container, Container::Mp4 | Container::Mov) {
+            // Move the moov atom to the front so the file can start playing before it is fully loaded
+            args.extend(["-movflags".to_string(), "+faststart".to_string()]);
+        }
+        args
+    }
+}
+
+fn pass_args(pass: u8, passlog: &str) -> Vec<String> {
+    vec![
+        "-pass".to_string(), pass.to_string(),
+        "-passlogfile".to_string(), passlog.to_string(),
+    ]
+}
+
+/// Check whether the bundled FFmpeg was built with the given encoder
+pub fn ffmpeg_has_encoder(encoder: &str) -> bool {
+    static ENCODERS: OnceLock<String> = OnceLock::new();
+
+    let encoders = ENCODERS.get_or_init(|| {
+        get_ffmpeg_path()
+            .ok()
+            .and_then(|path| Command::new(path).args(["-hide_banner", "-encoders"]).output().ok())
+            .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+            .unwrap_or_default()
+    });
+
+    encoders
+        .lines()
+        .any(|line| line.split_whitespace().nth(1) == Some(encoder))
+}
+
+/// Built-in presets offered in the export dialog
+pub fn export_presets() -> Vec<ExportPreset> {
+    vec![
+        ExportPreset {
+            id: "default",
+            name: "Standard MP4",
+            width: None,
+            height: None,
+            settings: ExportSettings::default(),
+        },
+        ExportPreset {
+            id: "youtube-1080p",
+            name: "YouTube 1080p",
+            width: Some(1920),
+            height: Some(1080),
+            settings: ExportSettings {
+                crf: Some(18),
+                encoder_preset: Some("slow".to_string()),
+                audio_bitrate_kbps: 384,
+                ..ExportSettings::default()
+            },
+        },
+        ExportPreset {
+            id: "twitter",
+            name: "Twitter",
+            width: Some(1280),
+            height: Some(720),
+            settings: ExportSettings {
+                crf: None,
+                video_bitrate_kbps: Some(5000),
+                two_pass: true,
+                frame_rate: Some(30.0),
+                encoder_preset: Some("medium".to_string()),
+                audio_bitrate_kbps: 128,
+                ..ExportSettings::default()
+            },
+        },
+        ExportPreset {
+            id: "web-vp9",
+            name: "Web (VP9 WebM)",
+            width: None,
+            height: None,
+            settings: ExportSettings {
+                codec: VideoCodec::Vp9,
+                container: Container::Webm,
+                crf: Some(31),
+                encoder_preset: None,
+                audio_bitrate_kbps: 128,
+                ..ExportSettings::default()
+            },
+        },
+        ExportPreset {
+            id: "archive-prores",
+            name: "Archive ProRes",
+            width: None,
+            height: None,
+            settings: ExportSettings {
+                codec: VideoCodec::ProRes,
+                container: Container::Mov,
+                crf: None,
+                encoder_preset: None,
+                ..ExportSettings::default()
+            },
+        },
+    ]
+}
+
+#[tauri::command]
+pub async fn list_export_presets() -> Result<Vec<ExportPreset>, String> {
+    Ok(export_presets())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_settings_match_legacy_encoder() {
+        let args = ExportSettings::default().video_args(None).unwrap();
+        assert_eq!(args, ["-c:v", "libx264", "-preset", "fast", "-crf", "23", "-pix_fmt", "yuv420p"]);
+    }
+
+    #[test]
+    fn test_container_codec_validation() {
+        let settings = ExportSettings {
+            codec: VideoCodec::ProRes,
+            container: Container::Webm,
+            ..ExportSettings::default()
+        };
+        assert!(settings.validate().is_err());
+
+        for preset in export_presets() {
+            assert!(preset.settings.validate().is_ok(), "preset {} is invalid", preset.id);
+        }
+
+        let settings = ExportSettings { crf: Some(52), ..ExportSettings::default() };
+        assert!(settings.validate().is_err());
+        let settings = ExportSettings { codec: VideoCodec::Vp9, container: Container::Webm, crf: Some(63), ..ExportSettings::default() };
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn test_prores_without_pixel_format_uses_422() {
+        let settings: ExportSettings = serde_json::from_value(serde_json::json!({
+            "codec": "prores",
+            "container": "mov"
+        })).unwrap();
+
+        let args = settings.video_args(None).unwrap();
+        let pix_fmt = args.iter().position(|a| a == "-pix_fmt").unwrap();
+        assert_eq!(args[pix_fmt + 1], "yuv422p10le");
+    }
+}
@@ -0,0 +1,232 @@
+// ClipForge - Filler Classifier Module
+// Scores words and phrases in a transcript as filler using exact tokens, context and pauses
+
+use serde::{Deserialize, Serialize};
+
+use crate::filler_detection::{FillerWord, WhisperResponse, WhisperWord};
+
+/// Detections below this confidence are not reported
+const MIN_CONFIDENCE: f64 = 0.5;
+/// A gap between words at least this long counts as a pause
+const PAUSE_SECONDS: f64 = 0.25;
+const HESITATION_CONFIDENCE: f64 = 0.95;
+const WORD_BASE_CONFIDENCE: f64 = 0.35;
+const PHRASE_BASE_CONFIDENCE: f64 = 0.5;
+const SENTENCE_START_BONUS: f64 = 0.2;
+const COMMA_BONUS: f64 = 0.15;
+const PAUSE_BONUS: f64 = 0.15;
+const LITERAL_USE_PENALTY: f64 = 0.4;
+
+/// A word or phrase that is only filler in some contexts, e.g. "like" but not "I like"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FillerMarker {
+    pub phrase: String,
+    /// Words directly before the marker that show it is used literally
+    #[serde(default, rename = "notAfter")]
+    pub not_after: Vec<String>,
+    /// Words directly after the marker that show it is used literally
+    #[serde(default, rename = "notBefore")]
+    pub not_before: Vec<String>,
+}
+
+impl FillerMarker {
+    fn tokens(&self) -> Vec<String> {
+        self.phrase.split_whitespace().map(normalize_word).collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FillerDictionary {
+    /// Sounds that are always filler ("um", "uh"); repeated letters are ignored when matching
+    pub hesitations: Vec<String>,
+    pub markers: Vec<FillerMarker>,
+}
+
+/// Normalize word for comparison - removes punctuation, converts to lowercase
+fn normalize_word(word: &str) -> String {
+    word.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+/// Drop repeated letters so "ummm" and "uhh" match "um" and "uh"
+fn collapse_repeats(word: &str) -> String {
+    let mut collapsed = String::new();
+    for c in word.chars() {
+        if !collapsed.ends_with(c) {
+            collapsed.push(c);
+        }
+    }
+    collapsed
+}
+
+struct Token<'a> {
+    word: &'a WhisperWord,
+    normalized: String,
+    /// First word of a segment or after sentence-ending punctuation
+    sentence_start: bool,
+}
+
+impl Token<'_> {
+    fn ends_with(&self, chars: &[char]) -> bool {
+        self.word.word.trim_end().ends_with(chars)
+    }
+}
+
+/// Flatten the transcript into tokens, remembering where sentences start
+fn tokenize(transcript: &WhisperResponse) -> Vec<Token<'_>> {
+    let words: Vec<(&WhisperWord, bool)> = match (&transcript.words, &transcript.segments) {
+        (Some(words), _) => words.iter().map(|w| (w, false)).collect(),
+        (None, Some(segments)) => segments.iter()
+            .flat_map(|s| s.words.iter().flatten().enumerate().map(|(i, w)| (w, i == 0)))
+            .collect(),
+        (None, None) => Vec::new(),
+    };
+
+    let mut tokens: Vec<Token> = Vec::new();
+    for (word, segment_start) in words {
+        let normalized = normalize_word(&word.word);
+        if normalized.is_empty() {
+            continue;
+        }
+        let after_sentence = tokens.last().is_none_or(|t| t.ends_with(&['.', '?', '!']));
+        tokens.push(Token {
+            word,
+            normalized,
+            sentence_start: segment_start || after_sentence,
+        });
+    }
+
+    tokens
+}
+
+/// Confidence that `tokens[start..end]`, matching `marker`, is used as filler
+fn marker_confidence(tokens: &[Token], start: usize, end: usize, marker: &FillerMarker) -> f64 {
+    let first = &tokens[start];
+    let last = &tokens[end - 1];
+    let previous = start.checked_sub(1).map(|i| &tokens[i]);
+    let next = tokens.get(end);
+
+    let mut confidence = if end - start > 1 { PHRASE_BASE_CONFIDENCE } else { WORD_BASE_CONFIDENCE };
+
+    if first.sentence_start {
+        confidence += SENTENCE_START_BONUS;
+    }
+    if last.ends_with(&[',']) || previous.is_some_and(|p| p.ends_with(&[','])) {
+        confidence += COMMA_BONUS;
+    }
+    if previous.is_some_and(|p| first.word.start - p.word.end >= PAUSE_SECONDS) {
+        confidence += PAUSE_BONUS;
+    }
+    if next.is_some_and(|n| n.word.start - last.word.end >= PAUSE_SECONDS) {
+        confidence += PAUSE_BONUS;
+    }
+
+    let listed = |words: &[String], token: &Token| words.iter().any(|w| normalize_word(w) == token.normalized);
+    let literal_after = previous.is_some_and(|p| listed(&marker.not_after, p));
+    let literal_before = next.is_some_and(|n| listed(&marker.not_before, n));
+    if literal_after || literal_before {
+        confidence -= LITERAL_USE_PENALTY;
+    }
+
+    confidence.clamp(0.0, 1.0)
+}
+
+/// Find filler words and phrases in a transcript, each with a confidence
+pub fn classify_fillers(transcript: &WhisperResponse, dictionary: &FillerDictionary) -> Vec<FillerWord> {
+    let tokens = tokenize(transcript);
+    let hesitations: Vec<String> = dictionary.hesitations.iter()
+        .map(|h| collapse_repeats(&normalize_word(h)))
+        .collect();
+
+    // Longest phrases first so "you know" wins over a single-word marker
+    let mut markers: Vec<(Vec<String>, &FillerMarker)> = dictionary.markers.iter()
+        .map(|m| (m.tokens(), m))
+        .filter(|(t, _)| !t.is_empty())
+        .collect();
+    markers.sort_by_key(|(t, _)| std::cmp::Reverse(t.len()));
+
+    let mut fillers = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if hesitations.contains(&collapse_repeats(&tokens[i].normalized)) {
+            fillers.push(FillerWord {
+                word: tokens[i].word.word.trim().to_string(),
+                start_time: tokens[i].word.start,
+                end_time: tokens[i].word.end,
+                confidence: Some(HESITATION_CONFIDENCE),
+                speaker: tokens[i].word.speaker.clone(),
+            });
+            i += 1;
+            continue;
+        }
+
+        let matched = markers.iter().find(|(phrase, _)| {
+            tokens.get(i..i + phrase.len())
+                .is_some_and(|window| window.iter().zip(phrase).all(|(t, p)| t.normalized == *p))
+        });
+
+        match matched {
+            Some((phrase, marker)) => {
+                let end = i + phrase.len();
+                let confidence = marker_confidence(&tokens, i, end, marker);
+                if confidence >= MIN_CONFIDENCE {
+                    fillers.push(FillerWord {
+                        word: tokens[i..end].iter().map(|t| t.word.word.trim()).collect::<Vec<_>>().join(" "),
+                        start_time: tokens[i].word.start,
+                        end_time: tokens[end - 1].word.end,
+                        confidence: Some(confidence),
+                        speaker: tokens[i].word.speaker.clone(),
+                    });
+                }
+                i = end;
+            }
+            None => i += 1,
+        }
+    }
+
+    fillers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filler_dictionary::builtin_dictionary;
+
+    fn transcript(words: &[(&str, f64, f64)]) -> WhisperResponse {
+        WhisperResponse {
+            text: String::new(),
+            words: Some(words.iter()
+                .map(|(word, start, end)| WhisperWord { word: word.to_string(), start: *start, end: *end, speaker: None })
+                .collect()),
+            segments: None,
+            language: None,
+        }
+    }
+
+    #[test]
+    fn test_literal_uses_are_not_filler() {
+        let response = transcript(&[
+            ("I", 0.0, 0.1), ("like", 0.1, 0.3), ("it,", 0.3, 0.5), ("it's", 0.5, 0.6),
+            ("likely", 0.6, 0.9), ("alright", 0.9, 1.2), ("and", 1.2, 1.3), ("so", 1.3, 1.4), ("much", 1.4, 1.6),
+        ]);
+
+        assert!(classify_fillers(&response, &builtin_dictionary("en").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_hesitations_phrases_and_sentence_initial_so() {
+        let response = transcript(&[
+            ("So,", 0.0, 0.2), ("ummm", 0.6, 0.9), ("we", 1.0, 1.1), ("shipped", 1.1, 1.4), ("it,", 1.4, 1.6),
+            ("you", 1.6, 1.7), ("know,", 1.7, 1.9), ("on", 2.3, 2.4), ("time.", 2.4, 2.7),
+        ]);
+
+        let fillers = classify_fillers(&response, &builtin_dictionary("en").unwrap());
+        let found: Vec<_> = fillers.iter().map(|f| (f.word.as_str(), f.start_time, f.end_time)).collect();
+
+        assert_eq!(found, vec![("So,", 0.0, 0.2), ("ummm", 0.6, 0.9), ("you know,", 1.6, 1.9)]);
+        assert_eq!(fillers[1].confidence, Some(HESITATION_CONFIDENCE));
+    }
+}
@@ -4,10 +4,19 @@
 use std::path::Path;
 use std::fs;
 use std::process::Command;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use serde::{Deserialize, Serialize};
 use tauri::AppHandle;
 
 use crate::get_ffmpeg_path;
+use crate::chunked_transcription::transcribe_audio_file;
+use crate::filler_classifier::classify_fillers;
+use crate::diarization::diarize_response;
+use crate::filler_dictionary::dictionary_for_language;
+use crate::edit_list::{split_clip, EditOptions};
+use crate::export::ClipData;
+use crate::transcriber::{create_transcriber, TranscriptionSettings};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FillerWord {
@@ -17,72 +26,83 @@ pub struct FillerWord {
     #[serde(rename = "endTime")]
     pub end_time: f64,
     pub confidence: Option<f64>,
+    pub speaker: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-struct WhisperWord {
-    word: String,
-    start: f64,
-    end: f64,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhisperWord {
+    pub word: String,
+    pub start: f64,
+    pub end: f64,
+    pub speaker: Option<String>,  // Filled in by speaker diarization
 }
 
-#[derive(Debug, Deserialize)]
-struct WhisperSegment {
-    id: Option<i32>,
-    seek: Option<i32>,
-    start: f64,
-    end: f64,
-    text: String,
-    words: Option<Vec<WhisperWord>>,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhisperSegment {
+    pub id: Option<i32>,
+    pub seek: Option<i32>,
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    pub words: Option<Vec<WhisperWord>>,
+    pub speaker: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-struct WhisperResponse {
-    text: String,
-    words: Option<Vec<WhisperWord>>,  // Direct words array (if available)
-    segments: Option<Vec<WhisperSegment>>,  // Segments with nested words
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhisperResponse {
+    pub text: String,
+    pub words: Option<Vec<WhisperWord>>,  // Direct words array (if available)
+    pub segments: Option<Vec<WhisperSegment>>,  // Segments with nested words
+    pub language: Option<String>,  // Detected language (name or ISO code, depending on the backend)
 }
 
-const FILLER_WORDS: &[&str] = &[
-    "um", "umm", "uh", "uhh", "uhm", "eh", "ah",
-    "like", "you know", "so", "well", 
-    "actually", "basically", "literally", "right",
-];
-
-/// Normalize word for comparison - removes punctuation, converts to lowercase
-fn normalize_word(word: &str) -> String {
-    word.to_lowercase()
-        .chars()
-        .filter(|c| c.is_alphanumeric())
-        .collect()
-}
+impl WhisperResponse {
+    /// Word timings from the direct words array, or from the segments when it is missing
+    pub fn all_words(&self) -> Vec<&WhisperWord> {
+        match (&self.words, &self.segments) {
+            (Some(words), _) => words.iter().collect(),
+            (None, Some(segments)) => segments.iter()
+                .filter_map(|s| s.words.as_ref())
+                .flatten()
+                .collect(),
+            (None, None) => Vec::new(),
+        }
+    }
 
-/// Check if a normalized word matches a filler word
-/// Handles variations like "umm", "uhh", etc.
-fn is_filler_word(normalized_word: &str, filler: &str) -> bool {
-    // Exact match
-    if normalized_word == filler {
-        return true;
+    /// Whether diarization has labelled this transcript
+    pub fn has_speakers(&self) -> bool {
+        self.all_words().iter().any(|w| w.speaker.is_some())
+            || self.segments.iter().flatten().any(|s| s.speaker.is_some())
     }
-    
-    // For short filler words (um, uh, etc.), check if word starts with the filler
-    // This catches "umm", "uhh", "uhm" variations
-    if filler.len() <= 3 && normalized_word.len() >= filler.len() {
-        if normalized_word.starts_with(filler) {
-            return true;
-        }
+}
+
+/// Validate OpenAI API key format
+pub(crate) fn validate_api_key(api_key: &str) -> Result<(), String> {
+    if api_key.trim().is_empty() {
+        return Err("API key is required".to_string());
     }
     
-    // Contains match for longer filler phrases like "you know"
-    if filler.len() > 3 && normalized_word.contains(filler) {
-        return true;
+    if !api_key.starts_with("sk-") {
+        return Err("Invalid API key format. OpenAI API keys start with 'sk-'".to_string());
     }
     
-    false
+    Ok(())
+}
+
+/// Extractions running at the same time, even of the same range, each get their own file
+fn temp_audio_filename(video_path: &str, in_point: Option<f64>, out_point: Option<f64>) -> String {
+    static EXTRACTIONS: AtomicU64 = AtomicU64::new(0);
+    let source = format!("{}|{:?}|{:?}", video_path, in_point, out_point);
+    format!(
+        "audio_{:x}_{}_{}.wav",
+        md5::compute(source.as_bytes()),
+        std::process::id(),
+        EXTRACTIONS.fetch_add(1, Ordering::Relaxed)
+    )
 }
 
 /// Extract audio from video file to temporary WAV file
-async fn extract_audio_to_temp(
+pub(crate) async fn extract_audio_to_temp(
     video_path: &str,
     in_point: Option<f64>,
     out_point: Option<f64>,
@@ -97,10 +117,7 @@ async fn extract_audio_to_temp(
     fs::create_dir_all(&temp_dir)
         .map_err(|e| format!("Failed to create temp directory: {}", e))?;
 
-    // Generate unique filename
-    let video_hash = format!("{:x}", md5::compute(video_path.as_bytes()));
-    let audio_filename = format!("audio_{}.wav", video_hash);
-    let audio_path = temp_dir.join(&audio_filename);
+    let audio_path = temp_dir.join(temp_audio_filename(video_path, in_point, out_point));
 
     let ffmpeg_path = get_ffmpeg_path()?;
 
@@ -142,169 +159,18 @@ async fn extract_audio_to_temp(
     Ok(audio_path.to_str().unwrap().to_string())
 }
 
-/// Transcribe audio using OpenAI Whisper API
-async fn transcribe_audio(
-    audio_path: &str,
-    api_key: &str,
-) -> Result<WhisperResponse, String> {
-    // Read audio file
-    let audio_data = fs::read(audio_path)
-        .map_err(|e| format!("Failed to read audio file: {}", e))?;
-
-    // Get filename for multipart form
-    let filename = Path::new(audio_path)
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("audio.wav");
-
-    // Create multipart form data
-    // For word-level timestamps, OpenAI API expects timestamp_granularities as a JSON array
-    // Try sending as JSON-encoded string first
-    let form = reqwest::multipart::Form::new()
-        .part(
-            "file",
-            reqwest::multipart::Part::bytes(audio_data)
-                .file_name(filename.to_string())
-                .mime_str("audio/wav")
-                .map_err(|e| format!("Failed to set mime type: {}", e))?,
-        )
-        .text("model", "whisper-1")
-        .text("response_format", "verbose_json");
-    
-    // Add timestamp_granularities as multiple form fields (array parameter format)
-    // Some APIs expect array parameters as multiple fields with same name
-    let form = form.text("timestamp_granularities[]", "word");
-
-    // Make API request
-    let client = reqwest::Client::new();
-    let response = client
-        .post("https://api.openai.com/v1/audio/transcriptions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .multipart(form)
-        .send()
-        .await
-        .map_err(|e| {
-            if e.is_timeout() {
-                "Request to OpenAI API timed out".to_string()
-            } else if e.is_connect() {
-                "Failed to connect to OpenAI API. Check your internet connection.".to_string()
-            } else {
-                format!("Network error: {}", e)
-            }
-        })?;
-
-    let status = response.status();
-    
-    if !status.is_success() {
-        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        
-        if status == 401 {
-            return Err("Invalid OpenAI API key. Please check your API key.".to_string());
-        } else if status == 429 {
-            return Err("OpenAI API rate limit exceeded. Please try again later.".to_string());
-        } else {
-            return Err(format!("OpenAI API error ({}): {}", status, error_text));
-        }
-    }
-
-    // Get response text first for debugging
-    let response_text = response
-        .text()
-        .await
-        .map_err(|e| format!("Failed to read API response: {}", e))?;
-    
-    // Log full response structure for debugging
-    eprintln!("=== Whisper API Response (full) ===");
-    eprintln!("{}", response_text);
-    eprintln!("=== End Response ===");
-    
-    // Parse JSON response
-    let whisper_response: WhisperResponse = serde_json::from_str(&response_text)
-        .map_err(|e| format!("Failed to parse API response: {}. Response: {}", e, 
-            if response_text.len() > 200 { 
-                &response_text[..200] 
-            } else { 
-                &response_text 
-            }))?;
-
-    Ok(whisper_response)
-}
-
-/// Detect filler words from Whisper transcript
-fn parse_filler_words_from_transcript(transcript: &WhisperResponse) -> Vec<FillerWord> {
-    let mut filler_words = Vec::new();
-
-    // Try direct words array first
-    if let Some(words) = &transcript.words {
-        for word_obj in words {
-            let normalized = normalize_word(&word_obj.word);
-            
-            // Debug: log first few words to see what Whisper is returning
-            if filler_words.len() == 0 && words.len() > 0 {
-                eprintln!("Sample words from transcript (first 5):");
-                for (i, w) in words.iter().take(5).enumerate() {
-                    eprintln!("  [{}] '{}' (normalized: '{}')", i, w.word, normalize_word(&w.word));
-                }
-            }
-            
-            // Check if word matches any filler word
-            for filler in FILLER_WORDS {
-                if is_filler_word(&normalized, filler) {
-                    filler_words.push(FillerWord {
-                        word: word_obj.word.clone(),
-                        start_time: word_obj.start,
-                        end_time: word_obj.end,
-                        confidence: None,
-                    });
-                    break;
-                }
-            }
-        }
-    }
-    // Fallback: check segments with nested words
-    else if let Some(segments) = &transcript.segments {
-        for segment in segments {
-            if let Some(words) = &segment.words {
-                for word_obj in words {
-                    let normalized = normalize_word(&word_obj.word);
-                    
-                    // Check if word matches any filler word
-                    for filler in FILLER_WORDS {
-                        if is_filler_word(&normalized, filler) {
-                            filler_words.push(FillerWord {
-                                word: word_obj.word.clone(),
-                                start_time: word_obj.start,
-                                end_time: word_obj.end,
-                                confidence: None,
-                            });
-                            break;
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    filler_words
-}
-
 /// Main Tauri command for detecting filler words
 #[tauri::command]
 pub async fn detect_filler_words(
     _app: AppHandle,
     file_path: String,
-    api_key: String,
+    api_key: Option<String>,
     in_point: Option<f64>,
     out_point: Option<f64>,
+    speakers: Option<Vec<String>>,
 ) -> Result<Vec<FillerWord>, String> {
-    // Validate API key format
-    if api_key.trim().is_empty() {
-        return Err("API key is required".to_string());
-    }
-    
-    if !api_key.starts_with("sk-") {
-        return Err("Invalid API key format. OpenAI API keys start with 'sk-'".to_string());
-    }
+    let settings = TranscriptionSettings::load();
+    let transcriber = create_transcriber(&settings, api_key.as_deref())?;
 
     // Extract audio to temp file
     let audio_path = extract_audio_to_temp(&file_path, in_point, out_point).await?;
@@ -313,7 +179,7 @@ pub async fn detect_filler_words(
     let _cleanup = TempFileCleanup::new(audio_path.clone());
 
     // Transcribe audio
-    let transcript = transcribe_audio(&audio_path, &api_key).await?;
+    let mut transcript = transcribe_audio_file(Arc::from(transcriber), &audio_path, settings.chunk_format).await?;
 
     // Check if we got word-level timestamps (either direct words array or in segments)
     let has_words = transcript.words.is_some() || 
@@ -345,20 +211,53 @@ pub async fn detect_filler_words(
     }
 
     // Detect filler words
-    let filler_words = parse_filler_words_from_transcript(&transcript);
+    if settings.diarization.enabled {
+        diarize_response(&mut transcript, &audio_path, &settings.diarization).await;
+    }
+
+    let dictionary = dictionary_for_language(transcript.language.as_deref(), settings.language.as_deref());
+    let mut filler_words = classify_fillers(&transcript, &dictionary);
+
+    // Only keep fillers from the selected speakers
+    if let Some(speakers) = speakers.filter(|s| !s.is_empty()) {
+        if !transcript.has_speakers() {
+            return Err("Filtering by speaker needs speaker labels, but the transcript has none. Enable speaker diarization or clear the speaker filter.".to_string());
+        }
+        filler_words.retain(|f| f.speaker.as_ref().is_some_and(|s| speakers.contains(s)));
+    }
 
     // Note: cleanup will auto-drop at end of function scope, cleaning up temp file
 
     Ok(filler_words)
 }
 
+/// Cut the selected filler words out of a clip and return the clips that remain.
+/// Filler times are relative to the clip's in point, exactly as `detect_filler_words` returns them.
+#[tauri::command]
+pub async fn remove_filler_words(
+    clip: ClipData,
+    filler_words: Vec<FillerWord>,
+    options: Option<EditOptions>,
+) -> Result<Vec<ClipData>, String> {
+    let ranges: Vec<(f64, f64)> = filler_words.iter()
+        .map(|w| (w.start_time, w.end_time))
+        .collect();
+
+    let pieces = split_clip(&clip, &ranges, &options.unwrap_or_default());
+    if pieces.is_empty() {
+        return Err("Removing these filler words would leave nothing of the clip".to_string());
+    }
+
+    Ok(pieces)
+}
+
 /// Helper struct to clean up temp file
-struct TempFileCleanup {
+pub(crate) struct TempFileCleanup {
     path: String,
 }
 
 impl TempFileCleanup {
-    fn new(path: String) -> Self {
+    pub(crate) fn new(path: String) -> Self {
         Self { path }
     }
 }
@@ -371,3 +270,21 @@ impl Drop for TempFileCleanup {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_remove_filler_words_uses_detected_times() {
+        let clip: ClipData = serde_json::from_value(serde_json::json!({
+            "path": "a.mp4", "duration": 20.0, "inPoint": 5.0, "outPoint": 15.0, "volume": 100.0, "muted": false
+        })).unwrap();
+        // Detection extracts audio from the in point, so "um" at 2s is source time 7s
+        let um = FillerWord { word: "um".to_string(), start_time: 2.0, end_time: 2.5, confidence: None, speaker: None };
+        let options = EditOptions { padding: 0.0, ..EditOptions::default() };
+
+        let pieces = remove_filler_words(clip, vec![um], Some(options)).await.unwrap();
+        let kept: Vec<(f64, f64)> = pieces.iter().map(|p| (p.source_offset.unwrap(), p.out_point)).collect();
+        assert_eq!(kept, [(5.0, 2.0), (7.5, 7.5)]);
+    }
+}
@@ -0,0 +1,198 @@
+// ClipForge - Filler Dictionary Module
+// Per-language filler dictionaries: built-in defaults plus user-edited JSON/TOML files in the app data directory
+
+use std::fs;
+use std::path::PathBuf;
+use serde::Serialize;
+
+use crate::filler_classifier::FillerDictionary;
+use crate::transcriber::app_data_dir;
+
+const DEFAULT_LANGUAGE: &str = "en";
+
+const BUILTIN_DICTIONARIES: &[(&str, &str)] = &[
+    ("en", include_str!("fillers/en.json")),
+    ("es", include_str!("fillers/es.json")),
+    ("de", include_str!("fillers/de.json")),
+    ("fr", include_str!("fillers/fr.json")),
+];
+
+/// Whisper's verbose_json reports the language by name; dictionaries are keyed by ISO 639-1 code
+const LANGUAGE_NAMES: &[(&str, &str)] = &[
+    ("english", "en"),
+    ("spanish", "es"),
+    ("german", "de"),
+    ("french", "fr"),
+    ("italian", "it"),
+    ("portuguese", "pt"),
+    ("dutch", "nl"),
+    ("polish", "pl"),
+    ("swedish", "sv"),
+    ("turkish", "tr"),
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FillerDictionaryEntry {
+    pub language: String,
+    /// A default ships with the app for this language
+    #[serde(rename = "builtIn")]
+    pub built_in: bool,
+    /// The user has their own version in the app data directory
+    pub customized: bool,
+    pub dictionary: FillerDictionary,
+}
+
+/// ISO 639-1 code for a Whisper language name or code, e.g. "Spanish" -> "es"
+pub(crate) fn language_code(language: &str) -> String {
+    let language = language.trim().to_lowercase();
+    LANGUAGE_NAMES.iter()
+        .find(|(name, _)| *name == language)
+        .map(|(_, code)| code.to_string())
+        .unwrap_or(language)
+}
+
+/// Language codes become file names, so only allow plain codes like "en" or "pt-br"
+fn validate_language(language: &str) -> Result<(), String> {
+    let valid = !language.is_empty()
+        && language.len() <= 16
+        && language.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid language code: {}", language))
+    }
+}
+
+pub fn builtin_dictionary(language: &str) -> Option<FillerDictionary> {
+    let (_, json) = BUILTIN_DICTIONARIES.iter().find(|(code, _)| *code == language)?;
+    match serde_json::from_str(json) {
+        Ok(dictionary) => Some(dictionary),
+        Err(e) => {
+            eprintln!("Warning: Built-in {} filler dictionary is invalid: {}", language, e);
+            None
+        }
+    }
+}
+
+fn dictionaries_dir() -> PathBuf {
+    app_data_dir().join("fillers")
+}
+
+/// User dictionary files for a language, JSON taking precedence over TOML
+fn user_dictionary_paths(language: &str) -> [PathBuf; 2] {
+    let dir = dictionaries_dir();
+    [dir.join(format!("{}.json", language)), dir.join(format!("{}.toml", language))]
+}
+
+fn load_user_dictionary(language: &str) -> Option<FillerDictionary> {
+    user_dictionary_paths(language).into_iter().find_map(|path| {
+        let content = fs::read_to_string(&path).ok()?;
+        let parsed = if path.extension().is_some_and(|e| e == "toml") {
+            toml::from_str(&content).map_err(|e| e.to_string())
+        } else {
+            serde_json::from_str(&content).map_err(|e| e.to_string())
+        };
+        parsed
+            .map_err(|e| eprintln!("Warning: Ignoring invalid filler dictionary {}: {}", path.display(), e))
+            .ok()
+    })
+}
+
+/// The user's dictionary for a language if they have one, otherwise the built-in one
+fn dictionary_entry(language: &str) -> Option<FillerDictionaryEntry> {
+    let builtin = builtin_dictionary(language);
+    let user = load_user_dictionary(language);
+
+    Some(FillerDictionaryEntry {
+        language: language.to_string(),
+        built_in: builtin.is_some(),
+        customized: user.is_some(),
+        dictionary: user.or(builtin)?,
+    })
+}
+
+/// Dictionary for a transcript's language: the language Whisper detected, then the configured
+/// transcription language, then English
+pub(crate) fn dictionary_for_language(detected: Option<&str>, configured: Option<&str>) -> FillerDictionary {
+    let language = detected.or(configured).map(language_code);
+
+    if let Some(entry) = language.as_deref().filter(|l| validate_language(l).is_ok()).and_then(dictionary_entry) {
+        return entry.dictionary;
+    }
+    if let Some(language) = language.filter(|l| l != DEFAULT_LANGUAGE) {
+        eprintln!("Warning: No filler dictionary for '{}', using English", language);
+    }
+
+    dictionary_entry(DEFAULT_LANGUAGE)
+        .map(|entry| entry.dictionary)
+        .unwrap_or_default()
+}
+
+fn list_dictionaries() -> Vec<FillerDictionaryEntry> {
+    let mut languages: Vec<String> = BUILTIN_DICTIONARIES.iter().map(|(code, _)| code.to_string()).collect();
+
+    if let Ok(entries) = fs::read_dir(dictionaries_dir()) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_dictionary = path.extension().is_some_and(|e| e == "json" || e == "toml");
+            if let (true, Some(stem)) = (is_dictionary, path.file_stem().and_then(|s| s.to_str())) {
+                if validate_language(stem).is_ok() && !languages.iter().any(|l| l == stem) {
+                    languages.push(stem.to_string());
+                }
+            }
+        }
+    }
+
+    languages.iter().filter_map(|l| dictionary_entry(l)).collect()
+}
+
+/// All filler dictionaries: the built-in languages plus any the user has added
+#[tauri::command]
+pub async fn list_filler_dictionaries() -> Result<Vec<FillerDictionaryEntry>, String> {
+    Ok(list_dictionaries())
+}
+
+/// Save a custom dictionary for a language, or pass None to go back to the built-in default
+#[tauri::command]
+pub async fn update_filler_dictionary(
+    language: String,
+    dictionary: Option<FillerDictionary>,
+) -> Result<Vec<FillerDictionaryEntry>, String> {
+    let language = language_code(&language);
+    validate_language(&language)?;
+
+    // Remove both formats so a stale TOML file can't shadow the reset or the new JSON file
+    for path in user_dictionary_paths(&language) {
+        if path.exists() {
+            fs::remove_file(&path)
+                .map_err(|e| format!("Failed to remove filler dictionary {}: {}", path.display(), e))?;
+        }
+    }
+
+    if let Some(dictionary) = dictionary {
+        let [path, _] = user_dictionary_paths(&language);
+        fs::create_dir_all(dictionaries_dir())
+            .map_err(|e| format!("Failed to create filler dictionary directory: {}", e))?;
+        let json = serde_json::to_string_pretty(&dictionary)
+            .map_err(|e| format!("Failed to serialize filler dictionary: {}", e))?;
+        fs::write(&path, json)
+            .map_err(|e| format!("Failed to save filler dictionary: {}", e))?;
+    }
+
+    Ok(list_dictionaries())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_dictionaries_parse() {
+        for (language, _) in BUILTIN_DICTIONARIES {
+            let dictionary = builtin_dictionary(language).unwrap();
+            assert!(!dictionary.hesitations.is_empty() && !dictionary.markers.is_empty(), "{}", language);
+        }
+        assert_eq!(language_code("Spanish"), "es");
+        assert_eq!(language_code("de"), "de");
+    }
+}
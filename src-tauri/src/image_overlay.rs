@@ -0,0 +1,245 @@
+// ClipForge - Image Overlay Module
+// Still-image layers (PNG, JPEG, SVG) and the project watermark, looped and composited at export
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use resvg::{tiny_skia, usvg};
+
+use crate::export_settings::ExportSettings;
+
+/// Frame rate used for looped images when the export doesn't force one
+const DEFAULT_IMAGE_FRAME_RATE: f64 = 30.0;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ImageLayer {
+    pub path: String,
+    /// Placement as a fraction of the free space: 0 = left/top, 0.5 = centred, 1 = right/bottom
+    #[serde(default = "default_position")]
+    pub x: f64,
+    #[serde(default = "default_position")]
+    pub y: f64,
+    /// Width as a fraction of the export frame; None keeps the image's own size
+    pub scale: Option<f64>,
+    #[serde(default = "default_opacity")]
+    pub opacity: f64,
+    #[serde(rename = "timelineStart")]
+    pub timeline_start: f64,
+    #[serde(rename = "timelineEnd")]
+    pub timeline_end: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum WatermarkPosition {
+    #[serde(rename = "topLeft")]
+    TopLeft,
+    #[serde(rename = "topRight")]
+    TopRight,
+    #[serde(rename = "bottomLeft")]
+    BottomLeft,
+    #[default]
+    #[serde(rename = "bottomRight")]
+    BottomRight,
+    #[serde(rename = "center")]
+    Center,
+}
+
+/// Project-wide logo stamped on every export
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Watermark {
+    pub path: String,
+    #[serde(default)]
+    pub position: WatermarkPosition,
+    /// Width as a fraction of the export frame
+    #[serde(default = "default_watermark_scale")]
+    pub scale: f64,
+    #[serde(default = "default_opacity")]
+    pub opacity: f64,
+    /// Distance from the frame edge as a fraction of the frame width
+    #[serde(default = "default_watermark_margin")]
+    pub margin: f64,
+}
+
+fn default_position() -> f64 {
+    0.5
+}
+
+fn default_opacity() -> f64 {
+    1.0
+}
+
+fn default_watermark_scale() -> f64 {
+    0.15
+}
+
+fn default_watermark_margin() -> f64 {
+    0.03
+}
+
+impl ImageLayer {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.timeline_end <= self.timeline_start {
+            return Err("Image layer must end after it starts".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Watermark {
+    /// Overlay position expressions for the configured corner
+    fn overlay_position(&self, canvas_width: i32) -> (String, String) {
+        let margin = (canvas_width as f64 * self.margin.clamp(0.0, 0.5)).round() as i32;
+        match self.position {
+            WatermarkPosition::TopLeft => (margin.to_string(), margin.to_string()),
+            WatermarkPosition::TopRight => (format!("W-w-{}", margin), margin.to_string()),
+            WatermarkPosition::BottomLeft => (margin.to_string(), format!("H-h-{}", margin)),
+            WatermarkPosition::BottomRight => (format!("W-w-{}", margin), format!("H-h-{}", margin)),
+            WatermarkPosition::Center => ("(W-w)/2".to_string(), "(H-h)/2".to_string()),
+        }
+    }
+}
+
+/// Pixel width for an image scaled to a fraction of the frame (even, for chroma subsampling)
+fn scaled_width(scale: f64, canvas_width: i32) -> i32 {
+    (((canvas_width as f64 * scale.clamp(0.0, 1.0)) as i32) & !1).max(2)
+}
+
+fn is_svg(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("svg"))
+}
+
+/// Render an SVG to a PNG in the temp directory. FFmpeg builds rarely include an SVG decoder,
+/// so vectors are rasterised here at the size they will be shown.
+fn rasterize_svg(path: &str, target_width: Option<i32>) -> Result<PathBuf, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read image '{}': {}", path, e))?;
+    let tree = usvg::Tree::from_data(&data, &usvg::Options::default())
+        .map_err(|e| format!("Failed to parse SVG '{}': {}", path, e))?;
+
+    let size = tree.size();
+    let scale = target_width.map(|w| w as f32 / size.width()).unwrap_or(1.0);
+    let width = (size.width() * scale).ceil() as u32;
+    let height = (size.height() * scale).ceil() as u32;
+    let mut pixmap = tiny_skia::Pixmap::new(width.max(1), height.max(1))
+        .ok_or_else(|| format!("SVG '{}' has an invalid size", path))?;
+    resvg::render(&tree, tiny_skia::Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+
+    let raster_dir = std::env::temp_dir().join("clipforge_svg");
+    fs::create_dir_all(&raster_dir)
+        .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    let raster_path = raster_dir.join(format!("{:x}_{}.png", md5::compute(path.as_bytes()), width));
+    pixmap.save_png(&raster_path)
+        .map_err(|e| format!("Failed to rasterise SVG '{}': {}", path, e))?;
+
+    Ok(raster_path)
+}
+
+/// Input arguments that loop a still image for `duration` seconds
+pub(crate) fn image_input_args(
+    path: &str,
+    target_width: Option<i32>,
+    duration: f64,
+    frame_rate: Option<f64>,
+) -> Result<Vec<String>, String> {
+    if !Path::new(path).exists() {
+        return Err(format!("Image not found: {}", path));
+    }
+
+    let input_path = if is_svg(path) {
+        rasterize_svg(path, target_width)?.to_string_lossy().to_string()
+    } else {
+        path.to_string()
+    };
+
+    Ok(vec![
+        "-loop".to_string(), "1".to_string(),
+        "-framerate".to_string(), frame_rate.unwrap_or(DEFAULT_IMAGE_FRAME_RATE).to_string(),
+        "-t".to_string(), duration.to_string(),
+        "-i".to_string(), input_path,
+    ])
+}
+
+/// Scale, fade and time-shift an image input into `[<label>_layer]`
+fn image_layer_filter(input_index: usize, width: Option<i32>, opacity: f64, start: f64, label: &str) -> String {
+    let scale_filter = width
+        .map(|w| format!(",scale={}:-2", w))
+        .unwrap_or_default();
+    let alpha_filter = if opacity < 1.0 {
+        format!(",colorchannelmixer=aa={}", opacity.clamp(0.0, 1.0))
+    } else {
+        String::new()
+    };
+
+    format!("[{}:v]format=rgba{}{},setpts=PTS-STARTPTS+{}/TB[{}_layer]",
+        input_index, scale_filter, alpha_filter, start, label)
+}
+
+/// Add an image layer's input and overlay it on `current`, returning the new video label
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn compile_image_layer(
+    cmd_args: &mut Vec<String>,
+    filter_parts: &mut Vec<String>,
+    layer: &ImageLayer,
+    input_index: usize,
+    label: &str,
+    current: &str,
+    canvas_width: i32,
+    frame_rate: Option<f64>,
+) -> Result<String, String> {
+    layer.validate()?;
+    let start = layer.timeline_start.max(0.0);
+    let end = layer.timeline_end;
+    let width = layer.scale.map(|s| scaled_width(s, canvas_width));
+
+    cmd_args.extend(image_input_args(&layer.path, width, end - start, frame_rate)?);
+    filter_parts.push(image_layer_filter(input_index, width, layer.opacity, start, label));
+
+    let output = format!("[{}_out]", label);
+    filter_parts.push(format!("{}[{}_layer]overlay=x=(W-w)*{}:y=(H-h)*{}:eof_action=pass:enable='between(t,{},{})'{}",
+        current, label, layer.x.clamp(0.0, 1.0), layer.y.clamp(0.0, 1.0), start, end, output));
+
+    Ok(output)
+}
+
+/// Stamp the export's watermark (if any) over the finished video, returning the new video label
+pub(crate) fn apply_watermark(
+    cmd_args: &mut Vec<String>,
+    filter_parts: &mut Vec<String>,
+    settings: &ExportSettings,
+    input_index: usize,
+    current: &str,
+    canvas_width: i32,
+    duration: f64,
+) -> Result<String, String> {
+    let Some(watermark) = &settings.watermark else {
+        return Ok(current.to_string());
+    };
+
+    let width = scaled_width(watermark.scale, canvas_width);
+    cmd_args.extend(image_input_args(&watermark.path, Some(width), duration, settings.frame_rate)?);
+    filter_parts.push(image_layer_filter(input_index, Some(width), watermark.opacity, 0.0, "watermark"));
+
+    let (x, y) = watermark.overlay_position(canvas_width);
+    filter_parts.push(format!("{}[watermark_layer]overlay=x={}:y={}:eof_action=pass[watermarked]",
+        current, x, y));
+
+    Ok("[watermarked]".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watermark_corner_position() {
+        let watermark: Watermark = serde_json::from_value(serde_json::json!({
+            "path": "/tmp/logo.png"
+        })).unwrap();
+
+        assert_eq!(watermark.position, WatermarkPosition::BottomRight);
+        assert_eq!(watermark.overlay_position(1000), ("W-w-30".to_string(), "H-h-30".to_string()));
+        assert_eq!(scaled_width(watermark.scale, 1280), 192);
+    }
+}
@@ -3,15 +3,63 @@
 
 use std::path::Path;
 use std::fs;
+use std::sync::OnceLock;
 use serde::{Deserialize, Serialize};
+use tauri::Manager;
 
 mod thumbnails;
+mod audio_export;
+mod audio_processing;
+mod audio_tracks;
+mod captions;
+mod chunked_transcription;
+mod diarization;
+mod edit_list;
 mod export;
+mod export_job_spec;
+mod export_plan;
+mod export_progress;
+mod export_queue;
+mod export_settings;
+mod filler_classifier;
 mod filler_detection;
+mod filler_dictionary;
+mod image_overlay;
+#[cfg(feature = "local-whisper")]
+mod local_whisper;
+mod loudness;
+mod multi_track;
+mod music;
+mod silence;
+mod speaker_clustering;
+mod stream_copy;
+mod text_overlay;
+mod timeline;
+mod transcriber;
+mod transcription;
+mod transitions;
 
 use thumbnails::extract_thumbnails;
-use export::{export_video, export_multi_track_video};
-use filler_detection::detect_filler_words;
+use audio_export::export_audio;
+use audio_processing::preview_audio_processing;
+use audio_tracks::detach_clip_audio;
+use export::export_video;
+use multi_track::export_multi_track_video;
+use captions::generate_captions;
+use export_progress::{cancel_export, ExportRegistry};
+use export_queue::{
+    cancel_export_job, enqueue_export, list_export_jobs, retry_export_job,
+    schedule_export_jobs, set_export_concurrency, ExportQueue,
+};
+use export_settings::list_export_presets;
+use filler_detection::{detect_filler_words, remove_filler_words};
+use filler_dictionary::{list_filler_dictionaries, update_filler_dictionary};
+use loudness::measure_loudness;
+use silence::{detect_silence, trim_pauses};
+use edit_list::edit_clip_from_transcript;
+use timeline::export_timeline;
+use transcriber::{download_whisper_model, get_transcription_settings, set_transcription_settings};
+use transcription::transcribe_clip;
 
 #[tauri::command]
 async fn restart_app(_app: tauri::AppHandle) -> Result<(), String> {
@@ -100,15 +148,28 @@ async fn select_video_file(app: tauri::AppHandle) -> Result<String, String> {
 }
 
 #[tauri::command]
-async fn select_export_path(app: tauri::AppHandle, default_filename: String) -> Result<String, String> {
+async fn select_export_path(app: tauri::AppHandle, default_filename: String, container: Option<String>) -> Result<String, String> {
     use tauri_plugin_dialog::DialogExt;
     use tokio::sync::oneshot;
     
     let (tx, rx) = oneshot::channel();
     
+    // Offer the file type matching the selected export container or audio format (MP4 by default)
+    let (filter_name, extension) = match container.as_deref().map(|c| c.to_lowercase()).as_deref() {
+        Some("mov") => ("QuickTime Movie", "mov"),
+        Some("webm") => ("WebM Video", "webm"),
+        Some("mkv") => ("Matroska Video", "mkv"),
+        Some("mp3") => ("MP3 Audio", "mp3"),
+        Some("m4a") => ("AAC Audio", "m4a"),
+        Some("opus") => ("Opus Audio", "opus"),
+        Some("flac") => ("FLAC Audio", "flac"),
+        Some("wav") => ("WAV Audio", "wav"),
+        _ => ("MP4 Video", "mp4"),
+    };
+    
     let dialog = app.dialog();
     let file_dialog = dialog.file()
-        .add_filter("MP4 Video", &["mp4"])
+        .add_filter(filter_name, &[extension])
         .set_file_name(&default_filename);
     
     file_dialog.save_file(move |file_path| {
@@ -499,6 +560,33 @@ pub fn get_ffprobe_path() -> Result<std::path::PathBuf, String> {
     Err("FFprobe not found. Please ensure FFprobe is installed and accessible.".to_string())
 }
 
+/// The app's resource directory, recorded at startup. Bundled fonts and models live here in
+/// production builds, independent of the working directory.
+static RESOURCE_DIR: OnceLock<std::path::PathBuf> = OnceLock::new();
+
+/// Find a bundled resource directory: in the app's resources, or in the source tree during development
+fn find_resource_dir(name: &str) -> Option<std::path::PathBuf> {
+    let possible_paths = [
+        // Bundled resources (for production)
+        RESOURCE_DIR.get().map(|dir| dir.join(name)),
+        // Development paths (relative to src-tauri or the project root)
+        Some(std::path::PathBuf::from(name)),
+        Some(std::path::PathBuf::from("src-tauri").join(name)),
+    ];
+
+    possible_paths.into_iter().flatten().find(|path| path.is_dir())
+}
+
+pub fn get_fonts_dir() -> Result<std::path::PathBuf, String> {
+    // Fonts are bundled with the app so text renders identically on every machine
+    find_resource_dir("fonts").ok_or_else(|| "Fonts directory not found. Please reinstall ClipForge.".to_string())
+}
+
+pub fn get_models_dir() -> Result<std::path::PathBuf, String> {
+    // Audio models (RNNoise) ship as bundled resources next to the fonts
+    find_resource_dir("models").ok_or_else(|| "Models directory not found. Please reinstall ClipForge.".to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -547,7 +635,9 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_macos_permissions::init())
-        .setup(|_app| {
+        .manage(ExportRegistry::default())
+        .manage(ExportQueue::load())
+        .setup(|app| {
             // Note: Asset protocol scope is configured in tauri.conf.json with "scope": ["**"]
             // which allows access to all directories. This should be sufficient for file access
             // in production builds. The dynamic scope API may not be available in Tauri v2, 
@@ -559,6 +649,14 @@ pub fn run() {
                 std::fs::create_dir_all(&recordings_dir).ok();
             }
             
+            // Bundled fonts and models are resolved against the resource directory
+            if let Ok(dir) = app.path().resource_dir() {
+                let _ = RESOURCE_DIR.set(dir);
+            }
+            
+            // Resume export jobs that were queued or interrupted before the last exit
+            schedule_export_jobs(app.handle());
+            
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -568,11 +666,34 @@ pub fn run() {
             select_export_path,
             export_video,
             export_multi_track_video,
+            export_timeline,
+            export_audio,
+            preview_audio_processing,
+            detach_clip_audio,
+            cancel_export,
+            enqueue_export,
+            list_export_jobs,
+            retry_export_job,
+            cancel_export_job,
+            set_export_concurrency,
+            list_export_presets,
             extract_thumbnails,
             save_project,
             load_project,
             save_recording,
             detect_filler_words,
+            remove_filler_words,
+            list_filler_dictionaries,
+            update_filler_dictionary,
+            detect_silence,
+            trim_pauses,
+            measure_loudness,
+            edit_clip_from_transcript,
+            transcribe_clip,
+            get_transcription_settings,
+            set_transcription_settings,
+            download_whisper_model,
+            generate_captions,
             restart_app
             // Commands will be added in future PRs:
             // - check_codec_compatibility
@@ -0,0 +1,121 @@
+// ClipForge - Local Whisper Module
+// Offline transcription with whisper.cpp, behind the local-whisper feature
+
+use async_trait::async_trait;
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+use crate::transcriber::Transcriber;
+use crate::filler_detection::{WhisperResponse, WhisperSegment, WhisperWord};
+
+/// Offline transcription with whisper.cpp and a ggml model file
+pub struct LocalWhisperTranscriber {
+    pub model_path: String,
+    pub language: Option<String>,
+}
+
+/// whisper.cpp timestamps are in centiseconds
+fn centis_to_seconds(t: i64) -> f64 {
+    t as f64 / 100.0
+}
+
+fn read_wav_samples(audio_path: &str) -> Result<Vec<f32>, String> {
+    let mut reader = hound::WavReader::open(audio_path)
+        .map_err(|e| format!("Failed to read audio file: {}", e))?;
+    let samples: Vec<i16> = reader.samples::<i16>()
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to read audio samples: {}", e))?;
+
+    let mut output = vec![0.0f32; samples.len()];
+    whisper_rs::convert_integer_to_float_audio(&samples, &mut output)
+        .map_err(|e| format!("Failed to convert audio samples: {}", e))?;
+    Ok(output)
+}
+
+impl LocalWhisperTranscriber {
+    fn run(&self, audio_path: &str) -> Result<WhisperResponse, String> {
+        let samples = read_wav_samples(audio_path)?;
+
+        let context = WhisperContext::new_with_params(&self.model_path, WhisperContextParameters::default())
+            .map_err(|e| format!("Failed to load Whisper model: {}", e))?;
+        let mut state = context.create_state()
+            .map_err(|e| format!("Failed to initialise Whisper: {}", e))?;
+
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_token_timestamps(true);
+        params.set_language(Some(self.language.as_deref().unwrap_or("auto")));
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_special(false);
+        params.set_print_timestamps(false);
+
+        state.full(params, &samples)
+            .map_err(|e| format!("Offline transcription failed: {}", e))?;
+
+        let segment_count = state.full_n_segments()
+            .map_err(|e| format!("Offline transcription failed: {}", e))?;
+        let mut segments = Vec::new();
+
+        for i in 0..segment_count {
+            let text = state.full_get_segment_text_lossy(i).map_err(|e| e.to_string())?;
+            let start = centis_to_seconds(state.full_get_segment_t0(i).map_err(|e| e.to_string())?);
+            let end = centis_to_seconds(state.full_get_segment_t1(i).map_err(|e| e.to_string())?);
+
+            // Tokens are sub-word pieces; a leading space starts a new word
+            let mut words: Vec<WhisperWord> = Vec::new();
+            for j in 0..state.full_n_tokens(i).map_err(|e| e.to_string())? {
+                let token = state.full_get_token_text_lossy(i, j).map_err(|e| e.to_string())?;
+                if token.starts_with("[_") || token.starts_with("<|") {
+                    continue;
+                }
+                let data = state.full_get_token_data(i, j).map_err(|e| e.to_string())?;
+                let (t0, t1) = (centis_to_seconds(data.t0), centis_to_seconds(data.t1));
+
+                match words.last_mut() {
+                    Some(word) if !token.starts_with(' ') => {
+                        word.word.push_str(&token);
+                        word.end = t1;
+                    }
+                    _ => words.push(WhisperWord { word: token.trim().to_string(), start: t0, end: t1, speaker: None }),
+                }
+            }
+
+            segments.push(WhisperSegment {
+                id: Some(i),
+                seek: None,
+                start,
+                end,
+                text,
+                words: Some(words),
+                speaker: None,
+            });
+        }
+
+        let language = state.full_lang_id_from_state()
+            .ok()
+            .and_then(whisper_rs::get_lang_str)
+            .map(|l| l.to_string());
+
+        Ok(WhisperResponse {
+            text: segments.iter().map(|s| s.text.trim()).collect::<Vec<_>>().join(" "),
+            words: None,
+            segments: Some(segments),
+            language,
+        })
+    }
+}
+
+#[async_trait]
+impl Transcriber for LocalWhisperTranscriber {
+    async fn transcribe(&self, audio_path: &str) -> Result<WhisperResponse, String> {
+        let transcriber = LocalWhisperTranscriber {
+            model_path: self.model_path.clone(),
+            language: self.language.clone(),
+        };
+        let audio_path = audio_path.to_string();
+
+        // Inference is CPU-bound; keep it off the async runtime
+        tokio::task::spawn_blocking(move || transcriber.run(&audio_path))
+            .await
+            .map_err(|e| format!("Offline transcription failed: {}", e))?
+    }
+}
@@ -0,0 +1,480 @@
+// ClipForge - Loudness Module
+// EBU R128 loudness measurement and two-pass loudnorm normalization for exports
+
+use std::collections::HashMap;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::get_ffmpeg_path;
+use crate::audio_tracks::AudioTrack;
+use crate::export::{has_audio_stream, parse_ffmpeg_error, ClipData, AUDIO_SAMPLE_RATE};
+use crate::export_plan::ExportPlan;
+use crate::export_progress::{RunningExport, EXPORT_CANCELLED};
+use crate::export_settings::ExportSettings;
+use crate::multi_track::PipClipData;
+use crate::timeline::{TimelineClip, TimelineData};
+
+const DEFAULT_LOUDNESS_RANGE: f64 = 11.0;
+/// ebur128 reports gated-out (silent) blocks at its -70 LUFS absolute gate or below
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+
+/// Delivery targets: integrated loudness (LUFS) and true-peak ceiling (dBTP)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoudnessPreset {
+    /// -14 LUFS / -1 dBTP, what YouTube and Spotify normalize to
+    #[default]
+    Streaming,
+    /// -16 LUFS / -1 dBTP
+    Podcast,
+    /// EBU R128: -23 LUFS / -1 dBTP
+    Broadcast,
+}
+
+impl LoudnessPreset {
+    fn targets(&self) -> (f64, f64) {
+        match self {
+            LoudnessPreset::Streaming => (-14.0, -1.0),
+            LoudnessPreset::Podcast => (-16.0, -1.0),
+            LoudnessPreset::Broadcast => (-23.0, -1.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LoudnessScope {
+    /// Measure the final mix and normalize it as a whole
+    #[default]
+    Mix,
+    /// Bring each source to the target with a fixed gain before mixing
+    PerClip,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LoudnessSettings {
+    pub preset: LoudnessPreset,
+    /// Overrides the preset's integrated loudness target
+    #[serde(rename = "integratedLufs")]
+    pub integrated_lufs: Option<f64>,
+    /// Overrides the preset's true-peak ceiling
+    #[serde(rename = "truePeak")]
+    pub true_peak: Option<f64>,
+    /// Target loudness range in LU, 11 when unset
+    #[serde(rename = "loudnessRange")]
+    pub loudness_range: Option<f64>,
+    pub scope: LoudnessScope,
+    /// First-pass measurement of the mix, filled in before the final encode
+    #[serde(skip)]
+    pub measured: Option<LoudnormStats>,
+}
+
+impl LoudnessSettings {
+    pub fn integrated_target(&self) -> f64 {
+        self.integrated_lufs.unwrap_or(self.preset.targets().0)
+    }
+
+    pub fn true_peak_target(&self) -> f64 {
+        self.true_peak.unwrap_or(self.preset.targets().1)
+    }
+
+    fn loudness_range_target(&self) -> f64 {
+        self.loudness_range.unwrap_or(DEFAULT_LOUDNESS_RANGE)
+    }
+
+    /// Ranges accepted by FFmpeg's loudnorm filter
+    pub fn validate(&self) -> Result<(), String> {
+        if !(-70.0..=-5.0).contains(&self.integrated_target()) {
+            return Err("Integrated loudness target must be between -70 and -5 LUFS".to_string());
+        }
+        if !(-9.0..=0.0).contains(&self.true_peak_target()) {
+            return Err("True peak ceiling must be between -9 and 0 dBTP".to_string());
+        }
+        if !(1.0..=20.0).contains(&self.loudness_range_target()) {
+            return Err("Loudness range target must be between 1 and 20 LU".to_string());
+        }
+        Ok(())
+    }
+
+    /// loudnorm filter for the mix: the measuring configuration until `measured` is set, then the
+    /// second pass that applies a linear gain from those measurements
+    fn loudnorm_filter(&self) -> String {
+        let targets = format!("loudnorm=I={}:TP={}:LRA={}",
+            self.integrated_target(), self.true_peak_target(), self.loudness_range_target());
+
+        match &self.measured {
+            Some(m) => format!("{}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+                targets, m.input_i, m.input_tp, m.input_lra, m.input_thresh, m.target_offset),
+            None => format!("{}:print_format=json", targets),
+        }
+    }
+}
+
+/// Input statistics loudnorm prints with print_format=json
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnormStats {
+    pub input_i: f64,
+    pub input_tp: f64,
+    pub input_lra: f64,
+    pub input_thresh: f64,
+    pub target_offset: f64,
+}
+
+/// Parse the JSON block loudnorm writes to stderr at the end of a pass
+pub(crate) fn parse_loudnorm_output(stderr: &str) -> Result<LoudnormStats, String> {
+    let block = stderr.rfind("[Parsed_loudnorm")
+        .map(|i| &stderr[i..])
+        .and_then(|s| Some(&s[s.find('{')?..=s.find('}')?]))
+        .ok_or("FFmpeg did not report loudness statistics")?;
+
+    let values: HashMap<String, String> = serde_json::from_str(block)
+        .map_err(|e| format!("Failed to parse loudness statistics: {}", e))?;
+    // Silent input is reported as "-inf"
+    let value = |key: &str| values.get(key)
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| format!("Loudness statistics have no usable {}", key));
+
+    Ok(LoudnormStats {
+        input_i: value("input_i")?,
+        input_tp: value("input_tp")?,
+        input_lra: value("input_lra")?,
+        input_thresh: value("input_thresh")?,
+        target_offset: value("target_offset")?,
+    })
+}
+
+/// Route the export's audio output through loudnorm when the mix is normalized. Returns the new label.
+pub(crate) fn apply_mix_loudness(filter_parts: &mut Vec<String>, audio_output: String, loudness: Option<&LoudnessSettings>) -> String {
+    match loudness.filter(|l| l.scope == LoudnessScope::Mix) {
+        Some(loudness) => {
+            // loudnorm works at 192kHz internally, so bring the output back to the export rate
+            filter_parts.push(format!("{}{},aresample={}[outa_loudnorm]",
+                audio_output, loudness.loudnorm_filter(), AUDIO_SAMPLE_RATE));
+            "[outa_loudnorm]".to_string()
+        }
+        None => audio_output,
+    }
+}
+
+/// Measuring pass for a mix that still needs its first loudnorm pass. It renders the whole graph,
+/// including any video output that keeps the graph connected, and discards the result.
+pub(crate) fn loudness_pass_args(base_args: &[String], loudness: Option<&LoudnessSettings>) -> Option<Vec<String>> {
+    loudness.filter(|l| l.scope == LoudnessScope::Mix && l.measured.is_none())?;
+
+    let mut args = base_args.to_vec();
+    args.extend(["-sn", "-f", "null", "-y", "-"].map(String::from));
+    Some(args)
+}
+
+/// Export settings that can carry a loudness target
+pub(crate) trait LoudnessTarget: Clone {
+    fn loudness_mut(&mut self) -> Option<&mut LoudnessSettings>;
+}
+
+impl LoudnessTarget for ExportSettings {
+    fn loudness_mut(&mut self) -> Option<&mut LoudnessSettings> {
+        self.loudness.as_mut()
+    }
+}
+
+/// Build an export plan, first running the measuring pass when the mix is loudness normalized.
+/// Plans the export's progress over every pass, the measuring pass included.
+pub(crate) async fn plan_with_loudness<S, F>(
+    export: &RunningExport,
+    ffmpeg_path: &Path,
+    settings: &S,
+    build: F,
+) -> Result<ExportPlan, String>
+where
+    S: LoudnessTarget,
+    F: Fn(&S) -> Result<ExportPlan, String>,
+{
+    let plan = build(settings)?;
+    export.plan_progress(plan.total_duration * plan.pass_count() as f64);
+    let Some(pass_args) = &plan.loudness_pass_args else {
+        return Ok(plan);
+    };
+
+    let output = export.run_ffmpeg(ffmpeg_path, pass_args, None, plan.total_duration).await?;
+    if !output.success {
+        return Err(parse_ffmpeg_error(&output.stderr));
+    }
+
+    let mut measured = settings.clone();
+    if let Some(loudness) = measured.loudness_mut() {
+        loudness.measured = Some(parse_loudnorm_output(&output.stderr)?);
+    }
+    build(&measured)
+}
+
+/// The audible part of a clip, whose volume a per-clip normalization adjusts
+pub(crate) struct ClipAudio<'a> {
+    path: &'a str,
+    start: f64,
+    end: f64,
+    volume: &'a mut f64,
+}
+
+impl<'a> ClipAudio<'a> {
+    fn new(path: &'a str, in_point: f64, out_point: f64, source_offset: Option<f64>, volume: &'a mut f64) -> Self {
+        let start = source_offset.unwrap_or(in_point);
+        Self { path, start, end: start + (out_point - in_point).max(0.0), volume }
+    }
+}
+
+pub(crate) fn main_clip_audio(clips: &mut [ClipData]) -> Vec<ClipAudio<'_>> {
+    clips.iter_mut()
+        .filter(|c| !c.muted)
+        .map(|c| ClipAudio::new(&c.path, c.in_point, c.out_point, c.source_offset, &mut c.volume))
+        .collect()
+}
+
+pub(crate) fn pip_clip_audio(clips: &mut [PipClipData]) -> Vec<ClipAudio<'_>> {
+    clips.iter_mut()
+        .filter(|c| !c.muted)
+        .map(|c| ClipAudio::new(&c.path, c.in_point, c.out_point, c.source_offset, &mut c.volume))
+        .collect()
+}
+
+pub(crate) fn audio_track_audio(tracks: &mut [AudioTrack]) -> Vec<ClipAudio<'_>> {
+    tracks.iter_mut()
+        .filter(|t| !t.muted)
+        .flat_map(|t| t.clips.iter_mut())
+        .filter(|c| !c.muted)
+        .map(|c| ClipAudio::new(&c.path, c.in_point, c.out_point, c.source_offset, &mut c.volume))
+        .collect()
+}
+
+pub(crate) fn timeline_clip_audio(timeline: &mut TimelineData) -> Vec<ClipAudio<'_>> {
+    timeline.tracks.iter_mut()
+        .filter(|t| !t.muted)
+        .flat_map(|t| t.clips.iter_mut())
+        .filter_map(|clip| match clip {
+            TimelineClip::Video(layer) if !layer.muted => {
+                Some(ClipAudio::new(&layer.path, layer.in_point, layer.out_point, layer.source_offset, &mut layer.volume))
+            }
+            TimelineClip::Audio(clip) if !clip.muted => {
+                Some(ClipAudio::new(&clip.path, clip.in_point, clip.out_point, clip.source_offset, &mut clip.volume))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Run loudnorm's analysis over `[start, end)` of a file's first audio stream
+async fn measure_range(
+    export: &RunningExport,
+    ffmpeg_path: &Path,
+    path: &str,
+    start: f64,
+    end: f64,
+    loudness: &LoudnessSettings,
+) -> Result<LoudnormStats, String> {
+    let filter = LoudnessSettings { measured: None, ..loudness.clone() }.loudnorm_filter();
+    let mut args: Vec<String> = ["-hide_banner", "-nostats", "-ss", &start.to_string(), "-t", &(end - start).to_string(), "-i", path]
+        .map(String::from)
+        .to_vec();
+    args.extend(["-map", "0:a:0", "-af", &filter, "-f", "null", "-"].map(String::from));
+
+    let output = export.run_ffmpeg_quiet(ffmpeg_path, &args).await?;
+    if !output.success {
+        return Err(format!("Loudness measurement failed: {}", output.stderr.lines().last().unwrap_or("unknown error")));
+    }
+
+    parse_loudnorm_output(&output.stderr)
+}
+
+/// Gain in dB that brings `stats` to the integrated target without pushing peaks over the ceiling
+fn normalization_gain(stats: &LoudnormStats, loudness: &LoudnessSettings) -> f64 {
+    let gain = loudness.integrated_target() - stats.input_i;
+    gain.min(loudness.true_peak_target() - stats.input_tp)
+}
+
+/// Fold a per-clip loudness gain into each clip's volume. Clips cut from the same source share
+/// one measurement over the span they use, so pieces of a recording keep their relative levels.
+/// The measurements run as part of `export`, so `cancel_export` stops them.
+pub(crate) async fn normalize_clip_loudness(
+    export: &RunningExport,
+    clips: Vec<ClipAudio<'_>>,
+    loudness: Option<&LoudnessSettings>,
+) -> Result<(), String> {
+    let Some(loudness) = loudness.filter(|l| l.scope == LoudnessScope::PerClip) else {
+        return Ok(());
+    };
+
+    let mut sources: Vec<(&str, f64, f64, Vec<&mut f64>)> = Vec::new();
+    for clip in clips {
+        match sources.iter_mut().find(|(path, ..)| *path == clip.path) {
+            Some((_, start, end, volumes)) => {
+                *start = start.min(clip.start);
+                *end = end.max(clip.end);
+                volumes.push(clip.volume);
+            }
+            None => sources.push((clip.path, clip.start, clip.end, vec![clip.volume])),
+        }
+    }
+
+    let ffmpeg_path = get_ffmpeg_path()?;
+    for (path, start, end, volumes) in sources {
+        let probe_path = path.to_string();
+        let has_audio = tokio::task::spawn_blocking(move || has_audio_stream(&probe_path)).await.unwrap_or(false);
+        if end <= start || !has_audio {
+            continue;
+        }
+        // A source that can't be measured (e.g. silence) is left at its own level
+        let gain = match measure_range(export, &ffmpeg_path, path, start, end, loudness).await {
+            Ok(stats) => normalization_gain(&stats, loudness),
+            Err(e) if e == EXPORT_CANCELLED => return Err(e),
+            Err(e) => {
+                eprintln!("Warning: Not normalizing {}: {}", path, e);
+                continue;
+            }
+        };
+        for volume in volumes {
+            *volume *= 10f64.powf(gain / 20.0);
+        }
+    }
+
+    Ok(())
+}
+
+/// Short-term loudness at one point in a file
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct LoudnessPoint {
+    pub time: f64,
+    pub lufs: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LoudnessReport {
+    #[serde(rename = "integratedLufs")]
+    pub integrated_lufs: f64,
+    /// Loudness range in LU
+    #[serde(rename = "loudnessRange")]
+    pub loudness_range: f64,
+    #[serde(rename = "truePeak")]
+    pub true_peak: Option<f64>,
+    /// Loudest 3-second window
+    #[serde(rename = "shortTermMax")]
+    pub short_term_max: Option<f64>,
+    /// Short-term loudness once per second, gated-out silence omitted
+    #[serde(rename = "shortTerm")]
+    pub short_term: Vec<LoudnessPoint>,
+}
+
+fn field_value(line: &str, key: &str) -> Option<f64> {
+    line.split(key).nth(1)?.split_whitespace().next()?.parse().ok()
+}
+
+/// Parse ebur128 output: per-frame `t: ... S: ...` lines, then the summary block
+pub(crate) fn parse_ebur128_output(stderr: &str) -> Result<LoudnessReport, String> {
+    let summary_start = stderr.rfind("Summary:").ok_or("FFmpeg did not report a loudness summary")?;
+    let (frames, summary) = stderr.split_at(summary_start);
+
+    let mut short_term: Vec<LoudnessPoint> = Vec::new();
+    for line in frames.lines().filter(|l| l.contains("Parsed_ebur128")) {
+        let (Some(time), Some(lufs)) = (field_value(line, " t:"), field_value(line, " S:")) else {
+            continue;
+        };
+        let second = time.floor();
+        if lufs > ABSOLUTE_GATE_LUFS && short_term.last().is_none_or(|p| p.time.floor() < second) {
+            short_term.push(LoudnessPoint { time, lufs });
+        }
+    }
+
+    let summary_value = |key: &str| summary.lines().find_map(|l| field_value(l, key));
+
+    Ok(LoudnessReport {
+        integrated_lufs: summary_value(" I:").ok_or("Loudness summary has no integrated loudness")?,
+        loudness_range: summary_value(" LRA:").ok_or("Loudness summary has no loudness range")?,
+        true_peak: summary_value(" Peak:"),
+        short_term_max: short_term.iter().map(|p| p.lufs).reduce(f64::max),
+        short_term,
+    })
+}
+
+/// Measure a source file's integrated and short-term loudness (LUFS), loudness range and true peak
+#[tauri::command]
+pub async fn measure_loudness(file_path: String) -> Result<LoudnessReport, String> {
+    let probe_path = file_path.clone();
+    if !tokio::task::spawn_blocking(move || has_audio_stream(&probe_path)).await.unwrap_or(false) {
+        return Err("File has no audio to measure".to_string());
+    }
+
+    let ffmpeg_path = get_ffmpeg_path()?;
+    // peak=true drops per-frame logging to verbose unless framelog is set
+    let output = Command::new(&ffmpeg_path)
+        .args(["-hide_banner", "-nostats", "-i", &file_path])
+        .args(["-map", "0:a:0", "-af", "ebur128=peak=true:framelog=info", "-f", "null", "-"])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Loudness measurement failed: {}", stderr.lines().last().unwrap_or("unknown error")));
+    }
+
+    parse_ebur128_output(&String::from_utf8_lossy(&output.stderr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loudnorm_second_pass_uses_measurements() {
+        let stderr = r#"size=N/A time=00:01:00.00 bitrate=N/A speed= 250x
+[Parsed_loudnorm_0 @ 0x55d1c2a0]
+{
+	"input_i" : "-27.61",
+	"input_tp" : "-4.47",
+	"input_lra" : "18.06",
+	"input_thresh" : "-39.20",
+	"output_i" : "-16.58",
+	"output_tp" : "-1.50",
+	"output_lra" : "14.78",
+	"output_thresh" : "-27.71",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.58"
+}"#;
+        let mut loudness = LoudnessSettings { preset: LoudnessPreset::Podcast, ..Default::default() };
+        loudness.measured = Some(parse_loudnorm_output(stderr).unwrap());
+
+        assert_eq!(loudness.loudnorm_filter(),
+            "loudnorm=I=-16:TP=-1:LRA=11:measured_I=-27.61:measured_TP=-4.47:measured_LRA=18.06:measured_thresh=-39.2:offset=0.58:linear=true");
+        // 11.61 dB would reach -16 LUFS, but the peaks only have 3.47 dB of headroom
+        assert!((normalization_gain(loudness.measured.as_ref().unwrap(), &loudness) - 3.47).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse_ebur128_summary_and_short_term() {
+        let stderr = "\
+[Parsed_ebur128_0 @ 0x1] t: 0.4  TARGET:-23 LUFS    M:-120.7 S:-120.7     I: -70.0 LUFS       LRA:   0.0 LU
+[Parsed_ebur128_0 @ 0x1] t: 1.1  TARGET:-23 LUFS    M: -18.2 S: -19.0     I: -18.9 LUFS       LRA:   0.0 LU
+[Parsed_ebur128_0 @ 0x1] t: 1.5  TARGET:-23 LUFS    M: -17.0 S: -17.5     I: -18.1 LUFS       LRA:   1.2 LU
+[Parsed_ebur128_0 @ 0x1] t: 2.1  TARGET:-23 LUFS    M: -16.0 S: -16.4     I: -17.6 LUFS       LRA:   2.0 LU
+[Parsed_ebur128_0 @ 0x1] Summary:
+
+  Integrated loudness:
+    I:         -17.6 LUFS
+    Threshold: -27.8 LUFS
+
+  Loudness range:
+    LRA:         2.0 LU
+    Threshold:  -37.7 LUFS
+
+  True peak:
+    Peak:        -0.8 dBFS
+";
+        let report = parse_ebur128_output(stderr).unwrap();
+
+        assert_eq!((report.integrated_lufs, report.loudness_range, report.true_peak), (-17.6, 2.0, Some(-0.8)));
+        let times: Vec<f64> = report.short_term.iter().map(|p| p.time).collect();
+        assert_eq!(times, vec![1.1, 2.1]);
+        assert_eq!(report.short_term_max, Some(-16.4));
+    }
+}
@@ -0,0 +1,422 @@
+// ClipForge - Multi-Track Export Module
+// Main track plus picture-in-picture exports, with still-image layers, audio tracks and a music bed
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::get_ffmpeg_path;
+use crate::audio_tracks::{audio_tracks_end, push_audio_tracks, split_edit_ranges, AudioTrack};
+use crate::export::{build_clip_audio_filter, has_audio_stream, parse_ffmpeg_error, push_main_clip_audio, resolve_export_resolution, ClipData, AUDIO_SAMPLE_RATE};
+use crate::export_plan::{apply_export_overlays, finish_output_args, run_export_plan, ExportPlan};
+use crate::export_progress::RunningExport;
+use crate::export_settings::ExportSettings;
+use crate::image_overlay::{compile_image_layer, ImageLayer};
+use crate::loudness::{
+    apply_mix_loudness, audio_track_audio, loudness_pass_args, main_clip_audio, normalize_clip_loudness, pip_clip_audio,
+    plan_with_loudness,
+};
+use crate::music::{apply_music_track, MusicTrack};
+use crate::transitions::{build_track_join, transition_normalize_filter, TrackSegment};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PipClipData {
+    pub path: String,
+    pub duration: f64,
+    #[serde(rename = "inPoint")]
+    pub in_point: f64,
+    #[serde(rename = "outPoint")]
+    pub out_point: f64,
+    pub volume: f64,
+    pub muted: bool,
+    #[serde(rename = "sourceOffset")]
+    pub source_offset: Option<f64>,
+    #[serde(rename = "pipSettings")]
+    pub pip_settings: Option<PipSettings>,
+    /// Position of the clip on the export timeline in seconds. Clips without one are
+    /// placed back-to-back after the previous PiP clip.
+    #[serde(rename = "timelineStart")]
+    pub timeline_start: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PipSettings {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub opacity: f64,
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn export_multi_track_video(
+    app: AppHandle,
+    main_track_clips: Vec<serde_json::Value>,
+    pip_track_clips: Vec<serde_json::Value>,
+    output_path: String,
+    width: Option<i32>,
+    height: Option<i32>,
+    export_id: Option<String>,
+    settings: Option<ExportSettings>,
+    music_track: Option<MusicTrack>,
+    audio_tracks: Option<Vec<AudioTrack>>,
+    image_layers: Option<Vec<ImageLayer>>,
+) -> Result<String, String> {
+    let image_layers = image_layers.unwrap_or_default();
+    if main_track_clips.is_empty() && pip_track_clips.is_empty() && image_layers.is_empty() {
+        return Err("No clips to export".to_string());
+    }
+    
+    let ffmpeg_path = get_ffmpeg_path()?;
+    
+    // Parse main track clips
+    let mut parsed_main_clips = Vec::new();
+    for clip_json in main_track_clips {
+        let clip: ClipData = serde_json::from_value(clip_json)
+            .map_err(|e| format!("Failed to parse main track clip data: {}", e))?;
+        parsed_main_clips.push(clip);
+    }
+    
+    // Parse PIP track clips
+    let mut parsed_pip_clips = Vec::new();
+    for clip_json in pip_track_clips {
+        let clip: PipClipData = serde_json::from_value(clip_json)
+            .map_err(|e| format!("Failed to parse PIP track clip data: {}", e))?;
+        parsed_pip_clips.push(clip);
+    }
+    
+    let settings = settings.unwrap_or_default();
+    let export_id = export_id.unwrap_or_else(|| output_path.clone());
+    let export = RunningExport::start(&app, &export_id)?;
+    
+    let mut audio_tracks = audio_tracks.unwrap_or_default();
+    let mut clip_audio = main_clip_audio(&mut parsed_main_clips);
+    clip_audio.extend(pip_clip_audio(&mut parsed_pip_clips));
+    clip_audio.extend(audio_track_audio(&mut audio_tracks));
+    normalize_clip_loudness(&export, clip_audio, settings.loudness.as_ref()).await?;
+    let plan = plan_with_loudness(&export, &ffmpeg_path, &settings, |s| {
+        build_multi_track_plan(&parsed_main_clips, &parsed_pip_clips, &image_layers, &audio_tracks, music_track.as_ref(), &output_path, width, height, s)
+    }).await?;
+    
+    // Execute FFmpeg, reporting progress against the final timeline duration
+    let output = run_export_plan(&export, &ffmpeg_path, &plan, &output_path).await?;
+    
+    if !output.success {
+        let stderr = &output.stderr;
+        
+        // Log full error for debugging
+        eprintln!("FFmpeg stderr: {}", stderr);
+        
+        // Return detailed error with actual FFmpeg output
+        let parsed_error = parse_ffmpeg_error(stderr);
+        return Err(format!("{}\n\nDebug info:\n- Resolution: {}x{}\n- Main clips: {}\n- PiP clips: {}\n\nFull FFmpeg error:\n{}", 
+            parsed_error, plan.width, plan.height, parsed_main_clips.len(), parsed_pip_clips.len(), stderr));
+    }
+    
+    Ok("Multi-track export completed successfully".to_string())
+}
+
+/// Build the FFmpeg arguments for a main track + PiP track export, with still-image layers over
+/// the picture, any audio tracks and an optional music track underneath
+#[allow(clippy::too_many_arguments)]
+pub fn build_multi_track_plan(
+    main_clips: &[ClipData],
+    pip_clips: &[PipClipData],
+    image_layers: &[ImageLayer],
+    audio_tracks: &[AudioTrack],
+    music_track: Option<&MusicTrack>,
+    output_path: &str,
+    width: Option<i32>,
+    height: Option<i32>,
+    settings: &ExportSettings,
+) -> Result<ExportPlan, String> {
+    if main_clips.is_empty() && pip_clips.is_empty() && image_layers.is_empty() {
+        return Err("No clips to export".to_string());
+    }
+    
+    let (export_width, export_height) = resolve_export_resolution(width, height)?;
+    
+    // Build FFmpeg command
+    let mut cmd_args = Vec::new();
+    
+    // Add all input files (main track first, then PIP track)
+    for clip in main_clips {
+        cmd_args.push("-i".to_string());
+        cmd_args.push(clip.path.clone());
+    }
+    for clip in pip_clips {
+        cmd_args.push("-i".to_string());
+        cmd_args.push(clip.path.clone());
+    }
+    
+    // Build filter complex
+    let mut filter_parts = Vec::new();
+    
+    // Process main track clips
+    let mut main_segments = Vec::new();
+    let normalize = transition_normalize_filter(main_clips, settings);
+    
+    // For split clips, use source_offset as the trim start; otherwise use in_point
+    let video_ranges: Vec<(f64, f64)> = main_clips.iter()
+        .map(|clip| {
+            let trim_duration = if clip.out_point < clip.duration {
+                clip.out_point - clip.in_point
+            } else {
+                clip.duration - clip.in_point
+            };
+            (clip.source_offset.unwrap_or(clip.in_point), trim_duration)
+        })
+        .collect();
+    let audio_ranges = split_edit_ranges(main_clips, &video_ranges);
+    
+    for (i, clip) in main_clips.iter().enumerate() {
+        let input_video = format!("[{}:v]", i);
+        let (trim_start, trim_duration) = video_ranges[i];
+        
+        let video_filter = format!("{}trim=start={}:duration={},setpts=PTS-STARTPTS,scale={}:{}:force_original_aspect_ratio=decrease,pad={}:{}:(ow-iw)/2:(oh-ih)/2{}[v{}]", 
+            input_video, trim_start, trim_duration, export_width, export_height, export_width, export_height, normalize, i);
+        
+        filter_parts.push(video_filter);
+        
+        let (audio_start, audio_duration) = audio_ranges[i];
+        push_main_clip_audio(&mut filter_parts, clip, i, audio_start, audio_duration, &format!("[a{}]", i))?;
+        
+        main_segments.push(TrackSegment {
+            video: format!("[v{}]", i),
+            audio: format!("[a{}]", i),
+            duration: trim_duration,
+            transition: clip.transition.clone(),
+        });
+    }
+    
+    // Join main track clips; transitions overlap neighbouring clips and shorten the track
+    let (main_join_parts, main_duration) = build_track_join(&main_segments, "main_join", "[main_concat]", "[main_audio_concat]");
+    
+    // Calculate durations
+    let pip_starts = pip_timeline_starts(pip_clips);
+    let pip_duration = pip_clips.iter()
+        .zip(&pip_starts)
+        .map(|(c, start)| start + (c.out_point - c.in_point))
+        .fold(0.0, f64::max);
+    
+    let image_duration = image_layers.iter().map(|l| l.timeline_end).fold(0.0, f64::max);
+    
+    // Use the longest track for the final video; audio tracks can run past the picture
+    let final_duration = main_duration.max(pip_duration).max(image_duration).max(audio_tracks_end(audio_tracks));
+    
+    // Concatenate main track videos
+    // If main track exists, we need to create [main] output
+    // If no main track clips, create black background as [main]
+    if !main_segments.is_empty() {
+        filter_parts.extend(main_join_parts);
+        
+        // If main track is shorter than final duration, extend with black
+        if main_duration < final_duration {
+            filter_parts.push(format!("[main_concat]tpad=stop_mode=clone:stop_duration={}[main]", 
+                final_duration - main_duration));
+        } else {
+            // Main track is long enough - just relabel by using scale passthrough
+            filter_parts.push("[main_concat]scale=iw:ih[main]".to_string());
+        }
+        
+        // Pad main audio with silence so it always covers the final duration
+        filter_parts.push(format!("[main_audio_concat]apad=whole_dur={}[main_audio]", final_duration));
+    } else {
+        // No main track clips, create a black background for the full duration
+        // Note: color filter needs explicit format for proper rendering
+        filter_parts.push(format!("color=black:size={}x{}:duration={}:rate=30[main]", 
+            export_width, export_height, final_duration));
+        filter_parts.push(format!("anullsrc=channel_layout=stereo:sample_rate={},atrim=duration={}[main_audio]", 
+            AUDIO_SAMPLE_RATE, final_duration));
+    }
+    
+    let mut audio_mix_inputs = vec!["[main_audio]".to_string()];
+    
+    // Process PIP track clips and overlay them with proper timing
+    let mut current_output = "[main]".to_string();
+    let mut input_index = main_clips.len();
+    
+    for (i, clip) in pip_clips.iter().enumerate() {
+        let input_video = format!("[{}:v]", input_index);
+        
+        // For split clips, use source_offset as the trim start; otherwise use in_point
+        let trim_start = clip.source_offset.unwrap_or(clip.in_point);
+        let trim_duration = clip.out_point - clip.in_point;
+        let pip_start_time = pip_starts[i];
+        let pip_end_time = pip_start_time + trim_duration;
+        
+        // Get PIP settings or use defaults
+        let pip_settings = clip.pip_settings.as_ref().unwrap_or(&PipSettings {
+            x: 0.75,
+            y: 0.75,
+            width: 0.25,
+            height: 0.25,
+            opacity: 1.0,
+        });
+        
+        // Scale PIP clip to the specified size (relative to export resolution)
+        let pip_width = (export_width as f64 * pip_settings.width) as i32;
+        let pip_height = (export_height as f64 * pip_settings.height) as i32;
+        
+        // Blend with the clip opacity through an alpha channel
+        let opacity = pip_settings.opacity.clamp(0.0, 1.0);
+        let opacity_filter = if opacity < 1.0 {
+            format!(",format=rgba,colorchannelmixer=aa={}", opacity)
+        } else {
+            String::new()
+        };
+        
+        // Process PIP clip with timing - timestamps are shifted to the clip's timeline start
+        // so the overlay consumes PiP frames from that point instead of from t=0
+        let pip_filter = format!("{}trim=start={}:duration={},setpts=PTS-STARTPTS+{}/TB,scale={}:{}{}[pip{}]", 
+            input_video, trim_start, trim_duration, pip_start_time, pip_width, pip_height, opacity_filter, i);
+        filter_parts.push(pip_filter);
+        
+        // Overlay PIP on main video with proper timing (relative to export resolution)
+        let overlay_x = (export_width as f64 * pip_settings.x) as i32;
+        let overlay_y = (export_height as f64 * pip_settings.y) as i32;
+        
+        // Show the PIP only between its start and end; eof_action=pass stops the last frame
+        // from being repeated after the clip ends
+        let overlay_filter = format!("{}[pip{}]overlay=x={}:y={}:eof_action=pass:enable='between(t,{},{})'[overlay{}]", 
+            current_output, i, overlay_x, overlay_y, pip_start_time, pip_end_time, i);
+        filter_parts.push(overlay_filter);
+        
+        // PiP audio is delayed to the clip's start and mixed over the main track
+        let pip_audio_filter = build_clip_audio_filter(
+            input_index,
+            has_audio_stream(&clip.path),
+            trim_start,
+            trim_duration,
+            clip.volume,
+            clip.muted,
+            &format!("[pip_audio_trim{}]", i),
+        );
+        filter_parts.push(pip_audio_filter);
+        let delay_ms = (pip_start_time * 1000.0).round() as i64;
+        filter_parts.push(format!("[pip_audio_trim{}]adelay=delays={}:all=1[pip_audio{}]", i, delay_ms, i));
+        audio_mix_inputs.push(format!("[pip_audio{}]", i));
+        
+        current_output = format!("[overlay{}]", i);
+        input_index += 1;
+    }
+    
+    // Still images (logos, lower thirds) go over both video tracks, looped for their duration
+    for (i, layer) in image_layers.iter().enumerate() {
+        current_output = compile_image_layer(&mut cmd_args, &mut filter_parts, layer, input_index,
+            &format!("image{}", i), &current_output, export_width, settings.frame_rate)?;
+        input_index += 1;
+    }
+    
+    let input_index = push_audio_tracks(&mut cmd_args, &mut filter_parts, audio_tracks, input_index, &mut audio_mix_inputs)?;
+    
+    // Mix main track audio with any PiP and audio track audio (normalize=0 keeps clip gains as set)
+    let audio_output = if audio_mix_inputs.len() > 1 {
+        filter_parts.push(format!("{}amix=inputs={}:duration=first:normalize=0[outa]", 
+            audio_mix_inputs.join(""), audio_mix_inputs.len()));
+        "[outa]".to_string()
+    } else {
+        "[main_audio]".to_string()
+    };
+    
+    let (audio_output, input_index) = match music_track {
+        Some(music) => apply_music_track(&mut cmd_args, &mut filter_parts, music, input_index, &audio_output, final_duration)?,
+        None => (audio_output, input_index),
+    };
+    
+    let (video_output, caption_maps) = apply_export_overlays(&mut cmd_args, &mut filter_parts, settings, input_index, &current_output, export_width, final_duration)?;
+    let audio_output = apply_mix_loudness(&mut filter_parts, audio_output, settings.loudness.as_ref());
+    
+    let filter_complex = filter_parts.join(";");
+    
+    cmd_args.push("-filter_complex".to_string());
+    cmd_args.push(filter_complex);
+    cmd_args.push("-map".to_string());
+    cmd_args.push(video_output);
+    cmd_args.push("-map".to_string());
+    cmd_args.push(audio_output);
+    cmd_args.extend(caption_maps);
+    let loudness_pass_args = loudness_pass_args(&cmd_args, settings.loudness.as_ref());
+    let (args, first_pass_args) = finish_output_args(cmd_args, settings, output_path)?;
+    
+    // Debug: Log export parameters for troubleshooting
+    eprintln!("FFmpeg export resolution: {}x{}", export_width, export_height);
+    eprintln!("Main clips: {}, PiP clips: {}", main_clips.len(), pip_clips.len());
+    
+    Ok(ExportPlan {
+        args,
+        first_pass_args,
+        loudness_pass_args,
+        total_duration: final_duration,
+        width: export_width,
+        height: export_height,
+    })
+}
+
+/// Timeline start of each PiP clip. Clips without an explicit start follow the previous clip.
+fn pip_timeline_starts(pip_clips: &[PipClipData]) -> Vec<f64> {
+    let mut starts = Vec::with_capacity(pip_clips.len());
+    let mut next_start = 0.0;
+    
+    for clip in pip_clips {
+        let start = clip.timeline_start.unwrap_or(next_start).max(0.0);
+        starts.push(start);
+        next_start = start + (clip.out_point - clip.in_point);
+    }
+    
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_pip_clips_shown_only_in_their_window() {
+        let main: Vec<ClipData> = serde_json::from_value(serde_json::json!([
+            { "path": "a.mp4", "duration": 12.0, "inPoint": 0.0, "outPoint": 12.0, "volume": 100.0, "muted": false }
+        ])).unwrap();
+        let pip: Vec<PipClipData> = serde_json::from_value(serde_json::json!([
+            { "path": "b.mp4", "duration": 5.0, "inPoint": 1.0, "outPoint": 4.0, "volume": 100.0, "muted": false,
+              "timelineStart": 1.0, "pipSettings": { "x": 0.75, "y": 0.75, "width": 0.25, "height": 0.25, "opacity": 0.5 } },
+            { "path": "c.mp4", "duration": 2.0, "inPoint": 0.0, "outPoint": 2.0, "volume": 100.0, "muted": false,
+              "timelineStart": 8.0 }
+        ])).unwrap();
+
+        let plan = build_multi_track_plan(&main, &pip, &[], &[], None, "out.mp4", Some(1280), Some(720), &ExportSettings::default()).unwrap();
+        let graph = &plan.args[plan.args.iter().position(|a| a == "-filter_complex").unwrap() + 1];
+        let parts: Vec<&str> = graph.split(';').collect();
+
+        // The half-opaque clip fades through an alpha channel; the opaque one needs no rgba conversion
+        assert!(parts.contains(&"[1:v]trim=start=1:duration=3,setpts=PTS-STARTPTS+1/TB,scale=320:180,format=rgba,colorchannelmixer=aa=0.5[pip0]"));
+        assert!(parts.contains(&"[2:v]trim=start=0:duration=2,setpts=PTS-STARTPTS+8/TB,scale=320:180[pip1]"));
+        // Each overlay is enabled only over its own clip, leaving the 4s-8s gap clear
+        assert!(parts.contains(&"[main][pip0]overlay=x=960:y=540:eof_action=pass:enable='between(t,1,4)'[overlay0]"));
+        assert!(parts.contains(&"[overlay0][pip1]overlay=x=960:y=540:eof_action=pass:enable='between(t,8,10)'[overlay1]"));
+    }
+
+    #[test]
+    fn test_multi_track_overlays_image_layers() {
+        let logo = std::env::temp_dir().join(format!("clipforge_logo_{}.png", std::process::id()));
+        fs::write(&logo, b"").unwrap();
+        let main: Vec<ClipData> = serde_json::from_value(serde_json::json!([
+            { "path": "a.mp4", "duration": 10.0, "inPoint": 0.0, "outPoint": 10.0, "volume": 100.0, "muted": false }
+        ])).unwrap();
+        let images: Vec<ImageLayer> = serde_json::from_value(serde_json::json!([
+            { "path": logo.to_string_lossy(), "x": 1.0, "y": 0.0, "scale": 0.1, "timelineStart": 2.0, "timelineEnd": 12.0 }
+        ])).unwrap();
+
+        let plan = build_multi_track_plan(&main, &[], &images, &[], None, "out.mp4", Some(1280), Some(720), &ExportSettings::default());
+        let _ = fs::remove_file(&logo);
+        let plan = plan.unwrap();
+
+        // The image is the second input, looped for its 10s and shown over the main track from 2s
+        let loop_at = plan.args.iter().position(|a| a == "-loop").unwrap();
+        assert_eq!(plan.args[loop_at..loop_at + 8].join(" "), format!("-loop 1 -framerate 30 -t 10 -i {}", logo.to_string_lossy()));
+        let graph = &plan.args[plan.args.iter().position(|a| a == "-filter_complex").unwrap() + 1];
+        assert!(graph.contains("[1:v]format=rgba,scale=128:-2,setpts=PTS-STARTPTS+2/TB[image0_layer]"));
+        assert!(graph.contains("[main][image0_layer]overlay=x=(W-w)*1:y=(H-h)*0:eof_action=pass:enable='between(t,2,12)'[image0_out]"));
+        assert_eq!(plan.total_duration, 12.0);
+    }
+}
@@ -0,0 +1,201 @@
+// ClipForge - Music Track Module
+// Background music track for multi-track exports: looping, fades and sidechain ducking under dialogue
+
+use serde::{Deserialize, Serialize};
+
+use crate::export::{has_audio_stream, AUDIO_SAMPLE_RATE};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MusicClip {
+    pub path: String,
+    /// Position on the export timeline in seconds
+    #[serde(rename = "timelineStart", default)]
+    pub timeline_start: f64,
+    /// Section of the source that plays (and repeats when looping)
+    #[serde(rename = "inPoint", default)]
+    pub in_point: f64,
+    #[serde(rename = "outPoint")]
+    pub out_point: f64,
+    /// Timeline length; defaults to the section length, or to the end of the export when looping
+    pub duration: Option<f64>,
+    #[serde(default = "default_volume")]
+    pub volume: f64,
+    #[serde(rename = "fadeIn", default)]
+    pub fade_in: f64,
+    #[serde(rename = "fadeOut", default)]
+    pub fade_out: f64,
+    #[serde(rename = "loop", default)]
+    pub looped: bool,
+}
+
+fn default_volume() -> f64 {
+    100.0
+}
+
+/// Sidechain compression of the music, keyed off the main track's dialogue
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Ducking {
+    /// Dialogue level above which the music starts to dip
+    #[serde(rename = "thresholdDb")]
+    pub threshold_db: f64,
+    /// How hard the music is pulled down while someone speaks
+    pub ratio: f64,
+    #[serde(rename = "attackMs")]
+    pub attack_ms: f64,
+    /// How long the music takes to come back after speech stops
+    #[serde(rename = "releaseMs")]
+    pub release_ms: f64,
+}
+
+impl Default for Ducking {
+    fn default() -> Self {
+        Self {
+            threshold_db: -35.0,
+            ratio: 8.0,
+            attack_ms: 20.0,
+            release_ms: 600.0,
+        }
+    }
+}
+
+impl Ducking {
+    /// sidechaincompress takes the threshold as a linear amplitude
+    fn filter(&self) -> String {
+        format!("sidechaincompress=threshold={}:ratio={}:attack={}:release={}",
+            10f64.powf(self.threshold_db.clamp(-60.0, 0.0) / 20.0),
+            self.ratio.clamp(1.0, 20.0),
+            self.attack_ms.clamp(0.01, 2000.0),
+            self.release_ms.clamp(0.01, 9000.0))
+    }
+}
+
+/// Audio-only track mixed under the main and PiP tracks
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MusicTrack {
+    pub clips: Vec<MusicClip>,
+    pub muted: bool,
+    pub ducking: Option<Ducking>,
+}
+
+impl MusicTrack {
+    fn audible_clips(&self) -> impl Iterator<Item = &MusicClip> {
+        self.clips.iter().filter(move |c| !self.muted && c.volume > 0.0)
+    }
+
+}
+
+/// Filter for one music clip: trim to its section, loop it if asked, fade, and delay it to its
+/// timeline start. `export_duration` bounds clips that loop to the end of the export.
+fn music_clip_filter(clip: &MusicClip, input_index: usize, export_duration: f64, output_label: &str) -> Result<String, String> {
+    let section = clip.out_point - clip.in_point;
+    if section <= 0.0 {
+        return Err(format!("Music clip '{}' has no length", clip.path));
+    }
+
+    let start = clip.timeline_start.max(0.0);
+    let length = match (clip.duration, clip.looped) {
+        (Some(duration), true) => duration,
+        (Some(duration), false) => duration.min(section),
+        (None, true) => export_duration - start,
+        (None, false) => section,
+    };
+    if length <= 0.0 {
+        return Err(format!("Music clip '{}' starts after the end of the export", clip.path));
+    }
+
+    let mut filters = vec![
+        format!("[{}:a]atrim=start={}:duration={},asetpts=PTS-STARTPTS", input_index, clip.in_point, section),
+        format!("aformat=sample_fmts=fltp:sample_rates={}:channel_layouts=stereo", AUDIO_SAMPLE_RATE),
+    ];
+    if clip.looped && length > section {
+        // aloop repeats a buffered number of samples, here the whole section
+        let samples = (section * AUDIO_SAMPLE_RATE as f64).round() as i64;
+        filters.push(format!("aloop=loop=-1:size={}", samples));
+        filters.push(format!("atrim=duration={}", length));
+    }
+    filters.push(format!("volume={}", clip.volume.max(0.0) / 100.0));
+
+    let max_fade = length / 2.0;
+    if clip.fade_in > 0.0 {
+        filters.push(format!("afade=t=in:st=0:d={}", clip.fade_in.min(max_fade)));
+    }
+    if clip.fade_out > 0.0 {
+        let fade_out = clip.fade_out.min(max_fade);
+        filters.push(format!("afade=t=out:st={}:d={}", length - fade_out, fade_out));
+    }
+    filters.push(format!("adelay=delays={}:all=1", (start * 1000.0).round() as i64));
+
+    Ok(format!("{}{}", filters.join(","), output_label))
+}
+
+/// Add the music track's inputs and mix it under `dialogue`, ducked by the whole dialogue mix (main,
+/// PiP and voice-over tracks) when the track has ducking. Returns the new audio label and the next
+/// free input index. The music never makes the export longer than the dialogue mix.
+pub(crate) fn apply_music_track(
+    cmd_args: &mut Vec<String>,
+    filter_parts: &mut Vec<String>,
+    track: &MusicTrack,
+    mut input_index: usize,
+    dialogue: &str,
+    export_duration: f64,
+) -> Result<(String, usize), String> {
+    let mut music_labels = Vec::new();
+    for (i, clip) in track.audible_clips().enumerate() {
+        if !has_audio_stream(&clip.path) {
+            eprintln!("Warning: Music clip {} has no audio, skipping", clip.path);
+            continue;
+        }
+        cmd_args.push("-i".to_string());
+        cmd_args.push(clip.path.clone());
+
+        let label = format!("[music{}]", i);
+        filter_parts.push(music_clip_filter(clip, input_index, export_duration, &label)?);
+        music_labels.push(label);
+        input_index += 1;
+    }
+
+    let bed = match music_labels.len() {
+        0 => return Ok((dialogue.to_string(), input_index)),
+        1 => music_labels.remove(0),
+        n => {
+            filter_parts.push(format!("{}amix=inputs={}:duration=longest:normalize=0[music_bed]", music_labels.join(""), n));
+            "[music_bed]".to_string()
+        }
+    };
+
+    let (dialogue, bed) = match track.ducking {
+        Some(ducking) => {
+            // Ducking needs its own copy of the dialogue as the sidechain key
+            filter_parts.push(format!("{}asplit=2[dialogue_mix][duck_key]", dialogue));
+            filter_parts.push(format!("{}[duck_key]{}[music_ducked]", bed, ducking.filter()));
+            ("[dialogue_mix]", "[music_ducked]".to_string())
+        }
+        None => (dialogue, bed),
+    };
+
+    filter_parts.push(format!("{}{}amix=inputs=2:duration=first:normalize=0[outa_music]", dialogue, bed));
+    Ok(("[outa_music]".to_string(), input_index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_looped_music_clip_fills_export() {
+        let clip: MusicClip = serde_json::from_value(serde_json::json!({
+            "path": "bed.mp3", "timelineStart": 2.0, "inPoint": 1.0, "outPoint": 21.0,
+            "fadeIn": 3.0, "fadeOut": 4.0, "volume": 40.0, "loop": true
+        })).unwrap();
+
+        let filter = music_clip_filter(&clip, 3, 62.0, "[music0]").unwrap();
+
+        assert_eq!(filter, "[3:a]atrim=start=1:duration=20,asetpts=PTS-STARTPTS,\
+aformat=sample_fmts=fltp:sample_rates=48000:channel_layouts=stereo,aloop=loop=-1:size=960000,atrim=duration=60,\
+volume=0.4,afade=t=in:st=0:d=3,afade=t=out:st=56:d=4,adelay=delays=2000:all=1[music0]");
+        let ducking = Ducking { threshold_db: -20.0, ..Ducking::default() };
+        assert_eq!(ducking.filter(), "sidechaincompress=threshold=0.1:ratio=8:attack=20:release=600");
+    }
+}
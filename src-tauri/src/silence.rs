@@ -0,0 +1,220 @@
+// ClipForge - Silence Detection Module
+// Finds silent ranges in media with FFmpeg's silencedetect filter and cuts or tightens pauses in clips
+
+use std::fs;
+use std::process::Command;
+use serde::{Deserialize, Serialize};
+
+use crate::get_ffmpeg_path;
+use crate::edit_list::{split_clip, EditOptions};
+use crate::export::ClipData;
+use crate::filler_detection::{extract_audio_to_temp, TempFileCleanup};
+use crate::transcription::ClipRange;
+
+const DEFAULT_THRESHOLD_DB: f64 = -40.0;
+const DEFAULT_MIN_DURATION: f64 = 0.75;
+/// `extract_audio_to_temp` writes 16kHz mono 16-bit PCM
+const EXTRACTED_BYTES_PER_SECOND: f64 = 32000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SilenceRange {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl SilenceRange {
+    pub fn midpoint(&self) -> f64 {
+        (self.start + self.end) / 2.0
+    }
+}
+
+/// Parse silencedetect output. A silence still open at the end of the input runs to `media_duration`.
+pub(crate) fn parse_silencedetect_output(stderr: &str, media_duration: Option<f64>) -> Vec<SilenceRange> {
+    let mut ranges = Vec::new();
+    let mut open_start: Option<f64> = None;
+
+    for line in stderr.lines() {
+        if let Some(value) = line.split("silence_start:").nth(1) {
+            open_start = value.split_whitespace().next().and_then(|v| v.parse().ok());
+        } else if let Some(value) = line.split("silence_end:").nth(1) {
+            let end = value.split_whitespace().next().and_then(|v| v.parse::<f64>().ok());
+            if let (Some(start), Some(end)) = (open_start.take(), end) {
+                ranges.push(SilenceRange { start: start.max(0.0), end });
+            }
+        }
+    }
+
+    if let (Some(start), Some(end)) = (open_start, media_duration) {
+        if end > start {
+            ranges.push(SilenceRange { start, end });
+        }
+    }
+
+    ranges
+}
+
+/// Run silencedetect over a file's first audio stream. Times are relative to the start of the file.
+pub(crate) fn detect_silence_ranges(
+    path: &str,
+    noise_db: f64,
+    min_duration: f64,
+    media_duration: Option<f64>,
+) -> Result<Vec<SilenceRange>, String> {
+    let ffmpeg_path = get_ffmpeg_path()?;
+    let filter = format!("silencedetect=noise={}dB:d={}", noise_db, min_duration);
+
+    let output = Command::new(&ffmpeg_path)
+        .args(["-hide_banner", "-nostats", "-i", path, "-map", "0:a:0", "-af", &filter, "-f", "null", "-"])
+        .output()
+        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Silence detection failed: {}", stderr.lines().last().unwrap_or("unknown error")));
+    }
+
+    Ok(parse_silencedetect_output(&String::from_utf8_lossy(&output.stderr), media_duration))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PauseMode {
+    /// Cut the whole pause
+    #[default]
+    Remove,
+    /// Shorten the pause to `keep_pause` seconds
+    Tighten,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PauseOptions {
+    pub mode: PauseMode,
+    /// Length a tightened pause is shortened to, in seconds
+    #[serde(rename = "keepPause")]
+    pub keep_pause: f64,
+}
+
+impl Default for PauseOptions {
+    fn default() -> Self {
+        Self {
+            mode: PauseMode::Remove,
+            keep_pause: 0.3,
+        }
+    }
+}
+
+/// Ranges to cut from a clip (clip time) for silences given in timeline time
+fn pause_cuts(silences: &[SilenceRange], timeline_start: f64, options: &PauseOptions) -> Vec<(f64, f64)> {
+    let keep_each_side = match options.mode {
+        PauseMode::Remove => 0.0,
+        PauseMode::Tighten => options.keep_pause.max(0.0) / 2.0,
+    };
+
+    silences.iter()
+        .map(|s| (s.start - timeline_start + keep_each_side, s.end - timeline_start - keep_each_side))
+        .filter(|(start, end)| end > start)
+        .collect()
+}
+
+/// Silent ranges of a clip in timeline time
+#[tauri::command]
+pub async fn detect_silence(
+    file_path: String,
+    in_point: Option<f64>,
+    out_point: Option<f64>,
+    source_offset: Option<f64>,
+    timeline_start: Option<f64>,
+    threshold_db: Option<f64>,
+    min_duration: Option<f64>,
+) -> Result<Vec<SilenceRange>, String> {
+    let range = ClipRange::new(in_point, out_point, source_offset, timeline_start);
+    let audio_path = extract_audio_to_temp(&file_path, Some(range.source_start), range.source_end).await?;
+    let _cleanup = TempFileCleanup::new(audio_path.clone());
+
+    let duration = fs::metadata(&audio_path)
+        .map(|m| m.len() as f64 / EXTRACTED_BYTES_PER_SECOND)
+        .ok();
+    let threshold_db = threshold_db.unwrap_or(DEFAULT_THRESHOLD_DB);
+    let min_duration = min_duration.unwrap_or(DEFAULT_MIN_DURATION);
+
+    let silences = tokio::task::spawn_blocking(move || {
+        detect_silence_ranges(&audio_path, threshold_db, min_duration, duration)
+    })
+    .await
+    .map_err(|e| format!("Silence detection failed: {}", e))??;
+
+    Ok(silences.into_iter()
+        .map(|s| SilenceRange {
+            start: range.timeline_start + s.start,
+            end: range.timeline_start + s.end,
+        })
+        .collect())
+}
+
+/// Cut or tighten the given pauses (timeline time) in a clip and return the clips that remain
+#[tauri::command]
+pub async fn trim_pauses(
+    clip: ClipData,
+    silences: Vec<SilenceRange>,
+    timeline_start: Option<f64>,
+    pause_options: Option<PauseOptions>,
+    options: Option<EditOptions>,
+) -> Result<Vec<ClipData>, String> {
+    let pause_options = pause_options.unwrap_or_default();
+    let mut options = options.unwrap_or_default();
+    // A tightened pause already keeps its own breathing room, including at the clip's edges
+    if pause_options.mode == PauseMode::Tighten {
+        options.padding = 0.0;
+        options.snap_edges = false;
+    }
+
+    let cuts = pause_cuts(&silences, timeline_start.unwrap_or(0.0), &pause_options);
+    let pieces = split_clip(&clip, &cuts, &options);
+    if pieces.is_empty() {
+        return Err("The clip is entirely silent".to_string());
+    }
+
+    Ok(pieces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_silencedetect_output() {
+        let stderr = "\
+[silencedetect @ 0x1] silence_start: 1.5
+[silencedetect @ 0x1] silence_end: 2.75 | silence_duration: 1.25
+[silencedetect @ 0x1] silence_start: 9.2";
+
+        assert_eq!(parse_silencedetect_output(stderr, Some(10.0)), vec![
+            SilenceRange { start: 1.5, end: 2.75 },
+            SilenceRange { start: 9.2, end: 10.0 },
+        ]);
+    }
+
+    #[test]
+    fn test_tighten_keeps_part_of_each_pause() {
+        let silences = [SilenceRange { start: 12.0, end: 14.0 }, SilenceRange { start: 15.0, end: 15.2 }];
+        let options = PauseOptions { mode: PauseMode::Tighten, keep_pause: 0.4 };
+
+        // Pauses are given in timeline time for a clip starting at 10s; the short one is left alone
+        assert_eq!(pause_cuts(&silences, 10.0, &options), vec![(2.2, 3.8)]);
+    }
+
+    #[tokio::test]
+    async fn test_tighten_keeps_leading_pause() {
+        let clip: ClipData = serde_json::from_value(serde_json::json!({
+            "path": "a.mp4", "duration": 10.0, "inPoint": 0.0, "outPoint": 10.0, "volume": 100.0, "muted": false
+        })).unwrap();
+        let silences = vec![SilenceRange { start: 0.0, end: 2.0 }];
+        let pause_options = PauseOptions { mode: PauseMode::Tighten, keep_pause: 0.4 };
+
+        // The 0.2s kept before the cut is shorter than min_gap but must not be snapped away
+        let pieces = trim_pauses(clip, silences, None, Some(pause_options), None).await.unwrap();
+        let kept: Vec<(f64, f64)> = pieces.iter().map(|p| (p.source_offset.unwrap(), p.out_point)).collect();
+        assert_eq!(kept, [(0.0, 0.2), (1.8, 8.2)]);
+    }
+}
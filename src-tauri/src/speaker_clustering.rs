@@ -0,0 +1,161 @@
+// ClipForge - Speaker Clustering Module
+// Groups diarization window embeddings into speakers with cosine k-means, and scores the result
+
+use crate::diarization::CEPSTRA;
+
+const KMEANS_ITERATIONS: usize = 25;
+/// Silhouette is quadratic in the window count, so score a strided sample of long recordings
+const SILHOUETTE_SAMPLE: usize = 800;
+/// Windows on each side considered when smoothing labels
+const SMOOTHING_RADIUS: usize = 2;
+
+/// Standardise each dimension so no coefficient dominates, then scale to unit length to compare by direction
+pub(crate) fn normalize_embeddings(embeddings: &[Vec<f32>]) -> Vec<Vec<f32>> {
+    let count = embeddings.len().max(1) as f32;
+    let mut normalized = embeddings.to_vec();
+
+    for d in 0..CEPSTRA {
+        let mean = embeddings.iter().map(|e| e[d]).sum::<f32>() / count;
+        let deviation = (embeddings.iter().map(|e| (e[d] - mean).powi(2)).sum::<f32>() / count).sqrt().max(1e-6);
+        for e in &mut normalized {
+            e[d] = (e[d] - mean) / deviation;
+        }
+    }
+    for e in &mut normalized {
+        let norm = e.iter().map(|v| v * v).sum::<f32>().sqrt().max(1e-6);
+        e.iter_mut().for_each(|v| *v /= norm);
+    }
+
+    normalized
+}
+
+fn euclidean_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y).powi(2)).sum::<f32>().sqrt()
+}
+
+/// Closest distance between two cluster centres over the mean distance of a window to its own centre,
+/// measured on the raw (unstandardised) embeddings
+pub(crate) fn separation(raw: &[Vec<f32>], labels: &[usize], k: usize) -> f32 {
+    let centroids: Vec<Vec<f32>> = (0..k)
+        .map(|c| {
+            let members: Vec<&Vec<f32>> = raw.iter().zip(labels).filter(|(_, l)| **l == c).map(|(e, _)| e).collect();
+            let count = members.len().max(1) as f32;
+            (0..CEPSTRA).map(|d| members.iter().map(|m| m[d]).sum::<f32>() / count).collect()
+        })
+        .collect();
+
+    let spread = raw.iter()
+        .zip(labels)
+        .map(|(e, l)| euclidean_distance(e, &centroids[*l]))
+        .sum::<f32>() / raw.len().max(1) as f32;
+    let closest = (0..k)
+        .flat_map(|a| (a + 1..k).map(move |b| (a, b)))
+        .map(|(a, b)| euclidean_distance(&centroids[a], &centroids[b]))
+        .fold(f32::MAX, f32::min);
+
+    closest / spread.max(1e-6)
+}
+
+fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    1.0 - a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>()
+}
+
+/// Cosine k-means with deterministic farthest-point initialisation
+pub(crate) fn kmeans(points: &[Vec<f32>], k: usize) -> Vec<usize> {
+    let mut centroids = vec![points[0].clone()];
+    while centroids.len() < k {
+        let farthest = points.iter()
+            .max_by(|a, b| {
+                let da = centroids.iter().map(|c| cosine_distance(a, c)).fold(f32::MAX, f32::min);
+                let db = centroids.iter().map(|c| cosine_distance(b, c)).fold(f32::MAX, f32::min);
+                da.total_cmp(&db)
+            })
+            .expect("points is not empty");
+        centroids.push(farthest.clone());
+    }
+
+    let mut labels = vec![0; points.len()];
+    for _ in 0..KMEANS_ITERATIONS {
+        let mut changed = false;
+        for (point, label) in points.iter().zip(labels.iter_mut()) {
+            let nearest = (0..k)
+                .min_by(|a, b| cosine_distance(point, &centroids[*a]).total_cmp(&cosine_distance(point, &centroids[*b])))
+                .unwrap_or(0);
+            changed |= nearest != *label;
+            *label = nearest;
+        }
+
+        for (c, centroid) in centroids.iter_mut().enumerate() {
+            let members: Vec<&Vec<f32>> = points.iter().zip(&labels).filter(|(_, l)| **l == c).map(|(p, _)| p).collect();
+            if members.is_empty() {
+                continue;
+            }
+            for d in 0..centroid.len() {
+                centroid[d] = members.iter().map(|m| m[d]).sum();
+            }
+            let norm = centroid.iter().map(|v| v * v).sum::<f32>().sqrt().max(1e-6);
+            centroid.iter_mut().for_each(|v| *v /= norm);
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    labels
+}
+
+/// Mean silhouette of a clustering, scored on an evenly spaced sample of the points
+pub(crate) fn silhouette(points: &[Vec<f32>], labels: &[usize], k: usize) -> f32 {
+    let step = points.len().div_ceil(SILHOUETTE_SAMPLE).max(1);
+    let sample: Vec<usize> = (0..points.len()).step_by(step).collect();
+
+    let scores: Vec<f32> = sample.iter()
+        .filter_map(|&i| {
+            let mut totals = vec![(0.0f32, 0usize); k];
+            for &j in sample.iter().filter(|&&j| j != i) {
+                let entry = &mut totals[labels[j]];
+                entry.0 += cosine_distance(&points[i], &points[j]);
+                entry.1 += 1;
+            }
+            let mean = |(total, count): (f32, usize)| (count > 0).then(|| total / count as f32);
+            let own = mean(totals[labels[i]])?;
+            let other = (0..k)
+                .filter(|c| *c != labels[i])
+                .filter_map(|c| mean(totals[c]))
+                .fold(f32::MAX, f32::min);
+            (other < f32::MAX).then(|| (other - own) / own.max(other).max(1e-6))
+        })
+        .collect();
+
+    scores.iter().sum::<f32>() / scores.len().max(1) as f32
+}
+
+/// Replace each label with the most common one among its neighbours to drop one-window blips
+pub(crate) fn smooth_labels(labels: &[usize], k: usize) -> Vec<usize> {
+    (0..labels.len())
+        .map(|i| {
+            let window = &labels[i.saturating_sub(SMOOTHING_RADIUS)..(i + SMOOTHING_RADIUS + 1).min(labels.len())];
+            let mut counts = vec![0; k];
+            window.iter().for_each(|l| counts[*l] += 1);
+            // Ties keep the window's own label
+            (0..k).max_by_key(|c| (counts[*c], *c == labels[i])).unwrap_or(labels[i])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kmeans_splits_directions_and_smoothing_drops_blips() {
+        let points = vec![vec![1.0, 0.0], vec![0.9, 0.1], vec![0.0, 1.0], vec![0.1, 0.9]];
+        let labels = kmeans(&points, 2);
+        assert_eq!(labels[0], labels[1]);
+        assert_eq!(labels[2], labels[3]);
+        assert_ne!(labels[0], labels[2]);
+
+        assert_eq!(smooth_labels(&[0, 0, 0, 1, 0, 0, 0], 2), [0; 7]);
+    }
+}
@@ -0,0 +1,457 @@
+// ClipForge - Stream Copy Module
+// Lossless fast-path exports: stream-copy whole GOPs and only re-encode the partial GOP at each cut
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::get_ffprobe_path;
+use crate::export::{parse_ffmpeg_error, ClipData};
+use crate::export_progress::RunningExport;
+use crate::export_settings::{Container, ExportSettings, VideoCodec};
+
+/// Cuts within this distance of a keyframe are treated as landing on it
+const KEYFRAME_TOLERANCE: f64 = 0.01;
+
+/// Codec parameters that must match across clips for their streams to be concatenated
+#[derive(Debug, Clone, PartialEq)]
+struct SourceStreams {
+    video_codec: String,
+    profile: Option<String>,
+    level: Option<i64>,
+    width: i64,
+    height: i64,
+    pix_fmt: String,
+    audio: Option<(String, i64, i64)>, // codec, sample rate, channels
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SegmentMode {
+    Copy,
+    Encode,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Segment {
+    start: f64,
+    end: f64,
+    mode: SegmentMode,
+}
+
+/// A clip range split into re-encoded and stream-copied segments
+struct ClipSegments {
+    path: String,
+    segments: Vec<Segment>,
+}
+
+/// Everything the fast path needs to render an export
+pub struct StreamCopyPlan {
+    clips: Vec<ClipSegments>,
+    /// Encoder options for the re-encoded heads, matched to the copied stream
+    head_video_args: Vec<String>,
+    audio: Option<(String, i64, i64)>,
+    audio_bitrate_kbps: u32,
+    /// Output options for the final concat (muxer, tags, faststart)
+    mux_args: Vec<String>,
+    total_duration: f64,
+}
+
+/// Split a trimmed range at the first keyframe after its start. The head before that keyframe
+/// has to be re-encoded; everything from the keyframe on can be copied.
+fn plan_clip_segments(start: f64, end: f64, keyframes: &[f64]) -> Vec<Segment> {
+    if keyframes.iter().any(|k| (k - start).abs() <= KEYFRAME_TOLERANCE) {
+        return vec![Segment { start, end, mode: SegmentMode::Copy }];
+    }
+
+    match keyframes.iter().copied().find(|k| *k > start && *k < end - KEYFRAME_TOLERANCE) {
+        Some(keyframe) => vec![
+            Segment { start, end: keyframe, mode: SegmentMode::Encode },
+            Segment { start: keyframe, end, mode: SegmentMode::Copy },
+        ],
+        None => vec![Segment { start, end, mode: SegmentMode::Encode }],
+    }
+}
+
+/// Check that the sources can be copied into the export as-is: same streams in every clip, the
+/// export's codec, resolution and pixel format, and AAC audio (the only codec that can be both
+/// copied into and re-encoded for the MPEG-TS segments). Returns the shared stream parameters.
+fn copyable_sources(sources: &[SourceStreams], settings: &ExportSettings, target: (i32, i32)) -> Option<SourceStreams> {
+    let first = sources.first()?.clone();
+    if sources.iter().any(|s| *s != first) {
+        return None;
+    }
+
+    let codec = match first.video_codec.as_str() {
+        "h264" => VideoCodec::H264,
+        "hevc" => VideoCodec::H265,
+        _ => return None,
+    };
+    if codec != settings.codec || (first.width, first.height) != (target.0 as i64, target.1 as i64) {
+        return None;
+    }
+    if settings.pixel_format() != first.pix_fmt {
+        return None;
+    }
+    if first.audio.as_ref().is_some_and(|(codec, _, _)| codec != "aac") {
+        return None;
+    }
+
+    Some(first)
+}
+
+/// Encoder options for a re-encoded head. The export's quality settings apply, but profile and
+/// level follow the source so the head's parameter sets are compatible with the copied GOPs.
+fn head_video_args(source: &SourceStreams, settings: &ExportSettings) -> Option<Vec<String>> {
+    let profile = match (source.video_codec.as_str(), source.profile.as_deref()?) {
+        ("h264", "Baseline" | "Constrained Baseline") => "baseline",
+        ("h264", "Main") | ("hevc", "Main") => "main",
+        ("h264", "High") => "high",
+        ("hevc", "Main 10") => "main10",
+        _ => return None,
+    };
+    let level = source.level.filter(|l| *l > 0)?;
+
+    let mut args = vec!["-c:v".to_string()];
+    if source.video_codec == "h264" {
+        // H.264 levels are reported as 10x the level number
+        args.extend(["libx264".to_string(), "-profile:v".to_string(), profile.to_string()]);
+        args.extend(["-level".to_string(), format!("{}.{}", level / 10, level % 10)]);
+    } else {
+        // HEVC levels are reported as 30x the level number
+        args.extend(["libx265".to_string(), "-profile:v".to_string(), profile.to_string()]);
+        args.extend(["-x265-params".to_string(), format!("level-idc={}", level as f64 / 30.0)]);
+    }
+    args.extend(["-preset".to_string(), settings.encoder_preset.clone().unwrap_or_else(|| "fast".to_string())]);
+    args.extend(["-crf".to_string(), settings.crf.unwrap_or(23).to_string()]);
+    args.extend(["-pix_fmt".to_string(), source.pix_fmt.clone()]);
+    Some(args)
+}
+
+/// Output options for the final concat. MP4 and MOV use the avc3/hev1 sample entries, which keep
+/// parameter sets in-band, so the re-encoded heads' SPS/PPS reach the decoder at each switch.
+fn concat_mux_args(source: &SourceStreams, settings: &ExportSettings) -> Vec<String> {
+    let mut args = Vec::new();
+    if source.audio.is_some() {
+        args.extend(["-bsf:a".to_string(), "aac_adtstoasc".to_string()]);
+    }
+    if matches!(settings.container, Container::Mp4 | Container::Mov) {
+        let tag = if source.video_codec == "h264" { "avc3" } else { "hev1" };
+        args.extend(["-tag:v".to_string(), tag.to_string()]);
+    }
+    args.extend(settings.container_args());
+    args
+}
+
+/// Decide whether an export can use the fast path. Returns None when a full re-encode is required.
+/// `target` is the export resolution; the fast path never scales, so sources must already match it.
+pub fn plan_stream_copy(clips: &[ClipData], settings: &ExportSettings, target: (i32, i32)) -> Option<StreamCopyPlan> {
+    if clips.is_empty() || settings.two_pass || settings.frame_rate.is_some() {
+        return None;
+    }
+    // A copy can't meet a target bitrate
+    if settings.video_bitrate_kbps.is_some() {
+        return None;
+    }
+    // Watermarks, captions and loudness normalization are added by the full render
+    if settings.watermark.is_some() || settings.captions.is_some() || settings.loudness.is_some() {
+        return None;
+    }
+    if !matches!(settings.container, Container::Mp4 | Container::Mov | Container::Mkv) {
+        return None;
+    }
+
+    // Gain, mute, fades, audio processing, J/L cuts and transitions need the filter graph
+    if clips.iter().any(|c| {
+        c.muted || (c.volume - 100.0).abs() > f64::EPSILON || c.transition.is_some() || c.has_audio_fades()
+            || c.has_audio_processing() || c.has_split_edit()
+    }) {
+        return None;
+    }
+
+    let mut sources = Vec::new();
+    for clip in clips {
+        sources.push(probe_source_streams(&clip.path)?);
+    }
+    let source = copyable_sources(&sources, settings, target)?;
+
+    let mut clip_segments = Vec::new();
+    let mut total_duration = 0.0;
+    for clip in clips {
+        let start = clip.source_offset.unwrap_or(clip.in_point);
+        let end = start + (clip.out_point - clip.in_point);
+        let keyframes = probe_keyframes(&clip.path, start, end)?;
+
+        clip_segments.push(ClipSegments {
+            path: clip.path.clone(),
+            segments: plan_clip_segments(start, end, &keyframes),
+        });
+        total_duration += end - start;
+    }
+
+    let needs_encode = clip_segments.iter().flat_map(|c| &c.segments).any(|s| s.mode == SegmentMode::Encode);
+    let head_video_args = if needs_encode {
+        // Matroska has no in-band parameter set entry, so cuts off a keyframe need the full render
+        if settings.container == Container::Mkv {
+            return None;
+        }
+        head_video_args(&source, settings)?
+    } else {
+        Vec::new()
+    };
+
+    Some(StreamCopyPlan {
+        clips: clip_segments,
+        head_video_args,
+        mux_args: concat_mux_args(&source, settings),
+        audio: source.audio,
+        audio_bitrate_kbps: settings.audio_bitrate_kbps,
+        total_duration,
+    })
+}
+
+fn probe_source_streams(path: &str) -> Option<SourceStreams> {
+    let ffprobe_path = get_ffprobe_path().ok()?;
+    let output = Command::new(&ffprobe_path)
+        .args([
+            "-v", "error",
+            "-show_entries", "stream=codec_type,codec_name,profile,level,width,height,pix_fmt,sample_rate,channels",
+            "-of", "json",
+            path,
+        ])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let parsed: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+    let streams = parsed["streams"].as_array()?;
+
+    let video = streams.iter().find(|s| s["codec_type"] == "video")?;
+    let audio = streams.iter().find(|s| s["codec_type"] == "audio").and_then(|a| {
+        let sample_rate = a["sample_rate"].as_str().and_then(|r| r.parse::<i64>().ok())?;
+        Some((a["codec_name"].as_str()?.to_string(), sample_rate, a["channels"].as_i64()?))
+    });
+
+    Some(SourceStreams {
+        video_codec: video["codec_name"].as_str()?.to_string(),
+        profile: video["profile"].as_str().map(String::from),
+        level: video["level"].as_i64(),
+        width: video["width"].as_i64()?,
+        height: video["height"].as_i64()?,
+        pix_fmt: video["pix_fmt"].as_str()?.to_string(),
+        audio,
+    })
+}
+
+/// Keyframe timestamps of the first video stream within [start, end]
+fn probe_keyframes(path: &str, start: f64, end: f64) -> Option<Vec<f64>> {
+    let ffprobe_path = get_ffprobe_path().ok()?;
+
+    // Read packet flags only (no decoding) around the trimmed range
+    let interval = format!("{}%{}", (start - 1.0).max(0.0), end + 1.0);
+    let output = Command::new(&ffprobe_path)
+        .args([
+            "-v", "error",
+            "-select_streams", "v:0",
+            "-read_intervals", &interval,
+            "-show_entries", "packet=pts_time,flags",
+            "-of", "csv=p=0",
+            path,
+        ])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let mut keyframes: Vec<f64> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (pts, flags) = line.split_once(',')?;
+            if flags.contains('K') {
+                pts.trim().parse::<f64>().ok()
+            } else {
+                None
+            }
+        })
+        .collect();
+    keyframes.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    Some(keyframes)
+}
+
+/// Render a fast-path export: write each segment to MPEG-TS, then concatenate without re-encoding.
+/// Progress covers every segment run and the concat as one export.
+pub async fn run_stream_copy_export(
+    export: &RunningExport,
+    ffmpeg_path: &Path,
+    plan: &StreamCopyPlan,
+    output_path: &str,
+) -> Result<(), String> {
+    let segments_duration: f64 = plan.clips.iter()
+        .flat_map(|clip| &clip.segments)
+        .map(|segment| segment.end - segment.start)
+        .sum();
+    export.plan_progress(segments_duration + plan.total_duration);
+
+    let work_dir = std::env::temp_dir()
+        .join("clipforge_stream_copy")
+        .join(format!("{:x}", md5::compute(output_path.as_bytes())));
+    fs::create_dir_all(&work_dir)
+        .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+
+    let result = render_segments(export, ffmpeg_path, plan, output_path, &work_dir).await;
+
+    let _ = fs::remove_dir_all(&work_dir);
+    result
+}
+
+async fn render_segments(
+    export: &RunningExport,
+    ffmpeg_path: &Path,
+    plan: &StreamCopyPlan,
+    output_path: &str,
+    work_dir: &Path,
+) -> Result<(), String> {
+    let mut segment_files: Vec<PathBuf> = Vec::new();
+
+    for clip in &plan.clips {
+        for segment in &clip.segments {
+            let segment_path = work_dir.join(format!("segment_{}.ts", segment_files.len()));
+            let duration = segment.end - segment.start;
+
+            let mut args = vec![
+                "-ss".to_string(), segment.start.to_string(),
+                "-i".to_string(), clip.path.clone(),
+                "-t".to_string(), duration.to_string(),
+                "-map".to_string(), "0:v:0".to_string(),
+            ];
+            if plan.audio.is_some() {
+                args.extend(["-map".to_string(), "0:a:0".to_string()]);
+            }
+
+            match segment.mode {
+                SegmentMode::Copy => {
+                    args.extend(["-c", "copy", "-avoid_negative_ts", "make_zero"].map(String::from));
+                }
+                SegmentMode::Encode => {
+                    args.extend(plan.head_video_args.iter().cloned());
+                    if let Some((_, sample_rate, channels)) = &plan.audio {
+                        args.extend([
+                            "-c:a".to_string(), "aac".to_string(),
+                            "-b:a".to_string(), format!("{}k", plan.audio_bitrate_kbps),
+                            "-ar".to_string(), sample_rate.to_string(),
+                            "-ac".to_string(), channels.to_string(),
+                        ]);
+                    }
+                }
+            }
+
+            args.extend(["-f".to_string(), "mpegts".to_string(), "-y".to_string()]);
+            args.push(segment_path.to_string_lossy().to_string());
+
+            let output = export.run_ffmpeg(ffmpeg_path, &args, Some(&segment_path.to_string_lossy()), duration).await?;
+            if !output.success {
+                return Err(parse_ffmpeg_error(&output.stderr));
+            }
+
+            segment_files.push(segment_path);
+        }
+    }
+
+    // Concat demuxer list - single quotes in paths are escaped as '\''
+    let list_path = work_dir.join("segments.txt");
+    let list = segment_files
+        .iter()
+        .map(|p| format!("file '{}'", p.to_string_lossy().replace('\'', "'\\''")))
+        .collect::<Vec<_>>()
+        .join("\n");
+    fs::write(&list_path, list)
+        .map_err(|e| format!("Failed to write segment list: {}", e))?;
+
+    let mut args = vec![
+        "-f".to_string(), "concat".to_string(),
+        "-safe".to_string(), "0".to_string(),
+        "-i".to_string(), list_path.to_string_lossy().to_string(),
+        "-c".to_string(), "copy".to_string(),
+    ];
+    args.extend(plan.mux_args.iter().cloned());
+    args.extend(["-y".to_string(), output_path.to_string()]);
+
+    let output = export.run_ffmpeg(ffmpeg_path, &args, Some(output_path), plan.total_duration).await?;
+    if !output.success {
+        return Err(parse_ffmpeg_error(&output.stderr));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cut_on_keyframe_is_copied() {
+        let segments = plan_clip_segments(4.0, 10.0, &[0.0, 4.0, 8.0]);
+        assert_eq!(segments, vec![Segment { start: 4.0, end: 10.0, mode: SegmentMode::Copy }]);
+    }
+
+    #[test]
+    fn test_cut_between_keyframes_is_smart_rendered() {
+        let segments = plan_clip_segments(5.0, 10.0, &[0.0, 4.0, 8.0]);
+        assert_eq!(segments, vec![
+            Segment { start: 5.0, end: 8.0, mode: SegmentMode::Encode },
+            Segment { start: 8.0, end: 10.0, mode: SegmentMode::Copy },
+        ]);
+
+        // No keyframe before the end: the whole range is re-encoded
+        let segments = plan_clip_segments(5.0, 7.0, &[0.0, 4.0, 8.0]);
+        assert_eq!(segments, vec![Segment { start: 5.0, end: 7.0, mode: SegmentMode::Encode }]);
+    }
+
+    fn h264_source() -> SourceStreams {
+        SourceStreams {
+            video_codec: "h264".to_string(),
+            profile: Some("High".to_string()),
+            level: Some(40),
+            width: 1920,
+            height: 1080,
+            pix_fmt: "yuv420p".to_string(),
+            audio: Some(("aac".to_string(), 48000, 2)),
+        }
+    }
+
+    #[test]
+    fn test_only_matching_sources_are_copied() {
+        let settings = ExportSettings::default();
+        let source = h264_source();
+        assert!(copyable_sources(&[source.clone(), source.clone()], &settings, (1920, 1080)).is_some());
+
+        // The fast path never scales
+        assert!(copyable_sources(std::slice::from_ref(&source), &settings, (1280, 720)).is_none());
+
+        // PCM can't be copied into MPEG-TS segments
+        let pcm = SourceStreams { audio: Some(("pcm_s16le".to_string(), 48000, 2)), ..source.clone() };
+        assert!(copyable_sources(&[pcm], &settings, (1920, 1080)).is_none());
+
+        let ten_bit = SourceStreams { pix_fmt: "yuv420p10le".to_string(), ..source };
+        assert!(copyable_sources(&[ten_bit], &settings, (1920, 1080)).is_none());
+    }
+
+    #[test]
+    fn test_heads_match_source_profile_and_export_quality() {
+        let settings = ExportSettings { crf: Some(18), encoder_preset: Some("slow".to_string()), ..ExportSettings::default() };
+        let args = head_video_args(&h264_source(), &settings).unwrap();
+        assert_eq!(args, [
+            "-c:v", "libx264", "-profile:v", "high", "-level", "4.0",
+            "-preset", "slow", "-crf", "18", "-pix_fmt", "yuv420p",
+        ]);
+
+        // In-band parameter sets so the decoder picks up the heads' SPS/PPS
+        let mux = concat_mux_args(&h264_source(), &settings);
+        assert_eq!(mux, ["-bsf:a", "aac_adtstoasc", "-tag:v", "avc3", "-f", "mp4", "-movflags", "+faststart"]);
+    }
+}
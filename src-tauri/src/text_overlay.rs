@@ -0,0 +1,222 @@
+// ClipForge - Text Overlay Module
+// Compiles title, lower-third and caption layers into FFmpeg drawtext filters
+
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+
+use crate::get_fonts_dir;
+
+/// Font used when a text layer doesn't name one
+pub const DEFAULT_FONT: &str = "DejaVuSans.ttf";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TextLayer {
+    pub text: String,
+    /// File name inside the bundled fonts directory
+    pub font: Option<String>,
+    #[serde(rename = "fontSize", default = "default_font_size")]
+    pub font_size: u32,
+    #[serde(default = "default_text_color")]
+    pub color: String,
+    pub stroke: Option<TextStroke>,
+    pub shadow: Option<TextShadow>,
+    #[serde(rename = "box")]
+    pub background: Option<TextBox>,
+    /// Placement as a fraction of the free space: 0 = left/top, 0.5 = centred, 1 = right/bottom
+    #[serde(default = "default_position")]
+    pub x: f64,
+    #[serde(default = "default_position")]
+    pub y: f64,
+    #[serde(rename = "timelineStart")]
+    pub timeline_start: f64,
+    #[serde(rename = "timelineEnd")]
+    pub timeline_end: f64,
+    #[serde(rename = "fadeIn", default)]
+    pub fade_in: f64,
+    #[serde(rename = "fadeOut", default)]
+    pub fade_out: f64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TextStroke {
+    pub color: String,
+    pub width: u32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TextShadow {
+    pub color: String,
+    #[serde(rename = "offsetX")]
+    pub offset_x: i32,
+    #[serde(rename = "offsetY")]
+    pub offset_y: i32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TextBox {
+    pub color: String,
+    #[serde(default)]
+    pub padding: u32,
+}
+
+fn default_font_size() -> u32 {
+    48
+}
+
+fn default_text_color() -> String {
+    "white".to_string()
+}
+
+fn default_position() -> f64 {
+    0.5
+}
+
+/// Escape a value for a filter option inside -filter_complex. Both the option parser and the
+/// filtergraph parser treat some characters specially, so the value is escaped once for each.
+pub(crate) fn escape_filter_value(value: &str) -> String {
+    let mut option_level = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if matches!(ch, '\\' | '\'' | ':') {
+            option_level.push('\\');
+        }
+        option_level.push(ch);
+    }
+
+    let mut graph_level = String::with_capacity(option_level.len());
+    for ch in option_level.chars() {
+        if matches!(ch, '\\' | '\'' | '[' | ']' | ',' | ';') {
+            graph_level.push('\\');
+        }
+        graph_level.push(ch);
+    }
+    graph_level
+}
+
+/// Look up a font in the bundled fonts directory. Only plain file names are accepted so
+/// projects can't depend on fonts installed on one particular machine.
+pub fn resolve_font_file(font: Option<&str>) -> Result<PathBuf, String> {
+    let name = font.unwrap_or(DEFAULT_FONT);
+    if name.is_empty() || name.contains(['/', '\\']) || name == ".." {
+        return Err(format!("Invalid font name '{}'", name));
+    }
+
+    let path = get_fonts_dir()?.join(name);
+    if !path.is_file() {
+        return Err(format!("Font '{}' is not in the bundled fonts directory", name));
+    }
+
+    Ok(path)
+}
+
+impl TextLayer {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.timeline_end <= self.timeline_start {
+            return Err("Text layer must end after it starts".to_string());
+        }
+        if self.font_size == 0 {
+            return Err("Text layer font size must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// Opacity expression for the fade in/out, evaluated per frame by drawtext
+    fn alpha_expression(&self) -> Option<String> {
+        let visible = self.timeline_end - self.timeline_start;
+        let fade_in = self.fade_in.clamp(0.0, visible);
+        let fade_out = self.fade_out.clamp(0.0, visible - fade_in);
+        if fade_in <= 0.0 && fade_out <= 0.0 {
+            return None;
+        }
+
+        let fade_in_expr = if fade_in > 0.0 {
+            format!("if(lt(t,{}),(t-{})/{},", self.timeline_start + fade_in, self.timeline_start, fade_in)
+        } else {
+            "if(0,0,".to_string()
+        };
+        let fade_out_expr = if fade_out > 0.0 {
+            format!("if(gt(t,{}),({}-t)/{},1)", self.timeline_end - fade_out, self.timeline_end, fade_out)
+        } else {
+            "1".to_string()
+        };
+
+        Some(format!("{}{})", fade_in_expr, fade_out_expr))
+    }
+
+    /// Build the drawtext filter (without input/output labels) for this layer
+    pub fn drawtext_filter(&self) -> Result<String, String> {
+        self.validate()?;
+        let font_file = resolve_font_file(self.font.as_deref())?;
+
+        let mut options = vec![
+            format!("fontfile={}", escape_filter_value(&font_file.to_string_lossy())),
+            format!("text={}", escape_filter_value(&self.text)),
+            // Render text literally; '%' would otherwise start an expansion sequence
+            "expansion=none".to_string(),
+            format!("fontsize={}", self.font_size),
+            format!("fontcolor={}", escape_filter_value(&self.color)),
+            format!("x=(w-text_w)*{}", self.x.clamp(0.0, 1.0)),
+            format!("y=(h-text_h)*{}", self.y.clamp(0.0, 1.0)),
+        ];
+
+        if let Some(stroke) = &self.stroke {
+            options.push(format!("borderw={}", stroke.width));
+            options.push(format!("bordercolor={}", escape_filter_value(&stroke.color)));
+        }
+        if let Some(shadow) = &self.shadow {
+            options.push(format!("shadowcolor={}", escape_filter_value(&shadow.color)));
+            options.push(format!("shadowx={}", shadow.offset_x));
+            options.push(format!("shadowy={}", shadow.offset_y));
+        }
+        if let Some(background) = &self.background {
+            options.push("box=1".to_string());
+            options.push(format!("boxcolor={}", escape_filter_value(&background.color)));
+            options.push(format!("boxborderw={}", background.padding));
+        }
+        if let Some(alpha) = self.alpha_expression() {
+            options.push(format!("alpha={}", escape_filter_value(&alpha)));
+        }
+
+        options.push(escape_filter_value(&format!(
+            "enable=between(t,{},{})",
+            self.timeline_start, self.timeline_end
+        )));
+
+        Ok(format!("drawtext={}", options.join(":")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_filter_value() {
+        // Example from the FFmpeg filtergraph escaping documentation
+        assert_eq!(
+            escape_filter_value("this is a 'string': may contain one, or more, special characters"),
+            "this is a \\\\\\'string\\\\\\'\\\\: may contain one\\, or more\\, special characters"
+        );
+    }
+
+    #[test]
+    fn test_default_font_is_bundled() {
+        assert!(resolve_font_file(None).unwrap().ends_with(DEFAULT_FONT));
+        assert!(resolve_font_file(Some("../Arial.ttf")).is_err());
+    }
+
+    #[test]
+    fn test_fade_alpha_expression() {
+        let layer: TextLayer = serde_json::from_value(serde_json::json!({
+            "text": "Title",
+            "timelineStart": 2.0,
+            "timelineEnd": 6.0,
+            "fadeIn": 1.0,
+            "fadeOut": 0.5
+        })).unwrap();
+
+        assert_eq!(
+            layer.alpha_expression().unwrap(),
+            "if(lt(t,3),(t-2)/1,if(gt(t,5.5),(6-t)/0.5,1))"
+        );
+    }
+}
@@ -0,0 +1,482 @@
+// ClipForge - Timeline Export Module
+// Compiles N-track timelines (z-ordered layers with transforms and blend modes) into FFmpeg overlay graphs
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::get_ffmpeg_path;
+use crate::audio_tracks::{push_audio_clip, AudioClip};
+use crate::export::{build_clip_audio_filter, has_audio_stream, parse_ffmpeg_error, resolve_export_resolution, AUDIO_SAMPLE_RATE};
+use crate::export_plan::{apply_export_overlays, finish_output_args, run_export_plan, ExportPlan};
+use crate::export_progress::RunningExport;
+use crate::export_settings::ExportSettings;
+use crate::image_overlay::{compile_image_layer, ImageLayer};
+use crate::loudness::{apply_mix_loudness, loudness_pass_args, normalize_clip_loudness, plan_with_loudness, timeline_clip_audio};
+use crate::text_overlay::TextLayer;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TimelineData {
+    pub tracks: Vec<TimelineTrack>,
+    /// Explicit export length in seconds; defaults to the end of the last clip
+    pub duration: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TimelineTrack {
+    pub id: Option<String>,
+    /// Tracks with a higher z-index are composited on top
+    #[serde(rename = "zIndex", default)]
+    pub z_index: i32,
+    #[serde(default)]
+    pub muted: bool,
+    pub clips: Vec<TimelineClip>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum TimelineClip {
+    #[serde(rename = "video")]
+    Video(VideoLayer),
+    #[serde(rename = "image")]
+    Image(ImageLayer),
+    #[serde(rename = "text")]
+    Text(TextLayer),
+    /// Voice-over or detached audio; contributes sound only
+    #[serde(rename = "audio")]
+    Audio(AudioClip),
+}
+
+impl TimelineClip {
+    /// Time on the export timeline where this clip ends
+    fn timeline_end(&self) -> f64 {
+        match self {
+            TimelineClip::Video(layer) => layer.timeline_start.max(0.0) + layer.trim_duration(),
+            TimelineClip::Image(layer) => layer.timeline_end,
+            TimelineClip::Text(layer) => layer.timeline_end,
+            TimelineClip::Audio(clip) => clip.timeline_end(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct VideoLayer {
+    pub path: String,
+    #[serde(rename = "timelineStart")]
+    pub timeline_start: f64,
+    #[serde(rename = "inPoint")]
+    pub in_point: f64,
+    #[serde(rename = "outPoint")]
+    pub out_point: f64,
+    #[serde(rename = "sourceOffset")]
+    pub source_offset: Option<f64>,
+    #[serde(default = "default_volume")]
+    pub volume: f64,
+    #[serde(default)]
+    pub muted: bool,
+    /// Position and size as fractions of the export frame; None fits the clip to the whole frame
+    pub transform: Option<LayerTransform>,
+    #[serde(rename = "blendMode", default)]
+    pub blend_mode: BlendMode,
+    #[serde(default = "default_opacity")]
+    pub opacity: f64,
+}
+
+impl VideoLayer {
+    fn trim_duration(&self) -> f64 {
+        (self.out_point - self.in_point).max(0.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct LayerTransform {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    Add,
+    Difference,
+}
+
+impl BlendMode {
+    /// Mode name for FFmpeg's blend filter; Normal uses plain alpha overlay instead
+    fn blend_filter_mode(&self) -> Option<&'static str> {
+        match self {
+            BlendMode::Normal => None,
+            BlendMode::Multiply => Some("multiply"),
+            BlendMode::Screen => Some("screen"),
+            BlendMode::Overlay => Some("overlay"),
+            BlendMode::Darken => Some("darken"),
+            BlendMode::Lighten => Some("lighten"),
+            BlendMode::Add => Some("addition"),
+            BlendMode::Difference => Some("difference"),
+        }
+    }
+}
+
+fn default_volume() -> f64 {
+    100.0
+}
+
+fn default_opacity() -> f64 {
+    1.0
+}
+
+/// Where a layer sits on the canvas, in pixels
+struct LayerGeometry {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    /// Scale (and pad or crop) filter producing a frame of exactly width x height
+    scale_filter: String,
+}
+
+/// The on-canvas part of a layer along one axis: (canvas position, visible length, offset into the layer)
+fn visible_span(position: i32, size: i32, canvas: i32) -> Option<(i32, i32, i32)> {
+    let start = position.clamp(0, canvas);
+    let length = ((position + size).min(canvas) - start) & !1;
+    (length >= 2).then_some((start, length, start - position))
+}
+
+/// Layer placement on the canvas, or None when the layer lies entirely off it. Layers are scaled
+/// to their requested size and any part hanging off the canvas is cropped away, so blend modes
+/// can crop the same area underneath.
+fn layer_geometry(transform: Option<&LayerTransform>, canvas_width: i32, canvas_height: i32) -> Option<LayerGeometry> {
+    let Some(t) = transform else {
+        return Some(LayerGeometry {
+            x: 0,
+            y: 0,
+            width: canvas_width,
+            height: canvas_height,
+            scale_filter: format!(
+                "scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2",
+                w = canvas_width,
+                h = canvas_height
+            ),
+        });
+    };
+
+    let scaled_width = (((canvas_width as f64 * t.width) as i32) & !1).max(2);
+    let scaled_height = (((canvas_height as f64 * t.height) as i32) & !1).max(2);
+    let (x, width, crop_x) = visible_span((canvas_width as f64 * t.x) as i32, scaled_width, canvas_width)?;
+    let (y, height, crop_y) = visible_span((canvas_height as f64 * t.y) as i32, scaled_height, canvas_height)?;
+
+    let mut scale_filter = format!("scale={}:{}", scaled_width, scaled_height);
+    if (width, height) != (scaled_width, scaled_height) {
+        scale_filter.push_str(&format!(",crop={}:{}:{}:{}", width, height, crop_x, crop_y));
+    }
+
+    Some(LayerGeometry { x, y, width, height, scale_filter })
+}
+
+/// Composite one video layer over `current` and return the label of the result
+#[allow(clippy::too_many_arguments)]
+fn compile_video_layer(
+    filter_parts: &mut Vec<String>,
+    layer: &VideoLayer,
+    input_index: usize,
+    label: &str,
+    current: &str,
+    canvas_width: i32,
+    canvas_height: i32,
+    timeline_duration: f64,
+) -> String {
+    let trim_start = layer.source_offset.unwrap_or(layer.in_point);
+    let trim_duration = layer.trim_duration();
+    let start = layer.timeline_start.max(0.0);
+    let end = start + trim_duration;
+    let opacity = layer.opacity.clamp(0.0, 1.0);
+    let Some(geometry) = layer_geometry(layer.transform.as_ref(), canvas_width, canvas_height) else {
+        return current.to_string();
+    };
+    let output = format!("[{}_out]", label);
+
+    match layer.blend_mode.blend_filter_mode() {
+        None => {
+            let alpha_filter = if opacity < 1.0 {
+                format!(",format=rgba,colorchannelmixer=aa={}", opacity)
+            } else {
+                String::new()
+            };
+
+            // Shift timestamps to the layer's timeline start so overlay consumes frames from there
+            filter_parts.push(format!("[{}:v]trim=start={}:duration={},setpts=PTS-STARTPTS+{}/TB,{}{}[{}_layer]",
+                input_index, trim_start, trim_duration, start, geometry.scale_filter, alpha_filter, label));
+            filter_parts.push(format!("{}[{}_layer]overlay=x={}:y={}:eof_action=pass:enable='between(t,{},{})'{}",
+                current, label, geometry.x, geometry.y, start, end, output));
+        }
+        Some(mode) => {
+            // blend needs two equal-sized inputs from t=0: pad the layer in time to cover the
+            // whole timeline, blend it with the area underneath, then overlay the result back
+            filter_parts.push(format!("[{}:v]trim=start={}:duration={},setpts=PTS-STARTPTS,{},format=gbrp,tpad=start_duration={}:stop_mode=clone:stop_duration={}[{}_layer]",
+                input_index, trim_start, trim_duration, geometry.scale_filter, start, (timeline_duration - end).max(0.0), label));
+            filter_parts.push(format!("{}split[{l}_base][{l}_under_src]", current, l = label));
+            filter_parts.push(format!("[{l}_under_src]crop={}:{}:{}:{},format=gbrp[{l}_under]",
+                geometry.width, geometry.height, geometry.x, geometry.y, l = label));
+            filter_parts.push(format!("[{l}_under][{l}_layer]blend=all_mode={}:all_opacity={}:enable='between(t,{},{})'[{l}_blended]",
+                mode, opacity, start, end, l = label));
+            filter_parts.push(format!("[{l}_base][{l}_blended]overlay=x={}:y={}:enable='between(t,{},{})'{}",
+                geometry.x, geometry.y, start, end, output, l = label));
+        }
+    }
+
+    output
+}
+
+/// Build the FFmpeg arguments for an N-track timeline export
+pub fn build_timeline_plan(
+    timeline: &TimelineData,
+    output_path: &str,
+    width: Option<i32>,
+    height: Option<i32>,
+    settings: &ExportSettings,
+) -> Result<ExportPlan, String> {
+    let (export_width, export_height) = resolve_export_resolution(width, height)?;
+
+    // Lowest z-index is composited first; the stable sort keeps payload order for equal z
+    let mut tracks: Vec<&TimelineTrack> = timeline.tracks.iter().collect();
+    tracks.sort_by_key(|t| t.z_index);
+
+    let content_end = tracks.iter()
+        .flat_map(|t| t.clips.iter())
+        .map(|c| c.timeline_end())
+        .fold(0.0, f64::max);
+    let duration = timeline.duration.unwrap_or(content_end);
+    if duration <= 0.0 {
+        return Err("No clips to export".to_string());
+    }
+
+    let frame_rate = settings.frame_rate.unwrap_or(30.0);
+
+    let mut cmd_args = Vec::new();
+    let mut filter_parts = Vec::new();
+
+    // Black canvas and silent bed covering the whole timeline
+    filter_parts.push(format!("color=black:size={}x{}:duration={}:rate={}[base]",
+        export_width, export_height, duration, frame_rate));
+    filter_parts.push(format!("anullsrc=channel_layout=stereo:sample_rate={},atrim=duration={}[base_audio]",
+        AUDIO_SAMPLE_RATE, duration));
+
+    let mut current_output = "[base]".to_string();
+    let mut audio_mix_inputs = vec!["[base_audio]".to_string()];
+    let mut input_index = 0;
+
+    for (t, track) in tracks.iter().enumerate() {
+        for (c, clip) in track.clips.iter().enumerate() {
+            let label = format!("t{}c{}", t, c);
+
+            match clip {
+                TimelineClip::Video(layer) => {
+                    cmd_args.push("-i".to_string());
+                    cmd_args.push(layer.path.clone());
+
+                    current_output = compile_video_layer(
+                        &mut filter_parts,
+                        layer,
+                        input_index,
+                        &label,
+                        &current_output,
+                        export_width,
+                        export_height,
+                        duration,
+                    );
+
+                    if !track.muted {
+                        let trim_start = layer.source_offset.unwrap_or(layer.in_point);
+                        filter_parts.push(build_clip_audio_filter(
+                            input_index,
+                            has_audio_stream(&layer.path),
+                            trim_start,
+                            layer.trim_duration(),
+                            layer.volume,
+                            layer.muted,
+                            &format!("[{}_atrim]", label),
+                        ));
+                        let delay_ms = (layer.timeline_start.max(0.0) * 1000.0).round() as i64;
+                        filter_parts.push(format!("[{l}_atrim]adelay=delays={}:all=1[{l}_audio]", delay_ms, l = label));
+                        audio_mix_inputs.push(format!("[{}_audio]", label));
+                    }
+
+                    input_index += 1;
+                }
+                TimelineClip::Image(layer) => {
+                    current_output = compile_image_layer(
+                        &mut cmd_args,
+                        &mut filter_parts,
+                        layer,
+                        input_index,
+                        &label,
+                        &current_output,
+                        export_width,
+                        settings.frame_rate,
+                    )?;
+                    input_index += 1;
+                }
+                TimelineClip::Text(layer) => {
+                    filter_parts.push(format!("{}{}[{}_out]", current_output, layer.drawtext_filter()?, label));
+                    current_output = format!("[{}_out]", label);
+                }
+                TimelineClip::Audio(audio) if !track.muted => {
+                    cmd_args.push("-i".to_string());
+                    cmd_args.push(audio.path.clone());
+                    audio_mix_inputs.push(push_audio_clip(&mut filter_parts, audio, input_index, &label)?);
+                    input_index += 1;
+                }
+                TimelineClip::Audio(_) => {}
+            }
+        }
+    }
+
+    // Mix every track's audio over the silent bed (duration=first keeps the timeline length)
+    let audio_output = if audio_mix_inputs.len() > 1 {
+        filter_parts.push(format!("{}amix=inputs={}:duration=first:normalize=0[outa]",
+            audio_mix_inputs.join(""), audio_mix_inputs.len()));
+        "[outa]".to_string()
+    } else {
+        "[base_audio]".to_string()
+    };
+
+    let (video_output, caption_maps) = apply_export_overlays(&mut cmd_args, &mut filter_parts, settings, input_index, &current_output, export_width, duration)?;
+    let audio_output = apply_mix_loudness(&mut filter_parts, audio_output, settings.loudness.as_ref());
+
+    cmd_args.push("-filter_complex".to_string());
+    cmd_args.push(filter_parts.join(";"));
+    cmd_args.push("-map".to_string());
+    cmd_args.push(video_output);
+    cmd_args.push("-map".to_string());
+    cmd_args.push(audio_output);
+    cmd_args.extend(caption_maps);
+    let loudness_pass_args = loudness_pass_args(&cmd_args, settings.loudness.as_ref());
+    let (args, first_pass_args) = finish_output_args(cmd_args, settings, output_path)?;
+
+    Ok(ExportPlan {
+        args,
+        first_pass_args,
+        loudness_pass_args,
+        total_duration: duration,
+        width: export_width,
+        height: export_height,
+    })
+}
+
+#[tauri::command]
+pub async fn export_timeline(
+    app: AppHandle,
+    mut timeline: TimelineData,
+    output_path: String,
+    width: Option<i32>,
+    height: Option<i32>,
+    export_id: Option<String>,
+    settings: Option<ExportSettings>,
+) -> Result<String, String> {
+    let ffmpeg_path = get_ffmpeg_path()?;
+    let settings = settings.unwrap_or_default();
+    let export_id = export_id.unwrap_or_else(|| output_path.clone());
+    let export = RunningExport::start(&app, &export_id)?;
+
+    normalize_clip_loudness(&export, timeline_clip_audio(&mut timeline), settings.loudness.as_ref()).await?;
+    let plan = plan_with_loudness(&export, &ffmpeg_path, &settings, |s| {
+        build_timeline_plan(&timeline, &output_path, width, height, s)
+    }).await?;
+
+    let output = run_export_plan(&export, &ffmpeg_path, &plan, &output_path).await?;
+
+    if !output.success {
+        return Err(parse_ffmpeg_error(&output.stderr));
+    }
+
+    Ok("Timeline export completed successfully".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layer_geometry_crops_off_canvas_part() {
+        // Hangs off the bottom-right corner: full-size scale, keep the top-left 128x72
+        let transform = LayerTransform { x: 0.9, y: 0.9, width: 0.25, height: 0.25 };
+        let geometry = layer_geometry(Some(&transform), 1280, 720).unwrap();
+        assert_eq!((geometry.x, geometry.y, geometry.width, geometry.height), (1152, 648, 128, 72));
+        assert_eq!(geometry.scale_filter, "scale=320:180,crop=128:72:0:0");
+
+        // Hangs off the left edge: the visible part is the layer's right side
+        let transform = LayerTransform { x: -0.1, y: 0.0, width: 0.25, height: 0.25 };
+        let geometry = layer_geometry(Some(&transform), 1280, 720).unwrap();
+        assert_eq!((geometry.x, geometry.width), (0, 192));
+        assert_eq!(geometry.scale_filter, "scale=320:180,crop=192:180:128:0");
+
+        let transform = LayerTransform { x: 1.2, y: 0.0, width: 0.25, height: 0.25 };
+        assert!(layer_geometry(Some(&transform), 1280, 720).is_none());
+    }
+
+    #[test]
+    fn test_timeline_deserializes_layers() {
+        let timeline: TimelineData = serde_json::from_value(serde_json::json!({
+            "tracks": [{
+                "zIndex": 1,
+                "clips": [{
+                    "type": "video",
+                    "path": "/tmp/broll.mp4",
+                    "timelineStart": 2.0,
+                    "inPoint": 0.0,
+                    "outPoint": 3.0,
+                    "blendMode": "screen"
+                }]
+            }]
+        })).unwrap();
+
+        let TimelineClip::Video(layer) = &timeline.tracks[0].clips[0] else {
+            panic!("expected a video layer");
+        };
+        assert_eq!(layer.blend_mode, BlendMode::Screen);
+        assert_eq!(layer.volume, 100.0);
+        assert_eq!(timeline.tracks[0].clips[0].timeline_end(), 5.0);
+    }
+
+    #[test]
+    fn test_plan_composites_tracks_by_z_index() {
+        // Payload order differs from z order; the middle track uses a blend mode
+        let timeline: TimelineData = serde_json::from_value(serde_json::json!({
+            "tracks": [
+                { "zIndex": 2, "clips": [{ "type": "video", "path": "top.mp4", "timelineStart": 2.0, "inPoint": 0.0, "outPoint": 1.0,
+                    "transform": { "x": 0.5, "y": 0.5, "width": 0.25, "height": 0.25 } }] },
+                { "zIndex": 0, "clips": [{ "type": "video", "path": "base.mp4", "timelineStart": 0.0, "inPoint": 0.0, "outPoint": 6.0 }] },
+                { "zIndex": 1, "clips": [{ "type": "video", "path": "blend.mp4", "timelineStart": 1.0, "inPoint": 0.0, "outPoint": 3.0,
+                    "transform": { "x": 0.0, "y": 0.0, "width": 0.5, "height": 0.5 }, "blendMode": "multiply", "opacity": 0.5 }] }
+            ]
+        })).unwrap();
+
+        let plan = build_timeline_plan(&timeline, "out.mp4", Some(1280), Some(720), &ExportSettings::default()).unwrap();
+        let inputs: Vec<&str> = plan.args.windows(2).filter(|w| w[0] == "-i").map(|w| w[1].as_str()).collect();
+        assert_eq!(inputs, ["base.mp4", "blend.mp4", "top.mp4"]);
+
+        // Bottom to top: base over the canvas, the multiply layer blended with the base's
+        // top-left quarter, then the top layer over the blended result
+        let graph = &plan.args[plan.args.iter().position(|a| a == "-filter_complex").unwrap() + 1];
+        let steps = [
+            "[base][t0c0_layer]overlay=x=0:y=0:eof_action=pass:enable='between(t,0,6)'[t0c0_out]",
+            "[t0c0_out]split[t1c0_base][t1c0_under_src]",
+            "[t1c0_under_src]crop=640:360:0:0,format=gbrp[t1c0_under]",
+            "[t1c0_under][t1c0_layer]blend=all_mode=multiply:all_opacity=0.5:enable='between(t,1,4)'[t1c0_blended]",
+            "[t1c0_base][t1c0_blended]overlay=x=0:y=0:enable='between(t,1,4)'[t1c0_out]",
+            "[t2c0_out]",
+        ];
+        let positions: Vec<usize> = steps.iter().map(|step| graph.find(step).unwrap_or_else(|| panic!("missing {}", step))).collect();
+        assert!(positions.windows(2).all(|p| p[0] < p[1]));
+        assert!(graph.contains("[t1c0_out][t2c0_layer]overlay=x=640:y=360:eof_action=pass:enable='between(t,2,3)'[t2c0_out]"));
+        assert!(graph.contains("[1:v]trim=start=0:duration=3,setpts=PTS-STARTPTS,scale=640:360,format=gbrp,tpad=start_duration=1:stop_mode=clone:stop_duration=2[t1c0_layer]"));
+    }
+}
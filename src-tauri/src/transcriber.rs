@@ -0,0 +1,387 @@
+// ClipForge - Transcriber Module
+// Pluggable speech-to-text backends: OpenAI, OpenAI-compatible servers and an offline whisper.cpp engine
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::chunked_transcription::ChunkFormat;
+use crate::diarization::DiarizationSettings;
+use crate::filler_detection::{validate_api_key, WhisperResponse};
+
+const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_MODEL: &str = "whisper-1";
+/// OpenAI rejects uploads over 25 MB
+const OPENAI_MAX_UPLOAD_BYTES: u64 = 25 * 1024 * 1024;
+
+/// ggml model names published for whisper.cpp
+const WHISPER_CPP_MODELS: &[&str] = &[
+    "tiny", "tiny.en", "base", "base.en", "small", "small.en",
+    "medium", "medium.en", "large-v3", "large-v3-turbo",
+];
+
+/// A speech-to-text engine producing Whisper-style transcripts with word timings
+#[async_trait]
+pub trait Transcriber: Send + Sync {
+    /// Transcribe a 16kHz mono audio file (WAV, or Ogg/MP3 chunks for remote backends)
+    async fn transcribe(&self, audio_path: &str) -> Result<WhisperResponse, String>;
+
+    /// Largest file the backend accepts in one request; longer audio is split into chunks
+    fn max_upload_bytes(&self) -> Option<u64> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum TranscriptionBackend {
+    #[default]
+    #[serde(rename = "openai")]
+    OpenAi,
+    /// Any server implementing the OpenAI transcription endpoint
+    #[serde(rename = "openaiCompatible")]
+    OpenAiCompatible,
+    /// whisper.cpp running on this machine
+    #[serde(rename = "local")]
+    Local,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TranscriptionSettings {
+    pub backend: TranscriptionBackend,
+    /// Base URL for OpenAI-compatible servers, e.g. http://localhost:8080/v1
+    #[serde(rename = "baseUrl")]
+    pub base_url: Option<String>,
+    /// Model name sent to the API
+    pub model: Option<String>,
+    /// ggml model file for the local engine
+    #[serde(rename = "modelPath")]
+    pub model_path: Option<String>,
+    /// Spoken language (ISO 639-1); None lets the engine detect it
+    pub language: Option<String>,
+    /// Upload format for chunks of long recordings
+    #[serde(rename = "chunkFormat")]
+    pub chunk_format: ChunkFormat,
+    pub diarization: DiarizationSettings,
+}
+
+impl TranscriptionSettings {
+    pub fn load() -> Self {
+        fs::read_to_string(settings_path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let path = settings_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create settings directory: {}", e))?;
+        }
+
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize transcription settings: {}", e))?;
+        fs::write(&path, json)
+            .map_err(|e| format!("Failed to save transcription settings: {}", e))
+    }
+
+    /// Identifies the engine and model that produce a transcript, so cached transcripts from a
+    /// different backend or model aren't reused
+    pub fn cache_key(&self) -> String {
+        let model = match self.backend {
+            TranscriptionBackend::OpenAi => self.model.clone().unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+            TranscriptionBackend::OpenAiCompatible => format!("{}|{}",
+                self.base_url.as_deref().unwrap_or_default().trim().trim_end_matches('/'),
+                self.model.as_deref().unwrap_or(DEFAULT_MODEL)),
+            TranscriptionBackend::Local => self.model_path.clone().unwrap_or_default(),
+        };
+        format!("{:?}|{}|{}", self.backend, model, self.language.as_deref().unwrap_or("auto"))
+    }
+}
+
+pub(crate) fn app_data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("ClipForge")
+}
+
+fn settings_path() -> PathBuf {
+    app_data_dir().join("transcription_settings.json")
+}
+
+/// Build the transcriber selected in settings. The API key is only needed for remote backends.
+pub fn create_transcriber(
+    settings: &TranscriptionSettings,
+    api_key: Option<&str>,
+) -> Result<Box<dyn Transcriber>, String> {
+    match settings.backend {
+        TranscriptionBackend::OpenAi | TranscriptionBackend::OpenAiCompatible => {
+            Ok(Box::new(create_openai_transcriber(settings, api_key)?))
+        }
+        TranscriptionBackend::Local => create_local_transcriber(settings),
+    }
+}
+
+fn create_openai_transcriber(settings: &TranscriptionSettings, api_key: Option<&str>) -> Result<OpenAiTranscriber, String> {
+    let model = settings.model.clone().unwrap_or_else(|| DEFAULT_MODEL.to_string());
+
+    if settings.backend == TranscriptionBackend::OpenAi {
+        let api_key = api_key.unwrap_or_default();
+        validate_api_key(api_key)?;
+        return Ok(OpenAiTranscriber {
+            base_url: OPENAI_BASE_URL.to_string(),
+            api_key: Some(api_key.to_string()),
+            model,
+            language: settings.language.clone(),
+            service_name: "OpenAI API",
+        });
+    }
+
+    let base_url = settings.base_url.as_deref()
+        .map(|url| url.trim().trim_end_matches('/'))
+        .filter(|url| !url.is_empty())
+        .ok_or("A base URL is required for OpenAI-compatible transcription servers")?;
+    let valid = reqwest::Url::parse(base_url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+    if !valid {
+        return Err(format!("Invalid transcription server URL '{}'. Use an http:// or https:// address.", base_url));
+    }
+
+    Ok(OpenAiTranscriber {
+        base_url: base_url.to_string(),
+        api_key: api_key.filter(|k| !k.trim().is_empty()).map(String::from),
+        model,
+        language: settings.language.clone(),
+        service_name: "transcription server",
+    })
+}
+
+#[cfg(feature = "local-whisper")]
+fn create_local_transcriber(settings: &TranscriptionSettings) -> Result<Box<dyn Transcriber>, String> {
+    let model_path = settings.model_path.clone()
+        .ok_or("Choose a Whisper model file for offline transcription")?;
+    if !Path::new(&model_path).is_file() {
+        return Err(format!("Whisper model not found: {}", model_path));
+    }
+
+    Ok(Box::new(crate::local_whisper::LocalWhisperTranscriber {
+        model_path,
+        language: settings.language.clone(),
+    }))
+}
+
+#[cfg(not(feature = "local-whisper"))]
+fn create_local_transcriber(_settings: &TranscriptionSettings) -> Result<Box<dyn Transcriber>, String> {
+    Err("This build of ClipForge does not include the offline transcription engine".to_string())
+}
+
+/// Client for the OpenAI `/audio/transcriptions` endpoint and servers that mimic it
+pub struct OpenAiTranscriber {
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+    language: Option<String>,
+    /// Used in error messages
+    service_name: &'static str,
+}
+
+impl OpenAiTranscriber {
+    fn transcriptions_url(&self) -> String {
+        format!("{}/audio/transcriptions", self.base_url)
+    }
+}
+
+#[async_trait]
+impl Transcriber for OpenAiTranscriber {
+    async fn transcribe(&self, audio_path: &str) -> Result<WhisperResponse, String> {
+        let audio_data = fs::read(audio_path)
+            .map_err(|e| format!("Failed to read audio file: {}", e))?;
+
+        let filename = Path::new(audio_path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("audio.wav");
+        let mime_type = match Path::new(audio_path).extension().and_then(|e| e.to_str()) {
+            Some("ogg") => "audio/ogg",
+            Some("mp3") => "audio/mpeg",
+            _ => "audio/wav",
+        };
+
+        // Word-level timestamps need verbose_json plus timestamp_granularities[]=word
+        let mut form = reqwest::multipart::Form::new()
+            .part(
+                "file",
+                reqwest::multipart::Part::bytes(audio_data)
+                    .file_name(filename.to_string())
+                    .mime_str(mime_type)
+                    .map_err(|e| format!("Failed to set mime type: {}", e))?,
+            )
+            .text("model", self.model.clone())
+            .text("response_format", "verbose_json")
+            .text("timestamp_granularities[]", "word");
+        if let Some(language) = &self.language {
+            form = form.text("language", language.clone());
+        }
+
+        let client = reqwest::Client::new();
+        let mut request = client
+            .post(self.transcriptions_url())
+            .multipart(form);
+        if let Some(api_key) = &self.api_key {
+            request = request.header("Authorization", format!("Bearer {}", api_key));
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| {
+                if e.is_timeout() {
+                    format!("Request to {} timed out", self.service_name)
+                } else if e.is_connect() {
+                    format!("Failed to connect to {}. Check your internet connection.", self.service_name)
+                } else {
+                    format!("Network error: {}", e)
+                }
+            })?;
+
+        let status = response.status();
+
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+
+            if status == 401 {
+                return Err(format!("Invalid API key for {}. Please check your API key.", self.service_name));
+            } else if status == 429 {
+                return Err(format!("{} rate limit exceeded. Please try again later.", self.service_name));
+            } else {
+                return Err(format!("{} error ({}): {}", self.service_name, status, error_text));
+            }
+        }
+
+        let response_text = response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read API response: {}", e))?;
+
+        serde_json::from_str(&response_text)
+            .map_err(|e| format!("Failed to parse API response: {}. Response: {}", e,
+                response_text.chars().take(200).collect::<String>()))
+    }
+
+    fn max_upload_bytes(&self) -> Option<u64> {
+        Some(OPENAI_MAX_UPLOAD_BYTES)
+    }
+}
+
+#[tauri::command]
+pub async fn get_transcription_settings() -> Result<TranscriptionSettings, String> {
+    Ok(TranscriptionSettings::load())
+}
+
+#[tauri::command]
+pub async fn set_transcription_settings(settings: TranscriptionSettings) -> Result<(), String> {
+    settings.save()
+}
+
+/// Download a whisper.cpp model into the app data directory and return its path
+#[tauri::command]
+pub async fn download_whisper_model(model: String) -> Result<String, String> {
+    if !WHISPER_CPP_MODELS.contains(&model.as_str()) {
+        return Err(format!("Unknown Whisper model '{}'", model));
+    }
+
+    let models_dir = app_data_dir().join("models");
+    fs::create_dir_all(&models_dir)
+        .map_err(|e| format!("Failed to create models directory: {}", e))?;
+    let model_path = models_dir.join(format!("ggml-{}.bin", model));
+    if model_path.is_file() {
+        return Ok(model_path.to_string_lossy().to_string());
+    }
+
+    let url = format!("https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-{}.bin", model);
+    let mut response = reqwest::get(&url)
+        .await
+        .map_err(|e| format!("Failed to download Whisper model: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Failed to download Whisper model ({})", response.status()));
+    }
+
+    // Write to a partial file so an interrupted download is never mistaken for a model
+    let partial_path = model_path.with_extension("part");
+    let mut file = tokio::fs::File::create(&partial_path)
+        .await
+        .map_err(|e| format!("Failed to save Whisper model: {}", e))?;
+    while let Some(chunk) = response.chunk()
+        .await
+        .map_err(|e| format!("Failed to download Whisper model: {}", e))?
+    {
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| format!("Failed to save Whisper model: {}", e))?;
+    }
+    file.flush()
+        .await
+        .map_err(|e| format!("Failed to save Whisper model: {}", e))?;
+    fs::rename(&partial_path, &model_path)
+        .map_err(|e| format!("Failed to save Whisper model: {}", e))?;
+
+    Ok(model_path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(backend: TranscriptionBackend) -> TranscriptionSettings {
+        TranscriptionSettings { backend, ..TranscriptionSettings::default() }
+    }
+
+    #[test]
+    fn test_create_transcriber_validates_backend_settings() {
+        let openai = settings(TranscriptionBackend::OpenAi);
+        assert_eq!(create_transcriber(&openai, None).err().unwrap(), "API key is required");
+        assert!(create_transcriber(&openai, Some("not-a-key")).is_err());
+        let transcriber = create_openai_transcriber(&openai, Some("sk-test")).unwrap();
+        assert_eq!(transcriber.transcriptions_url(), "https://api.openai.com/v1/audio/transcriptions");
+        assert_eq!(transcriber.model, "whisper-1");
+
+        // Compatible servers need a usable http(s) base URL but no key
+        let mut compatible = settings(TranscriptionBackend::OpenAiCompatible);
+        assert!(create_transcriber(&compatible, None).is_err());
+        for bad in ["localhost:8080/v1", "ftp://example.com/v1", "   "] {
+            compatible.base_url = Some(bad.to_string());
+            assert!(create_transcriber(&compatible, None).is_err(), "accepted {:?}", bad);
+        }
+        compatible.base_url = Some(" http://localhost:8080/v1/ ".to_string());
+        compatible.model = Some("large-v3".to_string());
+        let transcriber = create_openai_transcriber(&compatible, Some("  ")).unwrap();
+        assert_eq!(transcriber.transcriptions_url(), "http://localhost:8080/v1/audio/transcriptions");
+        assert_eq!(transcriber.api_key, None);
+        assert_eq!(transcriber.model, "large-v3");
+
+        // The offline engine needs a model file that exists
+        let mut local = settings(TranscriptionBackend::Local);
+        assert!(create_transcriber(&local, None).is_err());
+        local.model_path = Some("/nonexistent/ggml-base.bin".to_string());
+        assert!(create_transcriber(&local, None).is_err());
+    }
+
+    #[test]
+    fn test_cache_key_changes_with_backend_and_model() {
+        let openai = settings(TranscriptionBackend::OpenAi);
+        let other_model = TranscriptionSettings { model: Some("gpt-4o-transcribe".to_string()), ..openai.clone() };
+        let local = TranscriptionSettings { model_path: Some("/models/ggml-base.bin".to_string()), ..settings(TranscriptionBackend::Local) };
+        let other_local = TranscriptionSettings { model_path: Some("/models/ggml-small.bin".to_string()), ..local.clone() };
+
+        let keys = [openai.cache_key(), other_model.cache_key(), local.cache_key(), other_local.cache_key()];
+        for (i, key) in keys.iter().enumerate() {
+            assert!(keys[i + 1..].iter().all(|other| other != key), "duplicate cache key {}", key);
+        }
+
+        // An explicit default model is the same engine
+        let explicit = TranscriptionSettings { model: Some(DEFAULT_MODEL.to_string()), ..openai.clone() };
+        assert_eq!(explicit.cache_key(), openai.cache_key());
+    }
+}
@@ -0,0 +1,368 @@
+// ClipForge - Transcription Module
+// Full clip transcripts (text, segments, word timings) mapped to timeline time and cached per source file
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+
+use crate::chunked_transcription::transcribe_audio_file;
+use crate::diarization::diarize_response;
+use crate::filler_detection::{extract_audio_to_temp, TempFileCleanup, WhisperResponse, WhisperWord};
+use crate::transcriber::{create_transcriber, TranscriptionSettings};
+
+/// Words whose midpoint is this close to a range edge still count as inside it
+const RANGE_TOLERANCE: f64 = 0.01;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptWord {
+    pub word: String,
+    /// Timeline time in seconds
+    pub start: f64,
+    pub end: f64,
+    /// Time in the source file, for mapping edits back to clip trims
+    #[serde(rename = "sourceStart")]
+    pub source_start: f64,
+    #[serde(rename = "sourceEnd")]
+    pub source_end: f64,
+    /// Filled in by speaker diarization
+    pub speaker: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptSegment {
+    pub id: usize,
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    pub words: Vec<TranscriptWord>,
+    pub speaker: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transcript {
+    pub text: String,
+    pub segments: Vec<TranscriptSegment>,
+    /// Every word in order, flattened across segments
+    pub words: Vec<TranscriptWord>,
+    /// Hash of the source file contents, the transcript cache key
+    #[serde(rename = "sourceHash")]
+    pub source_hash: String,
+    /// Whether this result came from the cache instead of a new API call
+    pub cached: bool,
+}
+
+/// Which part of the source file a clip uses and where it sits on the timeline
+#[derive(Debug, Clone, Copy)]
+pub struct ClipRange {
+    pub source_start: f64,
+    /// None runs to the end of the file
+    pub source_end: Option<f64>,
+    pub timeline_start: f64,
+}
+
+impl ClipRange {
+    pub fn new(in_point: Option<f64>, out_point: Option<f64>, source_offset: Option<f64>, timeline_start: Option<f64>) -> Self {
+        // Split clips trim from source_offset; otherwise the trim starts at in_point
+        let source_start = source_offset.or(in_point).unwrap_or(0.0).max(0.0);
+        let source_end = out_point.map(|out| source_start + (out - in_point.unwrap_or(0.0)).max(0.0));
+
+        Self {
+            source_start,
+            source_end,
+            timeline_start: timeline_start.unwrap_or(0.0),
+        }
+    }
+
+    fn contains(&self, start: f64, end: f64) -> bool {
+        let midpoint = (start + end) / 2.0;
+        midpoint >= self.source_start - RANGE_TOLERANCE
+            && self.source_end.is_none_or(|e| midpoint <= e + RANGE_TOLERANCE)
+    }
+
+    /// Whether the whole source span lies inside the clip
+    fn covers(&self, start: f64, end: f64) -> bool {
+        start >= self.source_start - RANGE_TOLERANCE
+            && self.source_end.is_none_or(|e| end <= e + RANGE_TOLERANCE)
+    }
+
+    fn timeline_time(&self, source_time: f64) -> f64 {
+        let clamped = match self.source_end {
+            Some(end) => source_time.clamp(self.source_start, end),
+            None => source_time.max(self.source_start),
+        };
+        self.timeline_start + (clamped - self.source_start)
+    }
+
+    fn map_word(&self, word: &WhisperWord) -> TranscriptWord {
+        TranscriptWord {
+            word: word.word.trim().to_string(),
+            start: self.timeline_time(word.start),
+            end: self.timeline_time(word.end),
+            source_start: word.start,
+            source_end: word.end,
+            speaker: word.speaker.clone(),
+        }
+    }
+}
+
+/// Cut a whole-file transcript down to a clip's range and move it to timeline time
+pub fn map_transcript_to_clip(response: &WhisperResponse, range: &ClipRange, source_hash: &str) -> Transcript {
+    let mut segments = Vec::new();
+
+    for segment in response.segments.iter().flatten() {
+        let overlaps = segment.end > range.source_start
+            && range.source_end.is_none_or(|e| segment.start < e);
+        if !overlaps {
+            continue;
+        }
+
+        // Backends that only return a top-level words array still time each segment's words
+        let all_words: Vec<&WhisperWord> = match segment.words.as_deref() {
+            Some(words) if !words.is_empty() => words.iter().collect(),
+            _ => response.words.iter().flatten()
+                .filter(|w| {
+                    let midpoint = (w.start + w.end) / 2.0;
+                    midpoint >= segment.start && midpoint < segment.end
+                })
+                .collect(),
+        };
+        let words: Vec<TranscriptWord> = all_words
+            .iter()
+            .filter(|w| range.contains(w.start, w.end))
+            .map(|w| range.map_word(w))
+            .collect();
+
+        // Segments cut by the trim only keep the words inside the clip. Without word timings
+        // there is no telling which part of the text was cut, so only whole segments keep it.
+        let text = if all_words.is_empty() {
+            if range.covers(segment.start, segment.end) {
+                segment.text.trim().to_string()
+            } else {
+                String::new()
+            }
+        } else if words.len() == all_words.len() {
+            segment.text.trim().to_string()
+        } else {
+            words.iter().map(|w| w.word.as_str()).collect::<Vec<_>>().join(" ")
+        };
+        if text.is_empty() {
+            continue;
+        }
+
+        segments.push(TranscriptSegment {
+            id: segments.len(),
+            start: range.timeline_time(segment.start),
+            end: range.timeline_time(segment.end),
+            text,
+            words,
+            speaker: segment.speaker.clone(),
+        });
+    }
+
+    // Word timings may come back without segments
+    let mut words: Vec<TranscriptWord> = segments.iter().flat_map(|s| s.words.clone()).collect();
+    if words.is_empty() {
+        words = response.all_words()
+            .into_iter()
+            .filter(|w| range.contains(w.start, w.end))
+            .map(|w| range.map_word(w))
+            .collect();
+    }
+
+    let text = if segments.is_empty() {
+        words.iter().map(|w| w.word.as_str()).collect::<Vec<_>>().join(" ")
+    } else {
+        segments.iter().map(|s| s.text.as_str()).collect::<Vec<_>>().join(" ")
+    };
+
+    Transcript {
+        text,
+        segments,
+        words,
+        source_hash: source_hash.to_string(),
+        cached: false,
+    }
+}
+
+/// MD5 of the file contents, read in chunks so large videos aren't loaded into memory
+pub(crate) fn hash_file(path: &str) -> Result<String, String> {
+    let mut file = fs::File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let mut context = md5::Context::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        if read == 0 {
+            break;
+        }
+        context.consume(&buffer[..read]);
+    }
+
+    Ok(format!("{:x}", context.compute()))
+}
+
+/// Cache file for a source file transcribed by one engine; `engine` is
+/// `TranscriptionSettings::cache_key`
+fn transcript_cache_path(source_hash: &str, engine: &str) -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("ClipForge")
+        .join("transcripts")
+        .join(format!("{}-{:x}.json", source_hash, md5::compute(engine.as_bytes())))
+}
+
+/// Cached whole-file Whisper response, if this file has been transcribed by this engine before
+pub(crate) fn load_cached_transcript(source_hash: &str, engine: &str) -> Option<WhisperResponse> {
+    let content = fs::read_to_string(transcript_cache_path(source_hash, engine)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+pub(crate) fn save_cached_transcript(source_hash: &str, engine: &str, response: &WhisperResponse) {
+    let path = transcript_cache_path(source_hash, engine);
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+
+    match serde_json::to_string(response) {
+        Ok(json) => {
+            if let Err(e) = fs::write(&path, json) {
+                eprintln!("Warning: Failed to cache transcript: {}", e);
+            }
+        }
+        Err(e) => eprintln!("Warning: Failed to serialize transcript: {}", e),
+    }
+}
+
+/// Transcribe a whole source file, reusing the cached result unless `force_refresh` is set.
+/// Returns the response, the file hash and whether it came from the cache.
+pub(crate) async fn transcribe_source_file(
+    file_path: &str,
+    api_key: Option<&str>,
+    force_refresh: bool,
+) -> Result<(WhisperResponse, String, bool), String> {
+    if !Path::new(file_path).exists() {
+        return Err("Video file not found".to_string());
+    }
+
+    let path = file_path.to_string();
+    let source_hash = tokio::task::spawn_blocking(move || hash_file(&path))
+        .await
+        .map_err(|e| format!("Failed to hash source file: {}", e))??;
+
+    let settings = TranscriptionSettings::load();
+    let diarize = settings.diarization.enabled;
+    let engine = settings.cache_key();
+
+    let cached = if force_refresh { None } else { load_cached_transcript(&source_hash, &engine) };
+    if let Some(response) = &cached {
+        if !diarize || response.has_speakers() {
+            return Ok((response.clone(), source_hash, true));
+        }
+    }
+
+    // The whole file is transcribed once so every clip cut from it shares the cached result
+    let audio_path = extract_audio_to_temp(file_path, None, None).await?;
+    let _cleanup = TempFileCleanup::new(audio_path.clone());
+
+    // Transcripts cached before diarization was turned on only need the speaker labels
+    let from_cache = cached.is_some();
+    let mut response = match cached {
+        Some(response) => response,
+        None => {
+            let transcriber = create_transcriber(&settings, api_key)?;
+            transcribe_audio_file(Arc::from(transcriber), &audio_path, settings.chunk_format).await?
+        }
+    };
+    if diarize {
+        diarize_response(&mut response, &audio_path, &settings.diarization).await;
+    }
+
+    save_cached_transcript(&source_hash, &engine, &response);
+    Ok((response, source_hash, from_cache))
+}
+
+/// Full transcript of a clip with word timings in timeline time
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn transcribe_clip(
+    file_path: String,
+    api_key: Option<String>,
+    in_point: Option<f64>,
+    out_point: Option<f64>,
+    source_offset: Option<f64>,
+    timeline_start: Option<f64>,
+    force_refresh: Option<bool>,
+) -> Result<Transcript, String> {
+    let (response, source_hash, cached) =
+        transcribe_source_file(&file_path, api_key.as_deref(), force_refresh.unwrap_or(false)).await?;
+
+    let range = ClipRange::new(in_point, out_point, source_offset, timeline_start);
+    let mut transcript = map_transcript_to_clip(&response, &range, &source_hash);
+    transcript.cached = cached;
+
+    Ok(transcript)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcript_mapped_to_clip_range() {
+        let response: WhisperResponse = serde_json::from_value(serde_json::json!({
+            "text": "One two three four",
+            "segments": [{
+                "start": 0.0,
+                "end": 4.0,
+                "text": " One two three four",
+                "words": [
+                    { "word": "One", "start": 0.0, "end": 0.8 },
+                    { "word": "two", "start": 1.0, "end": 1.8 },
+                    { "word": "three", "start": 2.0, "end": 2.8 },
+                    { "word": "four", "start": 3.0, "end": 3.8 }
+                ]
+            }]
+        })).unwrap();
+
+        // Clip uses source 1.0..3.0 and sits at 10s on the timeline
+        let range = ClipRange::new(Some(1.0), Some(3.0), None, Some(10.0));
+        let transcript = map_transcript_to_clip(&response, &range, "hash");
+
+        assert_eq!(transcript.text, "two three");
+        assert_eq!(transcript.words.len(), 2);
+        assert_eq!((transcript.words[0].start, transcript.words[0].source_start), (10.0, 1.0));
+        assert_eq!((transcript.segments[0].start, transcript.segments[0].end), (10.0, 12.0));
+    }
+
+    #[test]
+    fn test_trimmed_segments_without_nested_words() {
+        let response: WhisperResponse = serde_json::from_value(serde_json::json!({
+            "text": "One two three four five",
+            "words": [
+                { "word": "One", "start": 0.0, "end": 0.8 },
+                { "word": "two", "start": 1.0, "end": 1.8 },
+                { "word": "three", "start": 2.0, "end": 2.8 },
+                { "word": "four", "start": 3.0, "end": 3.8 }
+            ],
+            "segments": [
+                { "start": 0.0, "end": 2.0, "text": " One two" },
+                { "start": 2.0, "end": 4.0, "text": " three four" },
+                { "start": 4.0, "end": 6.0, "text": " five" }
+            ]
+        })).unwrap();
+
+        // Source 1.0..5.0: the first segment is cut using the top-level words, and the untimed
+        // last segment is dropped rather than kept whole
+        let range = ClipRange::new(Some(1.0), Some(5.0), None, None);
+        let transcript = map_transcript_to_clip(&response, &range, "hash");
+
+        let texts: Vec<&str> = transcript.segments.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, ["two", "three four"]);
+        assert_eq!(transcript.text, "two three four");
+        assert_eq!(transcript.words.len(), 3);
+    }
+}
@@ -0,0 +1,161 @@
+// ClipForge - Transitions Module
+// Joins a track's clips end to end, cross-fading with xfade/acrossfade where a clip has a transition
+
+use serde::{Deserialize, Serialize};
+
+use crate::export::ClipData;
+use crate::export_settings::ExportSettings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TransitionKind {
+    #[serde(rename = "fade")]
+    Fade,
+    #[serde(rename = "dissolve")]
+    Dissolve,
+    #[serde(rename = "wipeLeft")]
+    WipeLeft,
+    #[serde(rename = "wipeRight")]
+    WipeRight,
+    #[serde(rename = "slideLeft")]
+    SlideLeft,
+    #[serde(rename = "slideRight")]
+    SlideRight,
+    #[serde(rename = "fadeThroughBlack")]
+    FadeThroughBlack,
+}
+
+impl TransitionKind {
+    /// Transition name understood by FFmpeg's xfade filter
+    fn xfade_name(&self) -> &'static str {
+        match self {
+            TransitionKind::Fade => "fade",
+            TransitionKind::Dissolve => "dissolve",
+            TransitionKind::WipeLeft => "wipeleft",
+            TransitionKind::WipeRight => "wiperight",
+            TransitionKind::SlideLeft => "slideleft",
+            TransitionKind::SlideRight => "slideright",
+            TransitionKind::FadeThroughBlack => "fadeblack",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Transition {
+    #[serde(rename = "type")]
+    pub kind: TransitionKind,
+    /// Overlap between the two clips in seconds
+    pub duration: f64,
+}
+
+/// A clip's filtered video and audio streams, ready to be joined onto a track
+pub(crate) struct TrackSegment {
+    pub(crate) video: String,
+    pub(crate) audio: String,
+    pub(crate) duration: f64,
+    pub(crate) transition: Option<Transition>,
+}
+
+/// xfade needs matching frame rate, pixel format and timebase on both inputs, so clips are
+/// normalized when the track has any transitions. Returns an empty string otherwise.
+pub(crate) fn transition_normalize_filter(clips: &[ClipData], settings: &ExportSettings) -> String {
+    if clips.iter().any(|c| c.transition.is_some()) {
+        format!(",fps={},format=yuv420p,settb=AVTB", settings.frame_rate.unwrap_or(30.0))
+    } else {
+        String::new()
+    }
+}
+
+/// Join a track's segments into `out_video`/`out_audio`, using xfade/acrossfade where a clip has
+/// a transition into the next one. Returns the filter parts and the joined duration; each
+/// transition overlaps two clips, so it shortens the track by its own duration.
+///
+/// Video and audio are joined as separate chains: with J/L cuts a clip's audio is not the same
+/// length as its picture, and a combined concat would pad the shorter stream of each segment.
+pub(crate) fn build_track_join(
+    segments: &[TrackSegment],
+    label: &str,
+    out_video: &str,
+    out_audio: &str,
+) -> (Vec<String>, f64) {
+    let mut parts = Vec::new();
+    
+    if segments.is_empty() {
+        return (parts, 0.0);
+    }
+    
+    // No transitions: one concat per stream keeps the graph simple
+    if segments[..segments.len() - 1].iter().all(|s| s.transition.is_none()) {
+        let videos: String = segments.iter().map(|s| s.video.as_str()).collect();
+        let audios: String = segments.iter().map(|s| s.audio.as_str()).collect();
+        parts.push(format!("{}concat=n={}:v=1:a=0{}", videos, segments.len(), out_video));
+        parts.push(format!("{}concat=n={}:v=0:a=1{}", audios, segments.len(), out_audio));
+        return (parts, segments.iter().map(|s| s.duration).sum());
+    }
+    
+    let mut video = segments[0].video.clone();
+    let mut audio = segments[0].audio.clone();
+    let mut duration = segments[0].duration;
+    // Part of the previous clip already used by its incoming transition
+    let mut incoming_overlap = 0.0;
+    
+    for (i, pair) in segments.windows(2).enumerate() {
+        let (prev, next) = (&pair[0], &pair[1]);
+        let is_last = i == segments.len() - 2;
+        let video_out = if is_last { out_video.to_string() } else { format!("[{}_v{}]", label, i) };
+        let audio_out = if is_last { out_audio.to_string() } else { format!("[{}_a{}]", label, i) };
+        
+        // A transition can't be longer than what's left of either clip
+        let overlap = prev.transition.as_ref()
+            .map(|t| t.duration.min(prev.duration - incoming_overlap).min(next.duration))
+            .filter(|d| *d > 0.0);
+        
+        match (prev.transition.as_ref(), overlap) {
+            (Some(transition), Some(overlap)) => {
+                parts.push(format!("{}{}xfade=transition={}:duration={}:offset={}{}", 
+                    video, next.video, transition.kind.xfade_name(), overlap, duration - overlap, video_out));
+                parts.push(format!("{}{}acrossfade=d={}{}", audio, next.audio, overlap, audio_out));
+                duration += next.duration - overlap;
+                incoming_overlap = overlap;
+            }
+            _ => {
+                parts.push(format!("{}{}concat=n=2:v=1:a=0{}", video, next.video, video_out));
+                parts.push(format!("{}{}concat=n=2:v=0:a=1{}", audio, next.audio, audio_out));
+                duration += next.duration;
+                incoming_overlap = 0.0;
+            }
+        }
+        
+        video = video_out;
+        audio = audio_out;
+    }
+    
+    (parts, duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transition_shortens_track() {
+        let segment = |i: usize, duration: f64, transition: Option<Transition>| TrackSegment {
+            video: format!("[v{}]", i),
+            audio: format!("[a{}]", i),
+            duration,
+            transition,
+        };
+        let fade = |duration: f64| Some(Transition { kind: TransitionKind::Fade, duration });
+        
+        let (parts, duration) = build_track_join(
+            &[segment(0, 5.0, fade(1.0)), segment(1, 4.0, None), segment(2, 3.0, None)],
+            "t",
+            "[outv]",
+            "[outa]",
+        );
+        assert_eq!(duration, 11.0);
+        assert_eq!(parts[0], "[v0][v1]xfade=transition=fade:duration=1:offset=4[t_v0]");
+        assert_eq!(parts[1], "[a0][a1]acrossfade=d=1[t_a0]");
+        assert_eq!(parts[2], "[t_v0][v2]concat=n=2:v=1:a=0[outv]");
+        assert_eq!(parts[3], "[t_a0][a2]concat=n=2:v=0:a=1[outa]");
+    }
+}